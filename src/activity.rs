@@ -0,0 +1,28 @@
+use std::{
+    collections::VecDeque,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use crate::{last_input_time, State};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const SAMPLE_COUNT: usize = 120;
+
+pub fn activity_thread(state: Arc<State>) -> ! {
+    let mut samples = VecDeque::with_capacity(SAMPLE_COUNT + 1);
+    let mut last_input = 0;
+    loop {
+        std::thread::sleep(SAMPLE_INTERVAL);
+        let input = last_input_time(&state).unwrap_or_default();
+        samples.push_back(input != last_input);
+        last_input = input;
+        if samples.len() > SAMPLE_COUNT {
+            samples.pop_front();
+        }
+        let active = samples.iter().filter(|&&active| active).count();
+        state
+            .activity
+            .store((active * 1000 / SAMPLE_COUNT) as u32, Ordering::Relaxed);
+    }
+}
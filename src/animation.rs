@@ -0,0 +1,115 @@
+use anyhow::{bail, Result};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::FilterType,
+    Delay, Frame, RgbaImage,
+};
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+use tracing::{info, warn};
+
+use crate::{imaging, index::Index, notification, timelapse, ByteSize, Config};
+
+const MIN_DIMENSION: u32 = 64;
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationConfig {
+    pub every: Option<usize>,
+    pub max_frames: usize,
+    pub frame_delay_ms: u32,
+    pub max_dimension: u32,
+    pub max_size: ByteSize,
+    pub on_session_end: bool,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            every: None,
+            max_frames: 30,
+            frame_delay_ms: 500,
+            max_dimension: 480,
+            max_size: ByteSize(8 << 20),
+            on_session_end: false,
+        }
+    }
+}
+
+impl AnimationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.every == Some(0) {
+            bail!("animation.every must be at least 1");
+        }
+        if self.max_frames == 0 {
+            bail!("animation.max_frames must be at least 1");
+        }
+        if self.max_dimension < MIN_DIMENSION {
+            bail!("animation.max_dimension must be at least {MIN_DIMENSION} pixels");
+        }
+        Ok(())
+    }
+}
+
+fn encode(frames: &[image::RgbImage], max_dimension: u32, delay_ms: u32) -> Result<Vec<u8>> {
+    let first = imaging::downscale(&frames[0], max_dimension).unwrap_or_else(|| frames[0].clone());
+    let (width, height) = first.dimensions();
+    let mut data = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut data, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in frames {
+            let rgba: RgbaImage = image::DynamicImage::ImageRgb8(frame.clone()).into_rgba8();
+            let resized = image::imageops::resize(&rgba, width, height, FilterType::Triangle);
+            encoder.encode_frame(Frame::from_parts(
+                resized,
+                0,
+                0,
+                Delay::from_numer_denom_ms(delay_ms, 1),
+            ))?;
+        }
+    }
+    Ok(data)
+}
+
+pub fn make_animation(config: &Config, game: &str, session_id: &str) -> Result<PathBuf> {
+    let settings = &config.animation;
+    let files = Index::open(&config.target_folder)?.session_screenshot_files(game, session_id)?;
+    if files.is_empty() {
+        bail!("Session {session_id} of {game} has no screenshots");
+    }
+    let every = settings
+        .every
+        .unwrap_or_else(|| files.len().div_ceil(settings.max_frames));
+    let frames = files
+        .iter()
+        .step_by(every.max(1))
+        .take(settings.max_frames)
+        .map(|path| Ok(image::open(path)?.into_rgb8()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut max_dimension = settings.max_dimension;
+    let data = loop {
+        let data = encode(&frames, max_dimension, settings.frame_delay_ms)?;
+        if data.len() as u64 <= settings.max_size.0 || max_dimension <= MIN_DIMENSION {
+            break data;
+        }
+        max_dimension = (max_dimension * 3 / 4).max(MIN_DIMENSION);
+    };
+    let output = timelapse::output_path(&config.target_folder, game, session_id, "gif");
+    fs::write(&output, data)?;
+    info!(%game, session = session_id, path = ?output, frames = frames.len(), "Created animation");
+    Ok(output)
+}
+
+pub fn make_animation_in_background(config: Config, game: String, session_id: String) {
+    std::thread::spawn(move || match make_animation(&config, &game, &session_id) {
+        Ok(path) => {
+            if config.notifications {
+                let text = format!("Animation of {game} saved to {}", path.display());
+                let _ = notification::show_toast("autogamejournal", &text);
+            }
+        }
+        Err(e) => warn!(%game, session = %session_id, "Could not create animation: {e:?}"),
+    });
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{Local, NaiveDate};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{atomic::Ordering, Arc};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+use crate::{
+    index::{Index, SessionFilter},
+    metrics, snooze, snoozed_until, Command, State,
+};
+
+fn status(state: &State) -> Value {
+    let config = state.config.read().unwrap();
+    let mut status = state.status.lock().unwrap();
+    status.roll_over();
+    json!({
+        "paused": state.paused.load(Ordering::Relaxed),
+        "snoozed_until": snoozed_until(state),
+        "quiet_hours": config.schedule.is_quiet(Local::now()),
+        "game": status.game,
+        "playtime_seconds": status.game.as_ref().map(|_| status.playtime.as_secs()),
+        "last_capture": status.last_capture.map(|(time, success)| json!({
+            "time": time,
+            "success": success,
+        })),
+        "screenshots_today": status.screenshots_today,
+    })
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date {date:?}, expected YYYY-MM-DD"))
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn sessions(state: &State, query: Option<&str>) -> Result<Value> {
+    let param = |name: &str| {
+        query.and_then(|q| {
+            q.split('&')
+                .find_map(|p| p.strip_prefix(name)?.strip_prefix('='))
+                .map(decode)
+        })
+    };
+    let game = param("game");
+    let mut filter = SessionFilter {
+        game: game.as_deref(),
+        from: param("from").as_deref().map(parse_date).transpose()?,
+        to: param("to").as_deref().map(parse_date).transpose()?,
+    };
+    if let Some(date) = param("date") {
+        filter.from = Some(parse_date(&date)?);
+        filter.to = filter.from;
+    } else if filter == SessionFilter::default() {
+        filter.from = Some(Local::now().date_naive());
+        filter.to = filter.from;
+    }
+    let target_folder = state.config.read().unwrap().target_folder.clone();
+    let sessions = Index::open(&target_folder)?.sessions(&filter)?;
+    Ok(sessions
+        .into_iter()
+        .map(|session| {
+            json!({
+                "id": session.id,
+                "game": session.game,
+                "start": session.start,
+                "end": session.end,
+                "played_seconds": session.played.as_secs(),
+                "screenshot_count": session.screenshot_count,
+            })
+        })
+        .collect())
+}
+
+fn route(state: &State, method: &Method, url: &str) -> Result<(u16, Value)> {
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    };
+    match (method, path) {
+        (Method::Get, "/status") => Ok((200, status(state))),
+        (Method::Get, "/sessions") => match sessions(state, query) {
+            Ok(sessions) => Ok((200, sessions)),
+            Err(e) => Ok((400, json!({ "error": format!("{e:#}") }))),
+        },
+        (Method::Post, "/capture") => {
+            state.commands.send(Command::CaptureLastGame)?;
+            Ok((202, json!({ "queued": true })))
+        }
+        (Method::Post, "/pause") => {
+            state.paused.store(true, Ordering::Relaxed);
+            Ok((200, status(state)))
+        }
+        (Method::Post, "/resume") => {
+            state.paused.store(false, Ordering::Relaxed);
+            snooze(state, None);
+            Ok((200, status(state)))
+        }
+        _ => Ok((404, json!({ "error": "Not found" }))),
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Compares hashes rather than the tokens themselves, so how long the comparison takes says
+/// nothing about how much of the token was right.
+fn token_matches(given: &str, token: &str) -> bool {
+    Sha256::digest(given.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+/// Rejects requests from web pages, which browsers mark with an `Origin` or send with a
+/// foreign `Host` after DNS rebinding, and requests without the configured token.
+fn check_access(state: &State, request: &Request, port: u16) -> Result<(), (u16, &'static str)> {
+    let local = |value: &str| {
+        [format!("127.0.0.1:{port}"), format!("localhost:{port}")]
+            .iter()
+            .any(|host| value.eq_ignore_ascii_case(host))
+    };
+    if header(request, "Origin")
+        .is_some_and(|origin| !origin.strip_prefix("http://").is_some_and(local))
+    {
+        return Err((403, "Cross-origin requests are not allowed"));
+    }
+    if header(request, "Host").is_some_and(|host| !local(host)) {
+        return Err((403, "Unexpected Host header"));
+    }
+    if let Some(token) = &state.config.read().unwrap().api_token {
+        let given =
+            header(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| token_matches(given, token)) {
+            return Err((401, "Missing or wrong API token"));
+        }
+    }
+    Ok(())
+}
+
+fn respond(state: &State, request: Request, port: u16) {
+    if let Err((code, error)) = check_access(state, &request, port) {
+        warn!(url = request.url(), "Rejected API request: {error}");
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(json!({ "error": error }).to_string())
+            .with_status_code(code)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            warn!("Could not send API response: {e:?}");
+        }
+        return;
+    }
+    if request.method() == &Method::Get && request.url() == "/metrics" {
+        let header =
+            Header::from_bytes("Content-Type", "text/plain; version=0.0.4; charset=utf-8").unwrap();
+        let response = Response::from_string(metrics::render(state)).with_header(header);
+        if let Err(e) = request.respond(response) {
+            warn!("Could not send API response: {e:?}");
+        }
+        return;
+    }
+    let (code, body) = route(state, request.method(), request.url()).unwrap_or_else(|e| {
+        warn!("API request failed: {e:?}");
+        (500, json!({ "error": format!("{e:#}") }))
+    });
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body.to_string())
+        .with_status_code(code)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        warn!("Could not send API response: {e:?}");
+    }
+}
+
+pub fn api_thread(state: Arc<State>, port: u16) -> Result<()> {
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| anyhow!(e))
+        .with_context(|| format!("Listening on port {port}"))?;
+    info!("HTTP API listening on http://127.0.0.1:{port}");
+    for request in server.incoming_requests() {
+        respond(&state, request, port);
+    }
+    Ok(())
+}
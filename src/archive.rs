@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, Months};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{imaging, maintenance, Config};
+
+fn entry_name(game_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(game_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn write_archive(game_dir: &Path, archive: &Path, paths: &[PathBuf]) -> Result<()> {
+    let temp = archive.with_extension("zip.tmp");
+    let mut writer = ZipWriter::new(File::create(&temp)?);
+    let mut names = HashSet::new();
+    if archive.exists() {
+        let mut existing = ZipArchive::new(File::open(archive)?)
+            .with_context(|| format!("Reading existing archive {archive:?}"))?;
+        for i in 0..existing.len() {
+            let file = existing.by_index_raw(i)?;
+            names.insert(file.name()?.into_owned());
+            writer.raw_copy_file(file)?;
+        }
+    }
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for path in paths {
+        let name = entry_name(game_dir, path);
+        if names.contains(&name) {
+            continue;
+        }
+        writer.start_file(name, options)?;
+        io::copy(&mut File::open(path)?, &mut writer)?;
+    }
+    writer.finish()?;
+    fs::rename(&temp, archive)?;
+    Ok(())
+}
+
+fn remove_empty_dirs(game_dir: &Path, path: &Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == game_dir || !dir.starts_with(game_dir) {
+            break;
+        }
+        let _ = fs::remove_dir(dir.join(imaging::THUMBNAIL_DIR));
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+pub fn archive_old_screenshots(config: &Config) -> Result<()> {
+    let Some(months) = config.archive_after_months.filter(|&m| m > 0) else {
+        return Ok(());
+    };
+    let today = Local::now().date_naive();
+    let cutoff = today.with_day(1).unwrap_or(today) - Months::new(months);
+
+    let (screenshots, _) = maintenance::list_screenshots(&config.target_folder)?;
+    let mut groups: BTreeMap<(String, String), Vec<PathBuf>> = BTreeMap::new();
+    for screenshot in screenshots {
+        let day = DateTime::<Local>::from(screenshot.modified).date_naive();
+        if day < cutoff {
+            let month = day.format("%Y-%m").to_string();
+            groups
+                .entry((screenshot.game, month))
+                .or_default()
+                .push(screenshot.path);
+        }
+    }
+
+    let index = maintenance::open_index(&config.target_folder);
+    for ((game, month), paths) in groups {
+        let game_dir = config.target_folder.join(&game);
+        let archive = game_dir.join(format!("{month}.zip"));
+        if let Err(e) = write_archive(&game_dir, &archive, &paths) {
+            warn!(%game, %month, "Could not archive screenshots: {e:?}");
+            continue;
+        }
+        for path in &paths {
+            if let Err(e) = fs::remove_file(path) {
+                warn!(?path, "Could not remove archived screenshot: {e:?}");
+                continue;
+            }
+            let _ = fs::remove_file(imaging::thumbnail_path(path));
+            if let Some(index) = &index {
+                if let Err(e) = index.archive_screenshot(path, &archive) {
+                    warn!(?path, "Could not update index: {e:?}");
+                }
+            }
+            remove_empty_dirs(&game_dir, path);
+        }
+        info!(%game, %month, count = paths.len(), "Archived screenshots");
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+#[cfg(not(windows))]
+use std::path::PathBuf;
+#[cfg(windows)]
+use winsafe::{co, prelude::*, RegistryValue, HKEY};
+
+#[cfg(target_os = "linux")]
+use crate::platform;
+
+#[cfg(windows)]
+const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+#[cfg(windows)]
+const VALUE_NAME: &str = "autogamejournal";
+
+#[cfg(any(windows, target_os = "linux"))]
+fn command() -> Result<String> {
+    let exe = std::env::current_exe().context("Getting executable path")?;
+    Ok(format!("\"{}\" --autostart", exe.display()))
+}
+
+#[cfg(windows)]
+pub fn is_enabled() -> bool {
+    match HKEY::CURRENT_USER.RegGetValue(Some(RUN_KEY), Some(VALUE_NAME)) {
+        Ok(RegistryValue::Sz(value)) => command().is_ok_and(|c| c == value),
+        _ => false,
+    }
+}
+
+#[cfg(windows)]
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    if enabled {
+        HKEY::CURRENT_USER
+            .RegSetKeyValue(
+                Some(RUN_KEY),
+                Some(VALUE_NAME),
+                RegistryValue::Sz(command()?),
+            )
+            .context("Writing autostart registry value")?;
+    } else {
+        let key = HKEY::CURRENT_USER
+            .RegOpenKeyEx(Some(RUN_KEY), co::REG_OPTION::NoValue, co::KEY::SET_VALUE)
+            .context("Opening autostart registry key")?;
+        match key.RegDeleteValue(Some(VALUE_NAME)) {
+            Ok(()) | Err(co::ERROR::FILE_NOT_FOUND) => {}
+            Err(e) => return Err(e).context("Deleting autostart registry value"),
+        }
+    }
+    Ok(())
+}
+
+/// An XDG autostart entry, which desktop environments start on login.
+#[cfg(target_os = "linux")]
+fn desktop_file() -> Result<PathBuf> {
+    Ok(platform::config_dir()
+        .context("No config folder")?
+        .join("autostart")
+        .join("autogamejournal.desktop"))
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_enabled() -> bool {
+    let Ok(text) = desktop_file().and_then(|path| Ok(std::fs::read_to_string(path)?)) else {
+        return false;
+    };
+    command().is_ok_and(|command| text.lines().any(|line| line == format!("Exec={command}")))
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let path = desktop_file()?;
+    if enabled {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=autogamejournal\nExec={}\nX-GNOME-Autostart-enabled=true\n",
+            command()?
+        );
+        std::fs::write(&path, entry).context("Writing autostart entry")?;
+    } else {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Deleting autostart entry"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+const LAUNCH_AGENT: &str = "com.autogamejournal";
+
+/// A per-user launch agent, which launchd starts on login.
+#[cfg(target_os = "macos")]
+fn launch_agent() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("No home folder")?;
+    Ok(PathBuf::from(home)
+        .join("Library/LaunchAgents")
+        .join(format!("{LAUNCH_AGENT}.plist")))
+}
+
+#[cfg(target_os = "macos")]
+fn executable() -> Result<String> {
+    let exe = std::env::current_exe().context("Getting executable path")?;
+    Ok(exe
+        .display()
+        .to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;"))
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_enabled() -> bool {
+    let Ok(text) = launch_agent().and_then(|path| Ok(std::fs::read_to_string(path)?)) else {
+        return false;
+    };
+    executable().is_ok_and(|exe| text.contains(&format!("<string>{exe}</string>")))
+}
+
+#[cfg(target_os = "macos")]
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let path = launch_agent()?;
+    if enabled {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let agent = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\
+             <key>Label</key>\n<string>{LAUNCH_AGENT}</string>\n\
+             <key>ProgramArguments</key>\n<array>\n<string>{}</string>\n<string>--autostart</string>\n</array>\n\
+             <key>RunAtLoad</key>\n<true/>\n\
+             </dict>\n</plist>\n",
+            executable()?
+        );
+        std::fs::write(&path, agent).context("Writing launch agent")?;
+    } else {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Deleting launch agent"),
+        }
+    }
+    Ok(())
+}
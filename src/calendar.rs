@@ -0,0 +1,222 @@
+use anyhow::Result;
+use chrono::{Datelike, Days, Local, NaiveDate, Timelike};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    format_duration, imaging,
+    index::{Index, SessionFilter, SessionRow},
+};
+
+const WEEKS: u64 = 53;
+const LEVEL_COLORS: [&str; 5] = ["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"];
+
+const STYLE: &str = "
+body { font-family: 'Segoe UI', sans-serif; margin: 2em; color: #24292f; }
+.heatmap { display: grid; grid-template-rows: repeat(7, 12px); grid-auto-flow: column; grid-auto-columns: 12px; gap: 3px; }
+.heatmap span, .heatmap a { border-radius: 2px; }
+.timeline { position: relative; height: 28px; background: repeating-linear-gradient(90deg, #f6f8fa 0, #f6f8fa calc(100% / 24 - 1px), #d0d7de calc(100% / 24 - 1px), #d0d7de calc(100% / 24)); border: 1px solid #d0d7de; }
+.timeline a { position: absolute; top: 3px; bottom: 3px; min-width: 3px; border-radius: 3px; }
+.hours { display: flex; justify-content: space-between; font-size: 11px; color: #57606a; }
+img { margin: 2px; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A relative URL for a screenshot path, escaped to go in an attribute.
+fn href(path: &str) -> String {
+    escape(
+        &path
+            .replace('%', "%25")
+            .replace(' ', "%20")
+            .replace('#', "%23")
+            .replace('?', "%3F")
+            .replace('\'', "%27"),
+    )
+}
+
+fn level(played: Duration) -> usize {
+    match played.as_secs() / 60 {
+        0 => 0,
+        1..=29 => 1,
+        30..=59 => 2,
+        60..=119 => 3,
+        _ => 4,
+    }
+}
+
+fn game_color(game: &str) -> String {
+    let hue = game.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte.into())
+    }) % 360;
+    format!("hsl({hue}, 60%, 55%)")
+}
+
+fn percent_of_day(seconds: i64) -> f64 {
+    seconds.clamp(0, 86400) as f64 / 864.0
+}
+
+fn heatmap(
+    text: &mut String,
+    first: NaiveDate,
+    today: NaiveDate,
+    playtime: &BTreeMap<NaiveDate, Duration>,
+) -> Result<()> {
+    writeln!(text, "<div class=\"heatmap\">")?;
+    for day in first.iter_days().take_while(|day| *day <= today) {
+        let played = playtime.get(&day).copied().unwrap_or_default();
+        let color = LEVEL_COLORS[level(played)];
+        if played.is_zero() {
+            writeln!(
+                text,
+                "<span title=\"{day}\" style=\"background: {color}\"></span>"
+            )?;
+        } else {
+            writeln!(
+                text,
+                "<a href=\"#{day}\" title=\"{day}: {}\" style=\"background: {color}\"></a>",
+                format_duration(played)
+            )?;
+        }
+    }
+    writeln!(text, "</div>")?;
+    Ok(())
+}
+
+fn day_section(
+    text: &mut String,
+    index: &Index,
+    target_folder: &Path,
+    day: NaiveDate,
+    played: Duration,
+    sessions: &[&SessionRow],
+    anchors: &mut usize,
+) -> Result<()> {
+    writeln!(
+        text,
+        "<h2 id=\"{day}\">{} – {}</h2>",
+        day.format("%A %-d %B %Y"),
+        format_duration(played)
+    )?;
+    writeln!(text, "<div class=\"timeline\">")?;
+    let first_anchor = *anchors;
+    for (i, session) in sessions.iter().enumerate() {
+        let start = session.start.num_seconds_from_midnight() as i64;
+        let end = start + (session.end - session.start).num_seconds();
+        let left = percent_of_day(start);
+        let width = percent_of_day(end) - left;
+        writeln!(
+            text,
+            "<a href=\"#s{}\" title=\"{} {}–{}\" style=\"left: {left:.2}%; width: {width:.2}%; background: {}\"></a>",
+            first_anchor + i,
+            escape(&session.game),
+            session.start.format("%H:%M"),
+            session.end.format("%H:%M"),
+            game_color(&session.game),
+        )?;
+    }
+    writeln!(text, "</div>")?;
+    writeln!(text, "<div class=\"hours\"><span>0:00</span><span>6:00</span><span>12:00</span><span>18:00</span><span>24:00</span></div>")?;
+
+    for session in sessions {
+        writeln!(
+            text,
+            "<h3 id=\"s{}\">{} {}–{} ({} played, {} screenshots)</h3>",
+            *anchors,
+            escape(&session.game),
+            session.start.format("%H:%M"),
+            session.end.format("%H:%M"),
+            format_duration(session.played),
+            session.screenshot_count,
+        )?;
+        *anchors += 1;
+        for path in index.session_screenshots(&session.game, &session.id)? {
+            let thumbnail = imaging::thumbnail_path(Path::new(&path));
+            let thumbnail = if target_folder.join(&thumbnail).exists() {
+                thumbnail.to_string_lossy().replace('\\', "/")
+            } else {
+                path.clone()
+            };
+            writeln!(
+                text,
+                "<a href=\"{}\"><img src=\"{}\" width=\"240\" loading=\"lazy\"></a>",
+                href(&path),
+                href(&thumbnail)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `calendar.html` to the journal folder, with a heatmap of the last year of play
+/// and a timeline of each day's sessions linking to their screenshots.
+pub fn write_calendar(target_folder: &Path) -> Result<PathBuf> {
+    let index = Index::open(target_folder)?;
+    let today = Local::now().date_naive();
+    let first = today - Days::new(WEEKS * 7);
+    let first = first - Days::new(first.weekday().num_days_from_monday().into());
+    let playtime: BTreeMap<_, _> = index.daily_playtime(first)?.into_iter().collect();
+    let sessions = index.sessions(&SessionFilter {
+        from: Some(first),
+        ..Default::default()
+    })?;
+    let mut days: BTreeMap<NaiveDate, Vec<&SessionRow>> =
+        playtime.keys().map(|day| (*day, Vec::new())).collect();
+    for session in &sessions {
+        days.entry(session.start.date_naive())
+            .or_default()
+            .push(session);
+    }
+
+    let mut text = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Play calendar</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>Play calendar</h1>\n"
+    );
+    heatmap(&mut text, first, today, &playtime)?;
+    if days.is_empty() {
+        text += "<p>No games played this year.</p>\n";
+    }
+    let mut anchors = 0;
+    for (day, sessions) in days.iter().rev() {
+        let played = playtime
+            .get(day)
+            .copied()
+            .unwrap_or_else(|| sessions.iter().map(|s| s.played).sum());
+        day_section(
+            &mut text,
+            &index,
+            target_folder,
+            *day,
+            played,
+            sessions,
+            &mut anchors,
+        )?;
+    }
+    text += "</body>\n</html>\n";
+
+    let path = target_folder.join("calendar.html");
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hrefs_are_attribute_safe() {
+        assert_eq!(
+            href("Tom & Jerry's #1/100% done?.jpg"),
+            "Tom%20&amp;%20Jerry%27s%20%231/100%25%20done%3F.jpg"
+        );
+    }
+}
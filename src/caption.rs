@@ -0,0 +1,84 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use image::{Rgb, RgbImage};
+use serde::Deserialize;
+
+use crate::{format_duration, imaging, session::Session};
+
+const TEXT_COLOR: Rgb<u8> = Rgb([240, 240, 240]);
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptionConfig {
+    pub enabled: bool,
+    pub corner: Corner,
+    pub size: f32,
+}
+
+impl Default for CaptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            corner: Corner::default(),
+            size: 2.5,
+        }
+    }
+}
+
+impl CaptionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.5..=20.0).contains(&self.size) {
+            bail!("caption.size must be between 0.5 and 20 percent of the image height");
+        }
+        Ok(())
+    }
+}
+
+pub fn text(game: &str, time: DateTime<Local>, session: Option<&Session>) -> String {
+    let mut text = format!("{game} \u{00b7} {}", time.format("%Y-%m-%d %H:%M"));
+    if let Some(session) = session {
+        let elapsed = (time - session.start).to_std().unwrap_or_default();
+        text += &format!(" \u{00b7} {} into session", format_duration(elapsed));
+    }
+    text
+}
+
+pub fn stamp(image: &mut RgbImage, config: &CaptionConfig, text: &str) {
+    let (width, height) = image.dimensions();
+    let size = (height as f32 * config.size / 100.0).max(8.0);
+    let padding = (size / 3.0) as u32;
+    let box_width = (imaging::text_width(text, size) as u32 + padding * 2).min(width);
+    let box_height = (size as u32 + padding * 2).min(height);
+    let x = match config.corner {
+        Corner::TopLeft | Corner::BottomLeft => 0,
+        Corner::TopRight | Corner::BottomRight => width - box_width,
+    };
+    let y = match config.corner {
+        Corner::TopLeft | Corner::TopRight => 0,
+        Corner::BottomLeft | Corner::BottomRight => height - box_height,
+    };
+    for py in y..y + box_height {
+        for px in x..x + box_width {
+            let pixel = image.get_pixel_mut(px, py);
+            pixel.0 = pixel.0.map(|channel| channel / 3);
+        }
+    }
+    imaging::draw_text(
+        image,
+        text,
+        (x + padding) as i32,
+        (y + padding) as i32,
+        size,
+        TEXT_COLOR,
+    );
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use image::RgbImage;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::{imaging, region, CaptureBackend, CaptureSettings};
+
+pub const BURST_INTERVAL: Duration = Duration::from_millis(150);
+
+pub struct Frame {
+    pub image: RgbImage,
+    pub method: CaptureBackend,
+}
+
+pub fn capture_window(id: u32, settings: &CaptureSettings) -> Result<Frame> {
+    let (image, method) = capture_image(id, settings)?;
+    let image = match &settings.crop {
+        Some(crop) => region::crop(image, crop),
+        None => image,
+    };
+    Ok(Frame { image, method })
+}
+
+#[cfg(windows)]
+fn capture_image(id: u32, settings: &CaptureSettings) -> Result<(RgbImage, CaptureBackend)> {
+    use crate::{duplication, gdi, graphics_capture};
+    use anyhow::Context;
+    use tracing::warn;
+    let captured = match settings.backend {
+        CaptureBackend::GraphicsCapture => graphics_capture::capture(id, settings),
+        CaptureBackend::Duplication => duplication::capture(id, settings.mode),
+        CaptureBackend::Gdi => gdi::capture(id, settings.mode),
+    };
+    match captured {
+        Ok(image) => Ok((image, settings.backend)),
+        Err(e) if !matches!(settings.backend, CaptureBackend::Gdi) => {
+            warn!(
+                "{} capture failed, falling back to GDI: {e:?}",
+                settings.backend.name()
+            );
+            let image = gdi::capture(id, settings.mode).context("GDI capture")?;
+            Ok((image, CaptureBackend::Gdi))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The Windows capture backends don't exist here, so `capture_backend` is ignored.
+#[cfg(not(windows))]
+fn capture_image(id: u32, settings: &CaptureSettings) -> Result<(RgbImage, CaptureBackend)> {
+    crate::platform::capture(id, settings)
+}
+
+/// Captures windows. The screenshot path goes through this so it can be tested without a desktop.
+pub trait Capturer {
+    fn capture(&self, id: u32, settings: &CaptureSettings) -> Result<Frame>;
+
+    /// Captures `burst_frames` frames and keeps the sharpest.
+    fn capture_burst(&self, id: u32, settings: &CaptureSettings) -> Result<Frame> {
+        let mut best = self.capture(id, settings)?;
+        if settings.burst_frames <= 1 {
+            return Ok(best);
+        }
+        let mut best_sharpness = imaging::sharpness(&best.image);
+        for _ in 1..settings.burst_frames {
+            std::thread::sleep(BURST_INTERVAL);
+            let frame = match self.capture(id, settings) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    debug!("Could not capture burst frame: {e:?}");
+                    break;
+                }
+            };
+            let sharpness = imaging::sharpness(&frame.image);
+            if sharpness > best_sharpness {
+                best = frame;
+                best_sharpness = sharpness;
+            }
+        }
+        Ok(best)
+    }
+}
+
+/// Captures real windows with the configured backend.
+pub struct DesktopCapturer;
+
+impl Capturer for DesktopCapturer {
+    fn capture(&self, id: u32, settings: &CaptureSettings) -> Result<Frame> {
+        capture_window(id, settings)
+    }
+}
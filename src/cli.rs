@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use clap::{Args, Subcommand};
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    animation, calendar,
+    capture::capture_window,
+    contact_sheet, discord, encoder, format_duration, get_valid_window, imaging,
+    index::{Index, SessionFilter, SessionRow},
+    maintenance, open_file,
+    platform::{DesktopWindow, WindowInfo},
+    rules::describe,
+    screenshot::store_screenshot,
+    session, timelapse, upload, webhook, Config,
+};
+
+/// Picks the session the timelapse, GIF and contact sheet commands work on.
+#[derive(Args, Clone)]
+pub struct SessionArgs {
+    /// Game of the session, defaults to the most recently played game
+    #[arg(long)]
+    game: Option<String>,
+    /// Session id, defaults to the game's latest session
+    #[arg(long)]
+    session: Option<String>,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CliCommand {
+    /// Run in the tray and capture screenshots (the default)
+    Run,
+    /// Capture the foreground window once and exit
+    CaptureOnce {
+        /// Seconds to wait before capturing, to switch to the game
+        #[arg(long, default_value_t = 0)]
+        wait: u64,
+        /// Capture the window even if the rules would skip it
+        #[arg(long)]
+        force: bool,
+    },
+    /// List open windows and the rule each one matches
+    Windows,
+    /// Delete old screenshots
+    Prune {
+        /// Age past which screenshots are deleted, e.g. 90d, 12w, 6m or 1y
+        #[arg(long, value_parser = parse_age)]
+        older_than: Duration,
+        /// Only prune this game's screenshots
+        #[arg(long)]
+        game: Option<String>,
+        /// List what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List recorded sessions
+    Sessions {
+        /// Only list this game's sessions
+        #[arg(long)]
+        game: Option<String>,
+        /// Only list sessions started on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only list sessions started on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Stitch a session's screenshots into an MP4 timelapse with ffmpeg
+    Timelapse(#[command(flatten)] SessionArgs),
+    /// Make a looping GIF from a session's screenshots
+    Gif(#[command(flatten)] SessionArgs),
+    /// Make a grid of a session's screenshots with the game name and date
+    ContactSheet(#[command(flatten)] SessionArgs),
+    /// Search text recognized in screenshots (needs `ocr = true`)
+    Search {
+        /// Text to look for
+        text: String,
+        /// Only search this game's screenshots
+        #[arg(long)]
+        game: Option<String>,
+    },
+    /// Show playtime and screenshot statistics
+    Stats {
+        /// Only show this game
+        #[arg(long)]
+        game: Option<String>,
+    },
+    /// Write an HTML calendar of play history to the journal folder and open it
+    Calendar,
+    /// Browse games, sessions and screenshots in a window
+    Journal,
+    /// Open the settings window
+    Settings,
+    /// Open the rules editor
+    Rules,
+    /// Run the first-run setup wizard again, replacing the config file
+    Setup,
+}
+
+impl CliCommand {
+    pub fn opens_window(&self) -> bool {
+        matches!(
+            self,
+            Self::Journal | Self::Settings | Self::Rules | Self::Setup
+        )
+    }
+}
+
+fn parse_age(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid age {text:?}"))?;
+    let hours = match unit {
+        "h" => 1,
+        "" | "d" => 24,
+        "w" => 7 * 24,
+        "m" => 30 * 24,
+        "y" => 365 * 24,
+        _ => return Err(format!("Unknown unit {unit:?}, expected h, d, w, m or y")),
+    };
+    Ok(Duration::from_secs(number * hours * 60 * 60))
+}
+
+fn capture_once(config: &Config, wait: u64, force: bool) -> Result<()> {
+    thread::sleep(Duration::from_secs(wait));
+    let window = get_valid_window(config, force)?;
+    let frame = capture_window(window.id, &config.capture_settings(&window.rule))?;
+    let hash = imaging::dhash(&frame.image);
+    let mut sessions = session::SessionTracker::default();
+    let path = store_screenshot(config, &mut sessions, &window, frame, hash)?;
+    encoder::flush();
+    println!("{}", path.display());
+    upload::drain(config);
+    webhook::drain(&config.webhooks);
+    discord::wait();
+    Ok(())
+}
+
+fn prune(config: &Config, older_than: Duration, game: Option<&str>, dry_run: bool) -> Result<()> {
+    let (screenshots, _) = maintenance::list_screenshots(&config.target_folder)?;
+    let now = SystemTime::now();
+    let index = maintenance::open_index(&config.target_folder);
+    let (mut count, mut size) = (0, 0);
+    for screenshot in screenshots {
+        if game.is_some_and(|game| !screenshot.game.eq_ignore_ascii_case(game)) {
+            continue;
+        }
+        if now.duration_since(screenshot.modified).unwrap_or_default() <= older_than {
+            continue;
+        }
+        if dry_run {
+            println!("{}", screenshot.path.display());
+        } else if let Err(e) = maintenance::remove_screenshot(index.as_ref(), &screenshot.path) {
+            eprintln!("Could not remove {}: {e:#}", screenshot.path.display());
+            continue;
+        }
+        count += 1;
+        size += screenshot.size;
+    }
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    println!(
+        "{verb} {count} screenshots ({:.1} MB)",
+        size as f64 / (1 << 20) as f64
+    );
+    Ok(())
+}
+
+fn sessions(config: &Config, filter: &SessionFilter) -> Result<()> {
+    let sessions = Index::open(&config.target_folder)?.sessions(filter)?;
+    let mut played = Duration::ZERO;
+    for session in &sessions {
+        println!(
+            "{}  {:>8}  {:>4} screenshots  {}",
+            session.start.format("%Y-%m-%d %H:%M"),
+            format_duration(session.played),
+            session.screenshot_count,
+            session.game
+        );
+        played += session.played;
+    }
+    println!(
+        "{} sessions, {} played",
+        sessions.len(),
+        format_duration(played)
+    );
+    Ok(())
+}
+
+fn search(config: &Config, text: &str, game: Option<&str>) -> Result<()> {
+    let matches = Index::open(&config.target_folder)?.search_text(text, game)?;
+    for m in &matches {
+        println!(
+            "{}  {}  {}",
+            m.timestamp.format("%Y-%m-%d %H:%M"),
+            m.game,
+            m.path.display()
+        );
+        println!("    {}", m.snippet.replace('\n', " "));
+    }
+    println!("{} screenshots found", matches.len());
+    Ok(())
+}
+
+impl SessionArgs {
+    fn find(&self, config: &Config) -> Result<SessionRow> {
+        let index = Index::open(&config.target_folder)?;
+        let found = match (&self.game, &self.session) {
+            (Some(game), Some(id)) => index.session(game, id)?,
+            (None, Some(_)) => bail!("--session needs --game"),
+            (game, None) => index.latest_session(game.as_deref())?,
+        };
+        found.context("No matching session found")
+    }
+}
+
+fn stats(config: &Config, game: Option<&str>) -> Result<()> {
+    let stats = Index::open(&config.target_folder)?.game_stats()?;
+    let stats: Vec<_> = stats
+        .into_iter()
+        .filter(|s| game.is_none_or(|game| s.game.eq_ignore_ascii_case(game)))
+        .collect();
+    if stats.is_empty() {
+        match game {
+            Some(game) => bail!("No sessions recorded for {game:?}"),
+            None => bail!("No sessions recorded yet"),
+        }
+    }
+    for s in stats {
+        println!("{}", s.game);
+        println!("  played:      {}", format_duration(s.played));
+        println!("  sessions:    {}", s.sessions);
+        println!("  screenshots: {}", s.screenshots);
+        if let (Some(first), Some(last)) = (s.first_played, s.last_played) {
+            println!(
+                "  played from: {} to {}",
+                first.format("%Y-%m-%d"),
+                last.format("%Y-%m-%d")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn list_windows(config: &Config) -> Result<()> {
+    let own_pid = std::process::id();
+    for window in DesktopWindow::visible()?
+        .iter()
+        .filter(|window| window.pid() != own_pid)
+    {
+        println!("{}", describe(config, window));
+    }
+    Ok(())
+}
+
+pub fn execute(command: &CliCommand, config: &Config) -> Result<()> {
+    match command {
+        CliCommand::Run => unreachable!("run is handled by main"),
+        CliCommand::CaptureOnce { wait, force } => capture_once(config, *wait, *force),
+        CliCommand::Windows => list_windows(config),
+        CliCommand::Prune {
+            older_than,
+            game,
+            dry_run,
+        } => prune(config, *older_than, game.as_deref(), *dry_run),
+        CliCommand::Sessions { game, from, to } => sessions(
+            config,
+            &SessionFilter {
+                game: game.as_deref(),
+                from: *from,
+                to: *to,
+            },
+        ),
+        CliCommand::Timelapse(args) => {
+            let session = args.find(config)?;
+            let path = timelapse::make_timelapse(config, &session.game, &session.id)?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        CliCommand::Gif(args) => {
+            let session = args.find(config)?;
+            let path = animation::make_animation(config, &session.game, &session.id)?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        CliCommand::ContactSheet(args) => {
+            let session = args.find(config)?;
+            let path = contact_sheet::make_contact_sheet(config, &session.game, &session.id)?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        CliCommand::Search { text, game } => search(config, text, game.as_deref()),
+        CliCommand::Stats { game } => stats(config, game.as_deref()),
+        CliCommand::Calendar => {
+            let path = calendar::write_calendar(&config.target_folder)?;
+            println!("{}", path.display());
+            open_file(&path)
+        }
+        CliCommand::Journal | CliCommand::Settings | CliCommand::Rules | CliCommand::Setup => {
+            unreachable!("windows are opened by main")
+        }
+    }
+}
@@ -0,0 +1,866 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::Deserialize;
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    animation, caption, contact_sheet, hooks, platform, power, region, schedule, script, template,
+    timelapse, upload, webhook, Args,
+};
+
+pub const MAX_BURST_FRAMES: u32 = 10;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    #[default]
+    Monitor,
+    Window,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureBackend {
+    #[default]
+    GraphicsCapture,
+    Duplication,
+    Gdi,
+    /// Reported for X11 captures, there is only one way to capture there.
+    #[cfg(target_os = "linux")]
+    #[serde(skip)]
+    X11,
+    /// Reported for Wayland captures through the xdg-desktop-portal Screenshot interface.
+    #[cfg(target_os = "linux")]
+    #[serde(skip)]
+    Portal,
+    /// Reported for macOS captures through the CoreGraphics window list.
+    #[cfg(target_os = "macos")]
+    #[serde(skip)]
+    CoreGraphics,
+}
+
+impl CaptureBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::GraphicsCapture => "graphics-capture",
+            Self::Duplication => "duplication",
+            Self::Gdi => "gdi",
+            #[cfg(target_os = "linux")]
+            Self::X11 => "x11",
+            #[cfg(target_os = "linux")]
+            Self::Portal => "portal",
+            #[cfg(target_os = "macos")]
+            Self::CoreGraphics => "core-graphics",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::GraphicsCapture,
+            Self::Duplication,
+            Self::Gdi,
+            #[cfg(target_os = "linux")]
+            Self::X11,
+            #[cfg(target_os = "linux")]
+            Self::Portal,
+            #[cfg(target_os = "macos")]
+            Self::CoreGraphics,
+        ]
+        .into_iter()
+        .find(|backend| backend.name() == name)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Whitelist,
+    Known,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrayAction {
+    #[default]
+    None,
+    Menu,
+    OpenFolder,
+    Journal,
+    Capture,
+    Pause,
+    Settings,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrayConfig {
+    pub click: TrayAction,
+    pub double_click: TrayAction,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self {
+            click: TrayAction::Menu,
+            double_click: TrayAction::None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for tracing_subscriber::filter::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ByteSizeRepr {
+    Bytes(u64),
+    Text(String),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "ByteSizeRepr")]
+pub struct ByteSize(pub u64);
+
+impl TryFrom<ByteSizeRepr> for ByteSize {
+    type Error = String;
+
+    fn try_from(value: ByteSizeRepr) -> Result<Self, Self::Error> {
+        match value {
+            ByteSizeRepr::Bytes(bytes) => Ok(Self(bytes)),
+            ByteSizeRepr::Text(text) => parse_byte_size(&text)
+                .map(Self)
+                .ok_or_else(|| format!("Invalid size {text:?}")),
+        }
+    }
+}
+
+pub fn parse_byte_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+    let number: f64 = number.trim().parse().ok()?;
+    let multiplier: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+fn default_quality() -> u8 {
+    90
+}
+
+fn default_session_timeout() -> u64 {
+    300
+}
+
+fn default_launch_capture_delay() -> u64 {
+    10
+}
+
+fn default_hdr_white_level() -> f32 {
+    200.0
+}
+
+fn default_timestamp_format() -> String {
+    template::DEFAULT_DATETIME_FORMAT.to_owned()
+}
+
+fn default_burst_frames() -> u32 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub target_folder: PathBuf,
+    pub screenshot_delay: u64,
+    pub min_screenshot_delay: Option<u64>,
+    #[serde(default)]
+    pub mode: MatchMode,
+    #[serde(default)]
+    pub capture_mode: CaptureMode,
+    #[serde(default)]
+    pub capture_backend: CaptureBackend,
+    #[serde(default)]
+    pub format: ImageFormat,
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    #[serde(default = "default_session_timeout")]
+    pub session_timeout: u64,
+    #[serde(default)]
+    pub duplicate_threshold: u32,
+    #[serde(default)]
+    pub date_folders: template::DateFolders,
+    pub filename_template: Option<String>,
+    #[serde(default = "default_timestamp_format")]
+    pub timestamp_format: String,
+    #[serde(default)]
+    pub utc_timestamps: bool,
+    #[serde(default)]
+    pub preserve_unicode: bool,
+    pub max_dimension: Option<u32>,
+    #[serde(default = "default_true")]
+    pub thumbnails: bool,
+    #[serde(default = "default_true")]
+    pub skip_blank_frames: bool,
+    #[serde(default = "default_burst_frames")]
+    pub burst_frames: u32,
+    pub capture_cursor: Option<bool>,
+    #[serde(default = "default_true")]
+    pub hdr_tonemap: bool,
+    #[serde(default = "default_hdr_white_level")]
+    pub hdr_white_level: f32,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    pub hotkey: Option<String>,
+    pub max_total_size: Option<ByteSize>,
+    #[serde(default)]
+    pub prune_marked_only: bool,
+    pub retention_days: Option<u64>,
+    pub archive_after_months: Option<u32>,
+    #[serde(default = "default_true")]
+    pub daily_journal: bool,
+    #[serde(default)]
+    pub fullscreen_tolerance: i32,
+    /// Gamepads aren't read on macOS.
+    #[serde(default = "default_true")]
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    pub gamepad_input: bool,
+    #[serde(default = "default_true")]
+    pub capture_on_launch: bool,
+    #[serde(default = "default_launch_capture_delay")]
+    pub launch_capture_delay: u64,
+    #[serde(default = "default_true")]
+    pub capture_on_exit: bool,
+    #[serde(default = "default_true")]
+    pub notifications: bool,
+    pub autostart: Option<bool>,
+    #[serde(default)]
+    pub schedule: schedule::Schedule,
+    pub upload: Option<upload::UploadConfig>,
+    #[serde(default)]
+    pub webhooks: Vec<webhook::Webhook>,
+    pub api_port: Option<u16>,
+    pub api_token: Option<String>,
+    #[serde(default)]
+    pub ocr: bool,
+    #[serde(default)]
+    pub caption: caption::CaptionConfig,
+    #[serde(default)]
+    pub timelapse: timelapse::TimelapseConfig,
+    #[serde(default)]
+    pub animation: animation::AnimationConfig,
+    #[serde(default)]
+    pub contact_sheet: contact_sheet::ContactSheetConfig,
+    #[serde(default)]
+    pub on_battery: power::BatteryConfig,
+    #[serde(default)]
+    pub tray: TrayConfig,
+    pub on_screenshot: Option<String>,
+    pub on_session_start: Option<String>,
+    pub on_session_end: Option<String>,
+    #[serde(default = "default_true", alias = "steam_names")]
+    pub store_names: bool,
+    pub rules_script: Option<PathBuf>,
+    #[serde(skip)]
+    pub script: Option<Arc<script::RulesScript>>,
+    #[serde(default)]
+    pub rules: Vec<RuleEntry>,
+}
+
+#[derive(Clone, Copy)]
+pub struct CaptureSettings {
+    pub mode: CaptureMode,
+    /// Only Windows has several capture backends.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub backend: CaptureBackend,
+    pub format: ImageFormat,
+    pub quality: u8,
+    pub duplicate_threshold: u32,
+    /// Only Windows captures HDR.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub hdr_white_level: Option<f32>,
+    pub max_dimension: Option<u32>,
+    pub thumbnails: bool,
+    pub skip_blank: bool,
+    pub burst_frames: u32,
+    pub crop: Option<region::Region>,
+    pub cursor: Option<bool>,
+    pub span_monitors: bool,
+}
+
+impl Config {
+    pub fn filename_template(&self) -> &str {
+        self.filename_template
+            .as_deref()
+            .unwrap_or(self.date_folders.template())
+    }
+
+    pub fn is_adaptive(&self, rule: Option<&RuleEntry>) -> bool {
+        rule.and_then(|r| r.min_delay)
+            .or(self.min_screenshot_delay)
+            .is_some()
+    }
+
+    pub fn capture_delay(&self, rule: Option<&RuleEntry>, activity: f64) -> Duration {
+        let max = rule
+            .and_then(|r| r.max_delay)
+            .unwrap_or(self.screenshot_delay);
+        let Some(min) = rule.and_then(|r| r.min_delay).or(self.min_screenshot_delay) else {
+            return Duration::from_secs(max);
+        };
+        let min = min.min(max);
+        Duration::from_secs_f64(max as f64 - (max - min) as f64 * activity.clamp(0.0, 1.0))
+    }
+
+    pub fn capture_settings(&self, rule: &RuleEntry) -> CaptureSettings {
+        CaptureSettings {
+            mode: rule.capture_mode.unwrap_or(self.capture_mode),
+            backend: rule.capture_backend.unwrap_or(self.capture_backend),
+            format: rule.format.unwrap_or(self.format),
+            quality: rule.quality.unwrap_or(self.quality),
+            duplicate_threshold: rule.duplicate_threshold.unwrap_or(self.duplicate_threshold),
+            hdr_white_level: self.hdr_tonemap.then_some(self.hdr_white_level),
+            max_dimension: rule.max_dimension.or(self.max_dimension),
+            thumbnails: self.thumbnails,
+            skip_blank: rule.skip_blank_frames.unwrap_or(self.skip_blank_frames),
+            burst_frames: rule.burst_frames.unwrap_or(self.burst_frames),
+            crop: rule.crop,
+            cursor: rule.capture_cursor.or(self.capture_cursor),
+            span_monitors: rule.span_monitors,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.screenshot_delay == 0 {
+            bail!("screenshot_delay must be at least 1 second");
+        }
+        if !(1..=MAX_BURST_FRAMES).contains(&self.burst_frames) {
+            bail!("burst_frames must be between 1 and {MAX_BURST_FRAMES}");
+        }
+        if self.min_screenshot_delay == Some(0) {
+            bail!("min_screenshot_delay must be at least 1 second");
+        }
+        if self.max_dimension == Some(0) {
+            bail!("max_dimension must be at least 1 pixel");
+        }
+        let timestamp =
+            template::format_time(Local::now(), &self.timestamp_format, self.utc_timestamps)
+                .context("Invalid timestamp_format")?;
+        if timestamp.contains(['<', '>', ':', '"', '|', '?', '*']) {
+            bail!("timestamp_format must not produce characters that aren't allowed in file names, like `:`");
+        }
+        template::render(
+            self.filename_template(),
+            &template::FileNameContext {
+                utc: self.utc_timestamps,
+                datetime_format: &self.timestamp_format,
+                ..template::FileNameContext::example()
+            },
+            self.format.extension(),
+        )
+        .context("Invalid filename_template")?;
+        if self.hdr_white_level <= 0.0 {
+            bail!("hdr_white_level must be positive");
+        }
+        if self.quality == 0 || self.quality > 100 {
+            bail!("quality must be between 1 and 100, got {}", self.quality);
+        }
+        if self.format != ImageFormat::Jpeg && self.quality != default_quality() {
+            bail!("quality only applies to jpeg, png and webp are saved lossless");
+        }
+        if let Some(upload) = &self.upload {
+            upload.validate()?;
+        }
+        for webhook in &self.webhooks {
+            webhook.validate()?;
+        }
+        self.caption.validate()?;
+        self.timelapse.validate()?;
+        self.animation.validate()?;
+        self.contact_sheet.validate()?;
+        self.on_battery.validate()?;
+        for (name, command) in [
+            ("on_screenshot", &self.on_screenshot),
+            ("on_session_start", &self.on_session_start),
+            ("on_session_end", &self.on_session_end),
+        ] {
+            if let Some(command) = command {
+                hooks::split_args(command).with_context(|| format!("Invalid {name}"))?;
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.has_name_matcher() && !rule.has_qualifier() {
+                bail!("rules[{i}] needs a `name`, `match`, `match_regex`, `path` or `window_class` to match anything");
+            }
+            if rule.max_dimension == Some(0) {
+                bail!(
+                    "rules[{i}] ({}): max_dimension must be at least 1 pixel",
+                    rule.name
+                );
+            }
+            if rule.quality.is_some_and(|q| q == 0 || q > 100) {
+                bail!(
+                    "rules[{i}] ({}): quality must be between 1 and 100",
+                    rule.name
+                );
+            }
+            if rule.quality.is_some() && rule.format.unwrap_or(self.format) != ImageFormat::Jpeg {
+                bail!(
+                    "rules[{i}] ({}): quality only applies to jpeg, png and webp are saved lossless",
+                    rule.name
+                );
+            }
+            if rule
+                .burst_frames
+                .is_some_and(|n| !(1..=MAX_BURST_FRAMES).contains(&n))
+            {
+                bail!(
+                    "rules[{i}] ({}): burst_frames must be between 1 and {MAX_BURST_FRAMES}",
+                    rule.name
+                );
+            }
+            if rule.min_delay == Some(0) || rule.max_delay == Some(0) {
+                bail!(
+                    "rules[{i}] ({}): min_delay and max_delay must be at least 1 second",
+                    rule.name
+                );
+            }
+            if rule.discord_every == Some(0) {
+                bail!(
+                    "rules[{i}] ({}): discord_every must be at least 1",
+                    rule.name
+                );
+            }
+            if rule
+                .discord_webhook
+                .as_ref()
+                .is_some_and(|url| !url.starts_with("https://"))
+            {
+                bail!(
+                    "rules[{i}] ({}): discord_webhook must be an https:// URL",
+                    rule.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct GlobPattern(glob::Pattern);
+
+impl TryFrom<String> for GlobPattern {
+    type Error = glob::PatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(Self(glob::Pattern::new(&value)?))
+    }
+}
+
+impl GlobPattern {
+    pub fn matches(&self, text: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.0.matches_with(text, options)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct PathPattern(glob::Pattern);
+
+impl TryFrom<String> for PathPattern {
+    type Error = glob::PatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(Self(glob::Pattern::new(&value.replace('\\', "/"))?))
+    }
+}
+
+impl PathPattern {
+    pub fn matches(&self, path: &Path) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.0
+            .matches_with(&path.to_string_lossy().replace('\\', "/"), options)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct RegexPattern(pub regex::Regex);
+
+impl TryFrom<String> for RegexPattern {
+    type Error = regex::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(Self(
+            regex::RegexBuilder::new(&value)
+                .case_insensitive(true)
+                .build()?,
+        ))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleEntry {
+    pub name: String,
+    #[serde(rename = "match")]
+    pub match_glob: Option<GlobPattern>,
+    pub match_regex: Option<RegexPattern>,
+    pub path: Option<PathPattern>,
+    pub window_class: Option<GlobPattern>,
+    pub title_matches: Option<RegexPattern>,
+    pub title_excludes: Option<RegexPattern>,
+    pub title_name: Option<RegexPattern>,
+    pub ignore: bool,
+    pub enabled: bool,
+    pub needs_fullscreen: bool,
+    pub use_window_name: bool,
+    pub override_name: Option<String>,
+    pub capture_mode: Option<CaptureMode>,
+    pub capture_backend: Option<CaptureBackend>,
+    pub format: Option<ImageFormat>,
+    pub quality: Option<u8>,
+    pub duplicate_threshold: Option<u32>,
+    pub max_dimension: Option<u32>,
+    pub prunable: bool,
+    pub retention_days: Option<u64>,
+    pub fullscreen_tolerance: Option<i32>,
+    pub max_per_hour: Option<usize>,
+    pub max_per_session: Option<u32>,
+    pub discord_webhook: Option<String>,
+    pub discord_every: Option<u32>,
+    pub skip_blank_frames: Option<bool>,
+    pub burst_frames: Option<u32>,
+    pub crop: Option<region::Region>,
+    pub capture_cursor: Option<bool>,
+    pub span_monitors: bool,
+    pub privacy_regions: Vec<region::Region>,
+    pub privacy_style: region::PrivacyStyle,
+    pub min_delay: Option<u64>,
+    pub max_delay: Option<u64>,
+}
+
+impl Default for RuleEntry {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            match_glob: None,
+            match_regex: None,
+            path: None,
+            window_class: None,
+            title_matches: None,
+            title_excludes: None,
+            title_name: None,
+            ignore: false,
+            enabled: true,
+            needs_fullscreen: true,
+            use_window_name: false,
+            override_name: None,
+            capture_mode: None,
+            capture_backend: None,
+            format: None,
+            quality: None,
+            duplicate_threshold: None,
+            max_dimension: None,
+            prunable: false,
+            retention_days: None,
+            fullscreen_tolerance: None,
+            max_per_hour: None,
+            max_per_session: None,
+            discord_webhook: None,
+            discord_every: None,
+            skip_blank_frames: None,
+            burst_frames: None,
+            crop: None,
+            capture_cursor: None,
+            span_monitors: false,
+            privacy_regions: Vec::new(),
+            privacy_style: region::PrivacyStyle::default(),
+            min_delay: None,
+            max_delay: None,
+        }
+    }
+}
+
+pub const DEFAULT_CONFIG: &str = include_str!("../default_config.toml");
+
+pub fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_owned))
+}
+
+pub fn config_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(dir) = platform::config_dir() {
+        candidates.push(dir.join("autogamejournal").join("config.toml"));
+    }
+    if let Some(dir) = exe_dir() {
+        candidates.push(dir.join("config.toml"));
+    }
+    candidates
+}
+
+pub fn default_target_folder() -> PathBuf {
+    platform::pictures_dir()
+        .map(|pictures| pictures.join("autogamejournal"))
+        .unwrap_or_else(|| PathBuf::from("screenshots"))
+}
+
+fn initial_config(target_folder: &Path, screenshot_delay: u64, mode: MatchMode) -> String {
+    let text = DEFAULT_CONFIG
+        .replace(
+            "{target_folder}",
+            &toml::Value::String(target_folder.to_string_lossy().into_owned()).to_string(),
+        )
+        .replace(
+            "\nscreenshot_delay = 300\n",
+            &format!("\nscreenshot_delay = {screenshot_delay}\n"),
+        );
+    let mode = match mode {
+        MatchMode::All => return text,
+        MatchMode::Whitelist => "whitelist",
+        MatchMode::Known => "known",
+    };
+    text.replace("# mode = \"all\"", &format!("mode = \"{mode}\""))
+}
+
+/// Writes the commented default config with the choices made in the setup wizard filled in.
+pub fn write_initial_config(
+    path: &Path,
+    target_folder: &Path,
+    screenshot_delay: u64,
+    mode: MatchMode,
+) -> Result<()> {
+    let text = initial_config(target_folder, screenshot_delay, mode);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    create_dir_all(dir).context("Creating config folder")?;
+    std::fs::write(path, text).context("Writing config")?;
+    create_dir_all(dir.join(target_folder)).context("Creating target folder")?;
+    Ok(())
+}
+
+pub fn write_default_config(path: &Path) -> Result<()> {
+    write_initial_config(path, &default_target_folder(), 300, MatchMode::All)
+}
+
+pub fn existing_config() -> Option<PathBuf> {
+    config_candidates().into_iter().find(|path| path.exists())
+}
+
+pub fn new_config_path() -> Result<PathBuf> {
+    config_candidates()
+        .into_iter()
+        .next()
+        .context("No location available for the config file")
+}
+
+pub fn find_config() -> Result<PathBuf> {
+    if let Some(path) = existing_config() {
+        return Ok(path);
+    }
+    let path = new_config_path()?;
+    write_default_config(&path)?;
+    Ok(path)
+}
+
+pub fn parse_config(path: &Path, text: &str) -> Result<Config> {
+    let mut config: Config =
+        toml::from_str(text).with_context(|| format!("Invalid config in {path:?}"))?;
+    if let Some(dir) = path.parent() {
+        config.target_folder = dir.join(&config.target_folder);
+        config.rules_script = config.rules_script.map(|script| dir.join(script));
+    }
+    if let Some(script) = &config.rules_script {
+        config.script = Some(Arc::new(script::RulesScript::load(script)?));
+    }
+    Ok(config)
+}
+
+pub fn load_config(path: &Path, args: &Args) -> Result<Config> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Reading {path:?}"))?;
+    let mut config = parse_config(path, &text)?;
+    args.apply(&mut config);
+    config
+        .validate()
+        .with_context(|| format!("Invalid config in {path:?}"))?;
+    Ok(config)
+}
+
+pub fn read_document(path: &Path) -> Result<toml_edit::DocumentMut> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Reading {path:?}"))?;
+    text.parse()
+        .with_context(|| format!("Invalid config in {path:?}"))
+}
+
+pub fn rule_tables(document: &mut toml_edit::DocumentMut) -> Result<&mut toml_edit::ArrayOfTables> {
+    document
+        .entry("rules")
+        .or_insert(toml_edit::Item::ArrayOfTables(
+            toml_edit::ArrayOfTables::new(),
+        ))
+        .as_array_of_tables_mut()
+        .context("Rules must be written as [[rules]] tables to be edited here")
+}
+
+pub fn write_document(path: &Path, document: &toml_edit::DocumentMut) -> Result<()> {
+    let text = document.to_string();
+    parse_config(path, &text)?
+        .validate()
+        .with_context(|| format!("Invalid config in {path:?}"))?;
+    std::fs::write(path, text).context("Writing config")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(parse_byte_size("512"), Some(512));
+        assert_eq!(parse_byte_size("8 MB"), Some(8 << 20));
+        assert_eq!(parse_byte_size("1.5gb"), Some(3 << 29));
+        assert_eq!(parse_byte_size("10 parsecs"), None);
+    }
+
+    #[test]
+    fn quality_needs_jpeg() {
+        let config: Config = toml::from_str(
+            r#"
+            target_folder = "shots"
+            screenshot_delay = 60
+            [[rules]]
+            name = "game"
+            format = "webp"
+            quality = 80
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = toml::from_str(
+            r#"
+            target_folder = "shots"
+            screenshot_delay = 60
+            format = "png"
+            quality = 80
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rule_overrides_capture_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            target_folder = "shots"
+            screenshot_delay = 60
+            quality = 80
+            [[rules]]
+            name = "game"
+            quality = 95
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.capture_settings(&config.rules[0]).quality, 95);
+        assert_eq!(config.capture_settings(&RuleEntry::default()).quality, 80);
+    }
+
+    #[test]
+    fn initial_config_choices() {
+        let text = initial_config(Path::new("D:/Journal"), 60, MatchMode::Whitelist);
+        let config: Config = toml::from_str(&text).unwrap();
+        assert_eq!(config.target_folder, Path::new("D:/Journal"));
+        assert_eq!(config.screenshot_delay, 60);
+        assert!(config.mode == MatchMode::Whitelist);
+        let config: Config =
+            toml::from_str(&initial_config(Path::new("shots"), 300, MatchMode::All)).unwrap();
+        assert!(config.mode == MatchMode::All);
+    }
+
+    #[test]
+    fn adaptive_delay() {
+        let config: Config = toml::from_str(
+            r#"
+            target_folder = "shots"
+            screenshot_delay = 300
+            min_screenshot_delay = 100
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.capture_delay(None, 0.0), Duration::from_secs(300));
+        assert_eq!(config.capture_delay(None, 0.5), Duration::from_secs(200));
+        assert_eq!(config.capture_delay(None, 1.0), Duration::from_secs(100));
+    }
+}
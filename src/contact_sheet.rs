@@ -0,0 +1,131 @@
+use anyhow::{bail, Context, Result};
+use image::{imageops::FilterType, Rgb, RgbImage};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::{
+    format_duration, imaging, index::Index, notification, screenshot::save_image, timelapse,
+    Config, ImageFormat,
+};
+
+pub const EXTENSION: &str = "contact.jpg";
+const CELL_WIDTH: u32 = 480;
+const MARGIN: u32 = 8;
+const HEADER_HEIGHT: u32 = 64;
+const BACKGROUND: Rgb<u8> = Rgb([24, 24, 28]);
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContactSheetConfig {
+    pub max_images: usize,
+    pub on_session_end: bool,
+}
+
+impl Default for ContactSheetConfig {
+    fn default() -> Self {
+        Self {
+            max_images: 16,
+            on_session_end: false,
+        }
+    }
+}
+
+impl ContactSheetConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_images == 0 {
+            bail!("contact_sheet.max_images must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+pub fn is_contact_sheet(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(EXTENSION)
+}
+
+fn pick_evenly(files: &[PathBuf], count: usize) -> Vec<&PathBuf> {
+    if files.len() <= count {
+        return files.iter().collect();
+    }
+    (0..count)
+        .map(|i| &files[i * (files.len() - 1) / (count - 1).max(1)])
+        .collect()
+}
+
+fn load_thumbnail(path: &Path) -> Result<RgbImage> {
+    let thumbnail = imaging::thumbnail_path(path);
+    let source = if thumbnail.exists() { &thumbnail } else { path };
+    Ok(image::open(source)?.into_rgb8())
+}
+
+pub fn make_contact_sheet(config: &Config, game: &str, session_id: &str) -> Result<PathBuf> {
+    let index = Index::open(&config.target_folder)?;
+    let session = index
+        .session(game, session_id)?
+        .with_context(|| format!("Session {session_id} of {game} not found"))?;
+    let files = index.session_screenshot_files(game, session_id)?;
+    if files.is_empty() {
+        bail!("Session {session_id} of {game} has no screenshots");
+    }
+    let picked = pick_evenly(&files, config.contact_sheet.max_images);
+    let columns = (picked.len() as f64).sqrt().ceil() as u32;
+    let rows = (picked.len() as u32).div_ceil(columns);
+
+    let first = load_thumbnail(picked[0])?;
+    let cell_height = (CELL_WIDTH as u64 * first.height() as u64 / first.width() as u64) as u32;
+    let width = columns * (CELL_WIDTH + MARGIN) + MARGIN;
+    let height = HEADER_HEIGHT + rows * (cell_height + MARGIN) + MARGIN;
+    let mut sheet = RgbImage::from_pixel(width, height, BACKGROUND);
+
+    let title = format!(
+        "{game} \u{2014} {} {}\u{2013}{} \u{00b7} {} played \u{00b7} {} screenshots",
+        session.start.format("%Y-%m-%d"),
+        session.start.format("%H:%M"),
+        session.end.format("%H:%M"),
+        format_duration(session.played),
+        session.screenshot_count,
+    );
+    imaging::draw_text(
+        &mut sheet,
+        &title,
+        MARGIN as i32 * 2,
+        16,
+        32.0,
+        Rgb([235, 235, 235]),
+    );
+
+    for (i, path) in picked.iter().enumerate() {
+        let image = match load_thumbnail(path) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!(?path, "Could not load screenshot for contact sheet: {e:?}");
+                continue;
+            }
+        };
+        let cell = image::imageops::resize(&image, CELL_WIDTH, cell_height, FilterType::Triangle);
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let x = MARGIN + column * (CELL_WIDTH + MARGIN);
+        let y = HEADER_HEIGHT + row * (cell_height + MARGIN);
+        image::imageops::replace(&mut sheet, &cell, x as i64, y as i64);
+    }
+
+    let output = timelapse::output_path(&config.target_folder, game, session_id, EXTENSION);
+    save_image(&sheet, &output, ImageFormat::Jpeg, 90)?;
+    info!(%game, session = session_id, path = ?output, "Created contact sheet");
+    Ok(output)
+}
+
+pub fn make_contact_sheet_in_background(config: Config, game: String, session_id: String) {
+    std::thread::spawn(
+        move || match make_contact_sheet(&config, &game, &session_id) {
+            Ok(path) => {
+                if config.notifications {
+                    let text = format!("Contact sheet of {game} saved to {}", path.display());
+                    let _ = notification::show_toast("autogamejournal", &text);
+                }
+            }
+            Err(e) => warn!(%game, session = %session_id, "Could not create contact sheet: {e:?}"),
+        },
+    );
+}
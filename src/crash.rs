@@ -0,0 +1,50 @@
+use chrono::Local;
+use std::{
+    backtrace::Backtrace,
+    fs::{create_dir_all, OpenOptions},
+    io::Write,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    thread,
+    time::Duration,
+};
+use tracing::error;
+
+use crate::notification;
+
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+pub fn install_hook(log_dir: &Path) {
+    let log_dir = log_dir.to_owned();
+    panic::set_hook(Box::new(move |info| {
+        let current = thread::current();
+        let name = current.name().unwrap_or("unnamed");
+        error!("Thread '{name}' {info}");
+        let report = format!(
+            "{} thread '{name}' {info}\n{}\n",
+            Local::now().to_rfc3339(),
+            Backtrace::force_capture()
+        );
+        eprintln!("{report}");
+        let path = log_dir.join("crash.log");
+        let written = create_dir_all(&log_dir).and_then(|()| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(report.as_bytes())
+        });
+        let text = match written {
+            Ok(()) => format!("Details were written to {}", path.display()),
+            Err(_) => info.to_string(),
+        };
+        let _ = notification::show_toast("autogamejournal ran into an error", &text);
+    }));
+}
+
+pub fn supervise(name: &str, mut run: impl FnMut()) {
+    while panic::catch_unwind(AssertUnwindSafe(&mut run)).is_err() {
+        error!("{name} crashed, restarting it in {RESTART_DELAY:?}");
+        thread::sleep(RESTART_DELAY);
+    }
+}
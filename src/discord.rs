@@ -0,0 +1,42 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde_json::json;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread::JoinHandle,
+};
+use tracing::{debug, warn};
+
+use crate::webhook;
+
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+fn send(url: &str, game: &str, time: DateTime<Local>, path: &Path) -> Result<()> {
+    let payload = json!({
+        "content": format!("**{game}** \u{2014} <t:{}:f>", time.timestamp()),
+        "allowed_mentions": { "parse": [] },
+    });
+    let body = webhook::multipart_body("payload_json", &payload.to_string(), "files[0]", path)?;
+    ureq::post(url)
+        .set("Content-Type", &webhook::multipart_content_type())
+        .send_bytes(&body)
+        .map_err(webhook::http_error)?;
+    Ok(())
+}
+
+pub fn post_screenshot(url: String, game: String, time: DateTime<Local>, path: PathBuf) {
+    let handle = std::thread::spawn(move || match send(&url, &game, time, &path) {
+        Ok(()) => debug!(%game, ?path, "Posted screenshot to Discord"),
+        Err(e) => warn!(%game, ?path, "Could not post screenshot to Discord: {e:?}"),
+    });
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|handle| !handle.is_finished());
+    pending.push(handle);
+}
+
+pub fn wait() {
+    for handle in std::mem::take(&mut *PENDING.lock().unwrap()) {
+        let _ = handle.join();
+    }
+}
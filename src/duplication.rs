@@ -0,0 +1,151 @@
+use anyhow::{bail, Context, Result};
+use image::RgbImage;
+use windows::{
+    core::Interface,
+    Win32::{
+        Foundation::{HMODULE, HWND, RECT},
+        Graphics::{
+            Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+            Direct3D11::{
+                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+                D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_MAPPED_SUBRESOURCE,
+                D3D11_MAP_READ, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+            },
+            Dxgi::{
+                CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory1, IDXGIOutput, IDXGIOutput1,
+                IDXGIResource, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC,
+            },
+            Gdi::{MonitorFromWindow, MONITOR_DEFAULTTONEAREST},
+        },
+        UI::WindowsAndMessaging::GetWindowRect,
+    },
+};
+
+use crate::CaptureMode;
+
+const FRAME_TIMEOUT_MS: u32 = 500;
+const FRAME_ATTEMPTS: usize = 4;
+
+fn find_output(window: HWND) -> Result<(IDXGIAdapter1, IDXGIOutput, DXGI_OUTPUT_DESC)> {
+    let monitor = unsafe { MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST) };
+    unsafe {
+        let factory: IDXGIFactory1 = CreateDXGIFactory1().context("Creating DXGI factory")?;
+        let mut adapter_index = 0;
+        while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+            let mut output_index = 0;
+            while let Ok(output) = adapter.EnumOutputs(output_index) {
+                let mut desc = DXGI_OUTPUT_DESC::default();
+                output.GetDesc(&mut desc)?;
+                if desc.Monitor == monitor {
+                    return Ok((adapter, output, desc));
+                }
+                output_index += 1;
+            }
+            adapter_index += 1;
+        }
+    }
+    bail!("No DXGI output for the window's monitor")
+}
+
+fn read_texture(
+    device: &ID3D11Device,
+    context: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+) -> Result<RgbImage> {
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    unsafe { texture.GetDesc(&mut desc) };
+    desc.Usage = D3D11_USAGE_STAGING;
+    desc.BindFlags = 0;
+    desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+    desc.MiscFlags = 0;
+    let mut staging = None;
+    unsafe { device.CreateTexture2D(&desc, None, Some(&mut staging))? };
+    let staging = staging.context("No staging texture")?;
+    let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+    unsafe {
+        context.CopyResource(&staging, texture);
+        context.Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+    }
+    let (width, height) = (desc.Width as usize, desc.Height as usize);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let row = unsafe {
+            std::slice::from_raw_parts(
+                mapped.pData.cast::<u8>().add(y * mapped.RowPitch as usize),
+                width * 4,
+            )
+        };
+        pixels.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0]]));
+    }
+    unsafe { context.Unmap(&staging, 0) };
+    RgbImage::from_raw(desc.Width, desc.Height, pixels).context("Invalid frame size")
+}
+
+fn window_area(window: HWND, output: &RECT, image: &RgbImage) -> Result<(u32, u32, u32, u32)> {
+    let mut rect = RECT::default();
+    unsafe { GetWindowRect(window, &mut rect)? };
+    let left = (rect.left - output.left).clamp(0, image.width() as i32) as u32;
+    let top = (rect.top - output.top).clamp(0, image.height() as i32) as u32;
+    let right = (rect.right - output.left).clamp(0, image.width() as i32) as u32;
+    let bottom = (rect.bottom - output.top).clamp(0, image.height() as i32) as u32;
+    if right <= left || bottom <= top {
+        bail!("Window is not on its monitor");
+    }
+    Ok((left, top, right - left, bottom - top))
+}
+
+pub fn capture(id: u32, mode: CaptureMode) -> Result<RgbImage> {
+    let window = HWND(id as isize);
+    let (adapter, output, desc) = find_output(window)?;
+    let mut device = None;
+    let mut context = None;
+    unsafe {
+        D3D11CreateDevice(
+            &adapter,
+            D3D_DRIVER_TYPE_UNKNOWN,
+            HMODULE::default(),
+            D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+            None,
+            D3D11_SDK_VERSION,
+            Some(&mut device),
+            None,
+            Some(&mut context),
+        )
+        .context("Creating Direct3D device")?;
+    }
+    let device = device.context("No Direct3D device")?;
+    let context = context.context("No Direct3D device context")?;
+    let duplication = unsafe { output.cast::<IDXGIOutput1>()?.DuplicateOutput(&device) }
+        .context("Duplicating output")?;
+
+    let mut texture = None;
+    for _ in 0..FRAME_ATTEMPTS {
+        let mut info = DXGI_OUTDUPL_FRAME_INFO::default();
+        let mut resource: Option<IDXGIResource> = None;
+        if unsafe { duplication.AcquireNextFrame(FRAME_TIMEOUT_MS, &mut info, &mut resource) }
+            .is_err()
+        {
+            continue;
+        }
+        let frame = match (info.LastPresentTime, resource) {
+            (0, _) | (_, None) => None,
+            (_, Some(resource)) => Some(resource.cast::<ID3D11Texture2D>()?),
+        };
+        let image = frame
+            .map(|frame| read_texture(&device, &context, &frame))
+            .transpose();
+        unsafe { duplication.ReleaseFrame()? };
+        texture = image?;
+        if texture.is_some() {
+            break;
+        }
+    }
+    let image = texture.context("No frame presented")?;
+    Ok(match mode {
+        CaptureMode::Monitor => image,
+        CaptureMode::Window => {
+            let (x, y, width, height) = window_area(window, &desc.DesktopCoordinates, &image)?;
+            image::imageops::crop_imm(&image, x, y, width, height).to_image()
+        }
+    })
+}
@@ -0,0 +1,134 @@
+use anyhow::{bail, Result};
+#[cfg(windows)]
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::{
+        Foundation::{CloseHandle, ERROR_ACCESS_DENIED, HANDLE, HWND},
+        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        System::Threading::{
+            GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+        },
+        UI::{Shell::ShellExecuteW, WindowsAndMessaging::SW_SHOWNORMAL},
+    },
+};
+
+#[cfg(windows)]
+fn token_elevated(process: HANDLE) -> windows::core::Result<bool> {
+    let mut token = HANDLE::default();
+    let mut elevation = TOKEN_ELEVATION::default();
+    let mut size = 0;
+    unsafe {
+        OpenProcessToken(process, TOKEN_QUERY, &mut token)?;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(std::ptr::from_mut(&mut elevation).cast()),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        let _ = CloseHandle(token);
+        result?;
+    }
+    Ok(elevation.TokenIsElevated != 0)
+}
+
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    token_elevated(unsafe { GetCurrentProcess() }).unwrap_or(false)
+}
+
+#[cfg(windows)]
+pub fn is_process_elevated(pid: u32) -> bool {
+    let process = match unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) } {
+        Ok(process) => process,
+        Err(e) => return e.code() == ERROR_ACCESS_DENIED.to_hresult(),
+    };
+    let elevated = match token_elevated(process) {
+        Ok(elevated) => elevated,
+        Err(e) => e.code() == ERROR_ACCESS_DENIED.to_hresult(),
+    };
+    let _ = unsafe { CloseHandle(process) };
+    elevated
+}
+
+#[cfg(windows)]
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains([' ', '\t', '"']) {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        arg.to_owned()
+    }
+}
+
+#[cfg(windows)]
+pub fn restart_elevated() -> Result<()> {
+    use anyhow::Context;
+    let exe = std::env::current_exe().context("Getting executable path")?;
+    let mut args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--restarted")
+        .map(|arg| quote(&arg))
+        .collect();
+    args.push("--restarted".to_owned());
+    let result = unsafe {
+        ShellExecuteW(
+            HWND::default(),
+            &HSTRING::from("runas"),
+            &HSTRING::from(exe.as_os_str()),
+            &HSTRING::from(args.join(" ")),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    if result.0 <= 32 {
+        bail!("Could not start as administrator (error {})", result.0);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn owned_by_root(path: &str) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.uid() == 0)
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_elevated() -> bool {
+    owned_by_root("/proc/self")
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_process_elevated(pid: u32) -> bool {
+    owned_by_root(&format!("/proc/{pid}"))
+}
+
+#[cfg(target_os = "linux")]
+pub fn restart_elevated() -> Result<()> {
+    bail!("Restarting as administrator is only supported on Windows")
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_elevated() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_process_elevated(pid: u32) -> bool {
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    let read = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            std::ptr::from_mut(&mut info).cast(),
+            size,
+        )
+    };
+    read == size && info.pbi_uid == 0
+}
+
+#[cfg(target_os = "macos")]
+pub fn restart_elevated() -> Result<()> {
+    bail!("Restarting as administrator is only supported on Windows")
+}
@@ -0,0 +1,55 @@
+use std::sync::{
+    mpsc::{self, Receiver, SyncSender},
+    Arc, Condvar, Mutex, OnceLock,
+};
+
+const WORKERS: usize = 2;
+const QUEUE_SIZE: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static SENDER: OnceLock<SyncSender<Job>> = OnceLock::new();
+static PENDING: Mutex<usize> = Mutex::new(0);
+static IDLE: Condvar = Condvar::new();
+
+fn worker(jobs: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = jobs.lock().unwrap().recv();
+        let Ok(job) = job else {
+            return;
+        };
+        job();
+        let mut pending = PENDING.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            IDLE.notify_all();
+        }
+    }
+}
+
+fn sender() -> &'static SyncSender<Job> {
+    SENDER.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WORKERS {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || worker(receiver));
+        }
+        sender
+    })
+}
+
+pub fn submit(job: impl FnOnce() + Send + 'static) {
+    *PENDING.lock().unwrap() += 1;
+    if let Err(mpsc::SendError(job)) = sender().send(Box::new(job)) {
+        job();
+        *PENDING.lock().unwrap() -= 1;
+    }
+}
+
+pub fn flush() {
+    let mut pending = PENDING.lock().unwrap();
+    while *pending > 0 {
+        pending = IDLE.wait(pending).unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+use crate::library::{InstalledGame, Store};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Manifest {
+    display_name: String,
+    install_location: PathBuf,
+    app_name: String,
+}
+
+pub fn installed_games() -> Vec<InstalledGame> {
+    let Some(program_data) = std::env::var_os("ProgramData") else {
+        return Vec::new();
+    };
+    let manifests = PathBuf::from(program_data).join("Epic\\EpicGamesLauncher\\Data\\Manifests");
+    let Ok(entries) = fs::read_dir(manifests) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "item"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|text| serde_json::from_str::<Manifest>(&text).ok())
+        .map(|manifest| InstalledGame {
+            store: Store::Epic,
+            id: manifest.app_name,
+            name: manifest.display_name,
+            install_dir: manifest.install_location,
+        })
+        .collect()
+}
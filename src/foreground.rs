@@ -0,0 +1,76 @@
+#[cfg(windows)]
+use anyhow::bail;
+use anyhow::Result;
+use std::sync::mpsc;
+#[cfg(windows)]
+use std::sync::OnceLock;
+#[cfg(windows)]
+use windows::Win32::{
+    Foundation::{HMODULE, HWND},
+    UI::{
+        Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+        WindowsAndMessaging::{
+            DispatchMessageW, GetMessageW, TranslateMessage, EVENT_SYSTEM_FOREGROUND, MSG,
+            WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+        },
+    },
+};
+
+use crate::Command;
+
+#[cfg(windows)]
+static SENDER: OnceLock<mpsc::Sender<Command>> = OnceLock::new();
+
+#[cfg(windows)]
+unsafe extern "system" fn on_foreground_changed(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    if let Some(sender) = SENDER.get() {
+        let _ = sender.send(Command::ForegroundChanged);
+    }
+}
+
+#[cfg(windows)]
+pub fn foreground_hook_thread(sender: mpsc::Sender<Command>) -> Result<()> {
+    if SENDER.set(sender).is_err() {
+        bail!("Foreground hook already installed");
+    }
+
+    let hook = unsafe {
+        SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_FOREGROUND,
+            HMODULE::default(),
+            Some(on_foreground_changed),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        )
+    };
+    if hook.is_invalid() {
+        bail!("SetWinEventHook failed");
+    }
+
+    let mut message = MSG::default();
+    unsafe {
+        while GetMessageW(&mut message, HWND::default(), 0, 0).as_bool() {
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+        let _ = UnhookWinEvent(hook);
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn foreground_hook_thread(sender: mpsc::Sender<Command>) -> Result<()> {
+    crate::platform::watch_foreground(|| {
+        let _ = sender.send(Command::ForegroundChanged);
+    })
+}
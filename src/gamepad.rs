@@ -0,0 +1,150 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+#[cfg(windows)]
+use windows::Win32::UI::Input::XboxController::{
+    XInputGetState, XINPUT_GAMEPAD, XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE,
+    XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE, XINPUT_GAMEPAD_TRIGGER_THRESHOLD, XINPUT_STATE,
+};
+
+use crate::{platform, State};
+
+#[cfg(windows)]
+const CONTROLLER_COUNT: u32 = 4;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const DISCONNECTED_RETRY: Duration = Duration::from_secs(2);
+
+#[cfg(windows)]
+fn stick_active(x: i16, y: i16, deadzone: u16) -> bool {
+    let (x, y) = (x as f32, y as f32);
+    (x * x + y * y).sqrt() > deadzone as f32
+}
+
+#[cfg(windows)]
+fn is_active(gamepad: &XINPUT_GAMEPAD) -> bool {
+    let trigger_threshold = XINPUT_GAMEPAD_TRIGGER_THRESHOLD.0 as u8;
+    gamepad.wButtons.0 != 0
+        || gamepad.bLeftTrigger > trigger_threshold
+        || gamepad.bRightTrigger > trigger_threshold
+        || stick_active(
+            gamepad.sThumbLX,
+            gamepad.sThumbLY,
+            XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE.0,
+        )
+        || stick_active(
+            gamepad.sThumbRX,
+            gamepad.sThumbRY,
+            XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE.0,
+        )
+}
+
+#[cfg(windows)]
+pub fn gamepad_thread(state: Arc<State>) -> ! {
+    let mut retry_at = [None; CONTROLLER_COUNT as usize];
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if !state.config.read().unwrap().gamepad_input {
+            continue;
+        }
+
+        for (index, retry_at) in (0..CONTROLLER_COUNT).zip(retry_at.iter_mut()) {
+            if retry_at.is_some_and(|t| Instant::now() < t) {
+                continue;
+            }
+            let mut xinput_state = XINPUT_STATE::default();
+            if unsafe { XInputGetState(index, &mut xinput_state) } != 0 {
+                *retry_at = Some(Instant::now() + DISCONNECTED_RETRY);
+                continue;
+            }
+            *retry_at = None;
+            if is_active(&xinput_state.Gamepad) {
+                let now = platform::tick_count();
+                state.last_gamepad_input.store(now, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Axis moves within this distance of the center are treated as stick drift.
+#[cfg(target_os = "linux")]
+const AXIS_DEADZONE: i16 = 8000;
+
+/// Reads the queued joystick events, returns whether any was input and `None` once unplugged.
+#[cfg(target_os = "linux")]
+fn read_events(device: &mut std::fs::File) -> Option<bool> {
+    use std::io::{ErrorKind, Read};
+    // struct js_event from linux/joystick.h: time, value, type, number.
+    const JS_EVENT_BUTTON: u8 = 0x01;
+    const JS_EVENT_AXIS: u8 = 0x02;
+    let mut active = false;
+    let mut event = [0u8; 8];
+    loop {
+        match device.read(&mut event) {
+            Ok(8) => {
+                let value = i16::from_ne_bytes([event[4], event[5]]);
+                // JS_EVENT_INIT is or'ed into the type of the state reported on open, so it's skipped.
+                active |= match event[6] {
+                    JS_EVENT_BUTTON => value != 0,
+                    JS_EVENT_AXIS => value.unsigned_abs() > AXIS_DEADZONE as u16,
+                    _ => false,
+                };
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Some(active),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn gamepad_thread(state: Arc<State>) -> ! {
+    use std::{collections::HashMap, fs, os::unix::fs::OpenOptionsExt};
+
+    let mut devices = HashMap::new();
+    let mut rescan_at = Instant::now();
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if !state.config.read().unwrap().gamepad_input {
+            devices.clear();
+            continue;
+        }
+
+        if Instant::now() >= rescan_at {
+            rescan_at = Instant::now() + DISCONNECTED_RETRY;
+            let paths = fs::read_dir("/dev/input").into_iter().flatten().flatten();
+            for path in paths.map(|entry| entry.path()) {
+                let is_joystick = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("js"));
+                if !is_joystick || devices.contains_key(&path) {
+                    continue;
+                }
+                let device = fs::OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&path);
+                if let Ok(device) = device {
+                    devices.insert(path, device);
+                }
+            }
+        }
+
+        let mut active = false;
+        devices.retain(|_, device| match read_events(device) {
+            Some(input) => {
+                active |= input;
+                true
+            }
+            None => false,
+        });
+        if active {
+            state
+                .last_gamepad_input
+                .store(platform::tick_count(), Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{anyhow, bail, Context, Result};
+use image::RgbImage;
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
+        GetDIBits, GetMonitorInfoW, MonitorFromWindow, ReleaseDC, SelectObject, BITMAPINFO,
+        BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS, HBITMAP, HDC, MONITORINFO,
+        MONITOR_DEFAULTTONEAREST, SRCCOPY,
+    },
+    Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+    UI::WindowsAndMessaging::GetWindowRect,
+};
+
+use crate::CaptureMode;
+
+const PW_RENDERFULLCONTENT: PRINT_WINDOW_FLAGS = PRINT_WINDOW_FLAGS(2);
+
+fn monitor_rect(window: HWND) -> Result<RECT> {
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        let monitor = MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST);
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            bail!("Could not get monitor info");
+        }
+    }
+    Ok(info.rcMonitor)
+}
+
+fn read_bitmap(dc: HDC, bitmap: HBITMAP, width: i32, height: i32) -> Result<RgbImage> {
+    let mut info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let lines = unsafe {
+        GetDIBits(
+            dc,
+            bitmap,
+            0,
+            height as u32,
+            Some(pixels.as_mut_ptr().cast()),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    };
+    if lines != height {
+        bail!("Could not read bitmap");
+    }
+    let rgb = pixels
+        .chunks_exact(4)
+        .flat_map(|p| [p[2], p[1], p[0]])
+        .collect();
+    RgbImage::from_raw(width as u32, height as u32, rgb).context("Invalid bitmap size")
+}
+
+pub fn capture(id: u32, mode: CaptureMode) -> Result<RgbImage> {
+    let window = HWND(id as isize);
+    let rect = match mode {
+        CaptureMode::Monitor => monitor_rect(window)?,
+        CaptureMode::Window => {
+            let mut rect = RECT::default();
+            unsafe { GetWindowRect(window, &mut rect)? };
+            rect
+        }
+    };
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    if width <= 0 || height <= 0 {
+        bail!("Empty capture area");
+    }
+    unsafe {
+        let screen = GetDC(HWND::default());
+        let dc = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, width, height);
+        let previous = SelectObject(dc, bitmap);
+        let copied = match mode {
+            CaptureMode::Monitor => BitBlt(
+                dc,
+                0,
+                0,
+                width,
+                height,
+                screen,
+                rect.left,
+                rect.top,
+                SRCCOPY | CAPTUREBLT,
+            )
+            .context("BitBlt failed"),
+            CaptureMode::Window if PrintWindow(window, dc, PW_RENDERFULLCONTENT).as_bool() => {
+                Ok(())
+            }
+            CaptureMode::Window => Err(anyhow!("PrintWindow failed")),
+        };
+        SelectObject(dc, previous);
+        let image = copied.and_then(|()| read_bitmap(dc, bitmap, width, height));
+        let _ = DeleteObject(bitmap);
+        let _ = DeleteDC(dc);
+        ReleaseDC(HWND::default(), screen);
+        image
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use winsafe::{co, prelude::*, RegistryValue, HKEY};
+
+use crate::library::InstalledGame;
+#[cfg(windows)]
+use crate::library::Store;
+
+#[cfg(windows)]
+const GAMES_KEY: &str = "SOFTWARE\\WOW6432Node\\GOG.com\\Games";
+
+#[cfg(windows)]
+pub fn installed_games() -> Vec<InstalledGame> {
+    let Ok(games) =
+        HKEY::LOCAL_MACHINE.RegOpenKeyEx(Some(GAMES_KEY), co::REG_OPTION::NoValue, co::KEY::READ)
+    else {
+        return Vec::new();
+    };
+    let Ok(ids) = games.RegEnumKeyEx() else {
+        return Vec::new();
+    };
+    ids.filter_map(|id| id.ok())
+        .filter_map(|id| {
+            let value = |name: &str| match games.RegGetValue(Some(&id), Some(name)) {
+                Ok(RegistryValue::Sz(value)) => Some(value),
+                _ => None,
+            };
+            Some(InstalledGame {
+                store: Store::Gog,
+                name: value("gameName")?,
+                install_dir: PathBuf::from(value("path")?),
+                id,
+            })
+        })
+        .collect()
+}
+
+/// GOG Galaxy only exists on Windows, its Linux games are plain folders.
+#[cfg(not(windows))]
+pub fn installed_games() -> Vec<InstalledGame> {
+    Vec::new()
+}
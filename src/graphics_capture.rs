@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use image::RgbImage;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use windows::Graphics::Capture::GraphicsCaptureItem;
+use windows_capture::capture::GraphicsCaptureApiHandler;
+use winsafe::{prelude::*, HWND};
+
+use crate::{hdr, platform::get_window_bounds, CaptureMode, CaptureSettings};
+
+pub type FrameSlot = Arc<Mutex<Option<RgbImage>>>;
+
+pub struct Screenshot {
+    pub slot: FrameSlot,
+    pub hdr_white_level: Option<f32>,
+}
+
+impl GraphicsCaptureApiHandler for Screenshot {
+    type Flags = (FrameSlot, Option<f32>);
+    type Error = anyhow::Error;
+
+    fn new((slot, hdr_white_level): Self::Flags) -> Result<Self, Self::Error> {
+        Ok(Self {
+            slot,
+            hdr_white_level,
+        })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut windows_capture::frame::Frame,
+        capture_control: windows_capture::graphics_capture_api::InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        let mut buffer = frame.buffer()?;
+        let (width, height) = (buffer.width(), buffer.height());
+        let raw = buffer.as_raw_nopadding_buffer()?;
+        let image = match self.hdr_white_level {
+            Some(white_level) => hdr::tonemap(raw, width, height, white_level)?,
+            None => {
+                let pixels = raw
+                    .chunks_exact(4)
+                    .flat_map(|p| [p[0], p[1], p[2]])
+                    .collect();
+                RgbImage::from_raw(width, height, pixels).context("Invalid frame size")?
+            }
+        };
+        *self.slot.lock().unwrap() = Some(image);
+        capture_control.stop();
+        Ok(())
+    }
+}
+
+pub fn capture_frame<T: TryInto<GraphicsCaptureItem>>(
+    item: T,
+    hdr_white_level: Option<f32>,
+    cursor: Option<bool>,
+) -> Result<RgbImage> {
+    let slot = FrameSlot::default();
+    let color_format = match hdr_white_level {
+        Some(_) => windows_capture::settings::ColorFormat::Rgba16F,
+        None => windows_capture::settings::ColorFormat::Rgba8,
+    };
+    Screenshot::start(windows_capture::settings::Settings::new(
+        item,
+        match cursor {
+            Some(true) => windows_capture::settings::CursorCaptureSettings::WithCursor,
+            Some(false) => windows_capture::settings::CursorCaptureSettings::WithoutCursor,
+            None => windows_capture::settings::CursorCaptureSettings::Default,
+        },
+        windows_capture::settings::DrawBorderSettings::WithoutBorder,
+        color_format,
+        (slot.clone(), hdr_white_level),
+    ))?;
+    let image = slot.lock().unwrap().take().context("No frame captured")?;
+    Ok(image)
+}
+
+pub fn capture(id: u32, settings: &CaptureSettings) -> Result<RgbImage> {
+    let window = windows_capture::window::Window::from_raw_hwnd(id as _);
+    let hdr_white_level = settings.hdr_white_level.filter(|_| {
+        hdr::is_hdr_window(id).unwrap_or_else(|e| {
+            debug!("Could not detect HDR: {e:?}");
+            false
+        })
+    });
+    match settings.mode {
+        CaptureMode::Monitor if settings.span_monitors => {
+            capture_spanning(id, hdr_white_level, settings.cursor)
+        }
+        CaptureMode::Monitor => {
+            let monitor = window.monitor().context("No monitor for window")?;
+            capture_frame(monitor, hdr_white_level, settings.cursor)
+        }
+        CaptureMode::Window => capture_frame(window, hdr_white_level, settings.cursor),
+    }
+}
+
+pub fn capture_spanning(
+    id: u32,
+    hdr_white_level: Option<f32>,
+    cursor: Option<bool>,
+) -> Result<RgbImage> {
+    let bounds = get_window_bounds(&unsafe { HWND::from_ptr(id as _) })?;
+    let mut parts = Vec::new();
+    for monitor in windows_capture::monitor::Monitor::enumerate()? {
+        let handle = unsafe { winsafe::HMONITOR::from_ptr(monitor.as_raw_hmonitor() as _) };
+        let mut info = winsafe::MONITORINFOEX::default();
+        handle.GetMonitorInfo(&mut info)?;
+        let rect = info.rcMonitor;
+        let overlaps = rect.left < bounds.right
+            && rect.right > bounds.left
+            && rect.top < bounds.bottom
+            && rect.bottom > bounds.top;
+        if overlaps {
+            parts.push((rect, capture_frame(monitor, hdr_white_level, cursor)?));
+        }
+    }
+    let area = parts
+        .iter()
+        .map(|(rect, _)| *rect)
+        .reduce(|a, b| winsafe::RECT {
+            left: a.left.min(b.left),
+            top: a.top.min(b.top),
+            right: a.right.max(b.right),
+            bottom: a.bottom.max(b.bottom),
+        })
+        .context("Window is on no monitor")?;
+    let mut image = RgbImage::new(
+        (area.right - area.left) as u32,
+        (area.bottom - area.top) as u32,
+    );
+    for (rect, part) in &parts {
+        image::imageops::replace(
+            &mut image,
+            part,
+            (rect.left - area.left) as i64,
+            (rect.top - area.top) as i64,
+        );
+    }
+    let left = bounds.left.max(area.left);
+    let top = bounds.top.max(area.top);
+    let (x, y) = ((left - area.left) as u32, (top - area.top) as u32);
+    let width = (bounds.right.min(area.right) - left) as u32;
+    let height = (bounds.bottom.min(area.bottom) - top) as u32;
+    Ok(image::imageops::crop_imm(&image, x, y, width, height).to_image())
+}
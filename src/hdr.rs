@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use image::RgbImage;
+use windows::{
+    core::Interface,
+    Win32::Graphics::{
+        Dxgi::{
+            Common::DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, CreateDXGIFactory1, IDXGIFactory1,
+            IDXGIOutput6, DXGI_OUTPUT_DESC1,
+        },
+        Gdi::HMONITOR,
+    },
+};
+use winsafe::{co, prelude::*, HWND};
+
+const SCRGB_WHITE_NITS: f32 = 80.0;
+
+pub fn is_hdr_window(id: u32) -> Result<bool> {
+    let monitor =
+        unsafe { HWND::from_ptr(id as _) }.MonitorFromWindow(co::MONITOR::DEFAULTTONEAREST);
+    let monitor = HMONITOR(monitor.ptr() as isize);
+    unsafe {
+        let factory: IDXGIFactory1 = CreateDXGIFactory1().context("Creating DXGI factory")?;
+        let mut adapter_index = 0;
+        while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+            let mut output_index = 0;
+            while let Ok(output) = adapter.EnumOutputs(output_index) {
+                let mut desc = DXGI_OUTPUT_DESC1::default();
+                output.cast::<IDXGIOutput6>()?.GetDesc1(&mut desc)?;
+                if desc.Monitor == monitor {
+                    return Ok(desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020);
+                }
+                output_index += 1;
+            }
+            adapter_index += 1;
+        }
+    }
+    Ok(false)
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn compress_highlights(value: f32) -> f32 {
+    const KNEE: f32 = 0.8;
+    if value <= KNEE {
+        value
+    } else {
+        KNEE + (1.0 - KNEE) * (1.0 - (-(value - KNEE) / (1.0 - KNEE)).exp())
+    }
+}
+
+fn srgb_encode(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let encoded = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+pub fn tonemap(raw: &[u8], width: u32, height: u32, white_level: f32) -> Result<RgbImage> {
+    let scale = SCRGB_WHITE_NITS / white_level;
+    let pixels = raw
+        .chunks_exact(8)
+        .flat_map(|p| {
+            let channel = |i: usize| {
+                let value = f16_to_f32(u16::from_le_bytes([p[i], p[i + 1]])) * scale;
+                srgb_encode(compress_highlights(value.max(0.0)))
+            };
+            [channel(0), channel(2), channel(4)]
+        })
+        .collect();
+    RgbImage::from_raw(width, height, pixels).context("Invalid frame size")
+}
@@ -0,0 +1,85 @@
+use anyhow::{bail, Result};
+use std::{ffi::OsStr, process};
+use tracing::{debug, warn};
+
+/// A command for `program` that doesn't open a console window.
+#[cfg(windows)]
+pub fn hidden_command(program: impl AsRef<OsStr>) -> process::Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let mut command = process::Command::new(program);
+    command.creation_flags(CREATE_NO_WINDOW);
+    command
+}
+
+#[cfg(not(windows))]
+pub fn hidden_command(program: impl AsRef<OsStr>) -> process::Command {
+    process::Command::new(program)
+}
+
+pub fn split_args(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quoted {
+        bail!("Unclosed quote in command {command:?}");
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        bail!("Command is empty");
+    }
+    Ok(args)
+}
+
+fn substitute(arg: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(arg.to_owned(), |arg, (name, value)| {
+        arg.replace(&format!("{{{name}}}"), value)
+    })
+}
+
+pub fn run(hook: &str, command: &str, values: &[(&str, &str)]) {
+    let args = match split_args(command) {
+        Ok(args) => args,
+        Err(e) => {
+            warn!(hook, "Invalid hook command: {e:?}");
+            return;
+        }
+    };
+    let args: Vec<_> = args.iter().map(|arg| substitute(arg, values)).collect();
+    let mut child = match hidden_command(&args[0]).args(&args[1..]).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!(hook, program = %args[0], "Could not run hook command: {e:?}");
+            return;
+        }
+    };
+    debug!(hook, pid = child.id(), "Started hook command");
+    // Waiting reaps the child, which would otherwise stay a zombie for as long as we run.
+    let hook = hook.to_owned();
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => warn!(hook, "Hook command failed with {status}"),
+        Ok(_) => {}
+        Err(e) => warn!(hook, "Could not wait for hook command: {e:?}"),
+    });
+}
@@ -0,0 +1,167 @@
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use anyhow::Result;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, Rgb, RgbImage};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use tracing::warn;
+
+pub const THUMBNAIL_DIR: &str = ".thumbs";
+const THUMBNAIL_SIZE: u32 = 320;
+const SHARPNESS_WIDTH: u32 = 960;
+const FONT_PATHS: &[&str] = &[
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+pub fn dhash(image: &RgbImage) -> u64 {
+    let small = image::imageops::grayscale(image);
+    let small = image::imageops::resize(&small, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn is_blank(image: &RgbImage) -> bool {
+    const MIN_BRIGHTNESS: f64 = 10.0;
+    const MIN_DEVIATION: f64 = 4.0;
+    let small = image::imageops::grayscale(image);
+    let small = image::imageops::resize(&small, 64, 36, FilterType::Triangle);
+    let count = small.pixels().len() as f64;
+    let mean = small.pixels().map(|p| p[0] as f64).sum::<f64>() / count;
+    let variance = small
+        .pixels()
+        .map(|p| (p[0] as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    mean < MIN_BRIGHTNESS || variance.sqrt() < MIN_DEVIATION
+}
+
+pub fn sharpness(image: &RgbImage) -> f64 {
+    let gray = image::imageops::grayscale(image);
+    let gray = if gray.width() > SHARPNESS_WIDTH {
+        let height = (gray.height() * SHARPNESS_WIDTH / gray.width()).max(3);
+        image::imageops::resize(&gray, SHARPNESS_WIDTH, height, FilterType::Triangle)
+    } else {
+        gray
+    };
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    let pixel = |x: u32, y: u32| gray.get_pixel(x, y)[0] as f64;
+    let mut values = Vec::with_capacity(((width - 2) * (height - 2)) as usize);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            values.push(
+                pixel(x - 1, y) + pixel(x + 1, y) + pixel(x, y - 1) + pixel(x, y + 1)
+                    - 4.0 * pixel(x, y),
+            );
+        }
+    }
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count
+}
+
+pub fn downscale(image: &RgbImage, max_dimension: u32) -> Option<RgbImage> {
+    let (width, height) = image.dimensions();
+    let largest = width.max(height);
+    if largest <= max_dimension {
+        return None;
+    }
+    let scale = max_dimension as f64 / largest as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    Some(image::imageops::resize(
+        image,
+        new_width,
+        new_height,
+        FilterType::Lanczos3,
+    ))
+}
+
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub fn thumbnail_path(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().unwrap_or_default();
+    parent.join(THUMBNAIL_DIR).join(stem).with_extension("jpg")
+}
+
+pub fn save_thumbnail(image: &RgbImage, path: &Path) -> Result<PathBuf> {
+    let thumbnail_path = thumbnail_path(path);
+    if let Some(dir) = thumbnail_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let thumbnail = downscale(image, THUMBNAIL_SIZE);
+    let mut file = BufWriter::new(File::create(&thumbnail_path)?);
+    thumbnail
+        .as_ref()
+        .unwrap_or(image)
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut file, 80))?;
+    file.flush()?;
+    Ok(thumbnail_path)
+}
+
+fn font() -> Option<&'static FontVec> {
+    static FONT: OnceLock<Option<FontVec>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let font = FONT_PATHS
+            .iter()
+            .find_map(|path| FontVec::try_from_vec(fs::read(path).ok()?).ok());
+        if font.is_none() {
+            warn!("Could not load a system font, text will not be drawn");
+        }
+        font
+    })
+    .as_ref()
+}
+
+pub fn text_width(text: &str, size: f32) -> f32 {
+    let Some(font) = font() else {
+        return 0.0;
+    };
+    let font = font.as_scaled(PxScale::from(size));
+    text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+}
+
+pub fn draw_text(image: &mut RgbImage, text: &str, x: i32, y: i32, size: f32, color: Rgb<u8>) {
+    let Some(font) = font() else {
+        return;
+    };
+    let font = font.as_scaled(PxScale::from(size));
+    let mut caret = x as f32;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        let glyph = id.with_scale_and_position(size, (caret, y as f32 + font.ascent()));
+        caret += font.h_advance(id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= image.width() as i32 || py >= image.height() as i32 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            for (channel, target) in pixel.0.iter_mut().zip(color.0) {
+                *channel = (*channel as f32 * (1.0 - coverage) + target as f32 * coverage) as u8;
+            }
+        });
+    }
+}
@@ -0,0 +1,412 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+use rusqlite::{params, types::Type, Connection};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::session::Session;
+
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT NOT NULL,
+    game TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    window_title TEXT NOT NULL,
+    process_path TEXT,
+    screenshot_count INTEGER NOT NULL,
+    PRIMARY KEY (game, id)
+);
+CREATE TABLE IF NOT EXISTS screenshots (
+    path TEXT PRIMARY KEY,
+    game TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    hash INTEGER NOT NULL,
+    window_title TEXT NOT NULL,
+    session_id TEXT
+);
+CREATE INDEX IF NOT EXISTS screenshots_game_timestamp ON screenshots (game, timestamp);
+",
+    "
+ALTER TABLE sessions ADD COLUMN played_seconds REAL NOT NULL DEFAULT 0;
+CREATE TABLE playtime (
+    game TEXT NOT NULL,
+    day TEXT NOT NULL,
+    seconds REAL NOT NULL,
+    PRIMARY KEY (game, day)
+);
+",
+    "
+ALTER TABLE screenshots ADD COLUMN archive TEXT;
+",
+    "
+CREATE VIRTUAL TABLE screenshot_text USING fts5(path UNINDEXED, text);
+",
+    "
+ALTER TABLE screenshots ADD COLUMN capture_method TEXT;
+",
+];
+
+fn migrate(connection: &Connection) -> Result<()> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        connection.execute_batch(migration)?;
+        connection.pragma_update(None, "user_version", i as i64 + 1)?;
+    }
+    Ok(())
+}
+
+pub struct ScreenshotRecord<'a> {
+    pub path: &'a Path,
+    pub game: &'a str,
+    pub timestamp: DateTime<Local>,
+    pub hash: u64,
+    pub window_title: &'a str,
+    pub session_id: Option<&'a str>,
+    pub capture_method: &'a str,
+}
+
+pub struct SessionRow {
+    pub id: String,
+    pub game: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub played: Duration,
+    pub screenshot_count: u32,
+}
+
+#[derive(Clone)]
+pub struct ScreenshotRow {
+    pub path: PathBuf,
+    pub game: String,
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+#[derive(Default, PartialEq)]
+pub struct SessionFilter<'a> {
+    pub game: Option<&'a str>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+pub struct TextMatch {
+    pub path: PathBuf,
+    pub game: String,
+    pub timestamp: DateTime<FixedOffset>,
+    pub snippet: String,
+}
+
+pub struct GameStats {
+    pub game: String,
+    pub sessions: u32,
+    pub screenshots: u32,
+    pub played: Duration,
+    pub first_played: Option<DateTime<FixedOffset>>,
+    pub last_played: Option<DateTime<FixedOffset>>,
+}
+
+fn parse_time(text: String) -> rusqlite::Result<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(&text)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+}
+
+pub struct Index {
+    connection: Connection,
+    root: PathBuf,
+}
+
+impl Index {
+    pub fn open(target_folder: &Path) -> Result<Self> {
+        fs::create_dir_all(target_folder)?;
+        let connection = Connection::open(target_folder.join("index.sqlite"))?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        migrate(&connection)?;
+        Ok(Self {
+            connection,
+            root: target_folder.to_owned(),
+        })
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    pub fn record_screenshot(&self, record: &ScreenshotRecord) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO screenshots (path, game, timestamp, hash, window_title, session_id, capture_method)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.relative(record.path),
+                record.game,
+                record.timestamp.to_rfc3339(),
+                record.hash as i64,
+                record.window_title,
+                record.session_id,
+                record.capture_method,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_session(&self, session: &Session) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO sessions (id, game, start, end, window_title, process_path, screenshot_count, played_seconds)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (game, id) DO UPDATE SET
+                end = excluded.end,
+                screenshot_count = excluded.screenshot_count,
+                played_seconds = excluded.played_seconds",
+            params![
+                session.id,
+                session.game,
+                session.start.to_rfc3339(),
+                session.end.to_rfc3339(),
+                session.window_title,
+                session
+                    .process_path
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().into_owned()),
+                session.screenshot_count,
+                session.played.as_secs_f64(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_playtime(&self, game: &str, day: NaiveDate, played: Duration) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO playtime (game, day, seconds) VALUES (?1, ?2, ?3)
+             ON CONFLICT (game, day) DO UPDATE SET seconds = seconds + excluded.seconds",
+            params![game, day.to_string(), played.as_secs_f64()],
+        )?;
+        Ok(())
+    }
+
+    pub fn total_playtime(&self, game: &str) -> Result<Duration> {
+        let seconds: f64 = self.connection.query_row(
+            "SELECT COALESCE(SUM(seconds), 0) FROM playtime WHERE game = ?1",
+            params![game],
+            |row| row.get(0),
+        )?;
+        Ok(Duration::from_secs_f64(seconds))
+    }
+
+    pub fn sessions_on(&self, day: NaiveDate) -> Result<Vec<SessionRow>> {
+        self.sessions(&SessionFilter {
+            game: None,
+            from: Some(day),
+            to: Some(day),
+        })
+    }
+
+    pub fn sessions(&self, filter: &SessionFilter) -> Result<Vec<SessionRow>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, game, start, end, played_seconds, screenshot_count FROM sessions
+             WHERE (?1 IS NULL OR game = ?1 COLLATE NOCASE)
+                AND (?2 IS NULL OR substr(start, 1, 10) >= ?2)
+                AND (?3 IS NULL OR substr(start, 1, 10) <= ?3)
+             ORDER BY start",
+        )?;
+        let params = params![
+            filter.game,
+            filter.from.map(|d| d.to_string()),
+            filter.to.map(|d| d.to_string()),
+        ];
+        let rows = statement.query_map(params, |row| {
+            Ok(SessionRow {
+                id: row.get(0)?,
+                game: row.get(1)?,
+                start: parse_time(row.get(2)?)?,
+                end: parse_time(row.get(3)?)?,
+                played: Duration::from_secs_f64(row.get(4)?),
+                screenshot_count: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn session_screenshots(&self, game: &str, session_id: &str) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare(
+            "SELECT path FROM screenshots WHERE game = ?1 AND session_id = ?2 ORDER BY timestamp",
+        )?;
+        let rows = statement.query_map(params![game, session_id], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn session_screenshot_files(&self, game: &str, session_id: &str) -> Result<Vec<PathBuf>> {
+        Ok(self
+            .session_screenshots(game, session_id)?
+            .into_iter()
+            .map(|path| self.root.join(path))
+            .filter(|path| path.exists())
+            .collect())
+    }
+
+    pub fn session(&self, game: &str, session_id: &str) -> Result<Option<SessionRow>> {
+        Ok(self
+            .sessions(&SessionFilter {
+                game: Some(game),
+                ..Default::default()
+            })?
+            .into_iter()
+            .find(|s| s.id == session_id))
+    }
+
+    pub fn latest_session(&self, game: Option<&str>) -> Result<Option<SessionRow>> {
+        Ok(self
+            .sessions(&SessionFilter {
+                game,
+                ..Default::default()
+            })?
+            .pop())
+    }
+
+    pub fn game_stats(&self) -> Result<Vec<GameStats>> {
+        let mut statement = self.connection.prepare(
+            "SELECT g.game,
+                (SELECT COUNT(*) FROM sessions s WHERE s.game = g.game),
+                (SELECT COUNT(*) FROM screenshots c WHERE c.game = g.game),
+                (SELECT COALESCE(SUM(seconds), 0) FROM playtime p WHERE p.game = g.game),
+                (SELECT MIN(start) FROM sessions s WHERE s.game = g.game),
+                (SELECT MAX(end) FROM sessions s WHERE s.game = g.game)
+             FROM (SELECT game FROM sessions UNION SELECT game FROM screenshots) g
+             ORDER BY 4 DESC",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(GameStats {
+                game: row.get(0)?,
+                sessions: row.get(1)?,
+                screenshots: row.get(2)?,
+                played: Duration::from_secs_f64(row.get(3)?),
+                first_played: row
+                    .get::<_, Option<String>>(4)?
+                    .map(parse_time)
+                    .transpose()?,
+                last_played: row
+                    .get::<_, Option<String>>(5)?
+                    .map(parse_time)
+                    .transpose()?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn recent_screenshots(&self, limit: u32) -> Result<Vec<ScreenshotRow>> {
+        let mut statement = self.connection.prepare(
+            "SELECT path, game, timestamp FROM screenshots WHERE archive IS NULL
+             ORDER BY timestamp DESC LIMIT ?1",
+        )?;
+        let rows = statement.query_map(params![limit], |row| {
+            Ok(ScreenshotRow {
+                path: self.root.join(row.get::<_, String>(0)?),
+                game: row.get(1)?,
+                timestamp: parse_time(row.get(2)?)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn screenshot_count(&self, game: &str) -> Result<u32> {
+        Ok(self.connection.query_row(
+            "SELECT COUNT(*) FROM screenshots WHERE game = ?1",
+            params![game],
+            |row| row.get(0),
+        )?)
+    }
+
+    pub fn screenshot_count_on(&self, day: NaiveDate) -> Result<u32> {
+        Ok(self.connection.query_row(
+            "SELECT COUNT(*) FROM screenshots WHERE substr(timestamp, 1, 10) = ?1",
+            params![day.to_string()],
+            |row| row.get(0),
+        )?)
+    }
+
+    pub fn playtime_on(&self, day: NaiveDate) -> Result<Vec<(String, Duration)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT game, seconds FROM playtime WHERE day = ?1 ORDER BY seconds DESC")?;
+        let rows = statement.query_map(params![day.to_string()], |row| {
+            Ok((row.get(0)?, Duration::from_secs_f64(row.get(1)?)))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn daily_playtime(&self, from: NaiveDate) -> Result<Vec<(NaiveDate, Duration)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT day, SUM(seconds) FROM playtime WHERE day >= ?1 GROUP BY day ORDER BY day",
+        )?;
+        let rows = statement.query_map(params![from.to_string()], |row| {
+            let day: String = row.get(0)?;
+            let day = day.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e))
+            })?;
+            Ok((day, Duration::from_secs_f64(row.get(1)?)))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn archive_screenshot(&self, path: &Path, archive: &Path) -> Result<()> {
+        self.connection.execute(
+            "UPDATE screenshots SET archive = ?2 WHERE path = ?1",
+            params![self.relative(path), self.relative(archive)],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_screenshot(&self, path: &Path) -> Result<()> {
+        self.connection.execute(
+            "DELETE FROM screenshots WHERE path = ?1",
+            params![self.relative(path)],
+        )?;
+        self.connection.execute(
+            "DELETE FROM screenshot_text WHERE path = ?1",
+            params![self.relative(path)],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_text(&self, path: &Path, text: &str) -> Result<()> {
+        let path = self.relative(path);
+        self.connection
+            .execute("DELETE FROM screenshot_text WHERE path = ?1", params![path])?;
+        self.connection.execute(
+            "INSERT INTO screenshot_text (path, text) VALUES (?1, ?2)",
+            params![path, text],
+        )?;
+        Ok(())
+    }
+
+    pub fn search_text(&self, query: &str, game: Option<&str>) -> Result<Vec<TextMatch>> {
+        let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+        let mut statement = self.connection.prepare(
+            "SELECT t.path, s.game, s.timestamp, snippet(screenshot_text, 1, '[', ']', '...', 12)
+             FROM screenshot_text t JOIN screenshots s ON s.path = t.path
+             WHERE screenshot_text MATCH ?1 AND (?2 IS NULL OR s.game = ?2 COLLATE NOCASE)
+             ORDER BY s.timestamp",
+        )?;
+        let rows = statement.query_map(params![phrase, game], |row| {
+            Ok(TextMatch {
+                path: self.root.join(row.get::<_, String>(0)?),
+                game: row.get(1)?,
+                timestamp: parse_time(row.get(2)?)?,
+                snippet: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+pub fn update(target_folder: &Path, f: impl FnOnce(&Index) -> Result<()>) {
+    if let Err(e) = Index::open(target_folder).and_then(|index| f(&index)) {
+        warn!("Could not update index: {e:?}");
+    }
+}
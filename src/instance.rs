@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+};
+#[cfg(windows)]
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS},
+        System::Threading::CreateMutexW,
+    },
+};
+
+fn instance_name(config_path: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    config_path
+        .to_string_lossy()
+        .to_lowercase()
+        .hash(&mut hasher);
+    format!("autogamejournal-{:016x}", hasher.finish())
+}
+
+#[cfg(windows)]
+pub fn is_first_instance(config_path: &Path) -> Result<bool> {
+    let name = HSTRING::from(format!("Local\\{}", instance_name(config_path)));
+    // The handle is intentionally kept open for the lifetime of the process.
+    unsafe {
+        let handle = CreateMutexW(None, false, &name).context("Creating instance mutex")?;
+        if GetLastError() == ERROR_ALREADY_EXISTS {
+            let _ = CloseHandle(handle);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_first_instance(config_path: &Path) -> Result<bool> {
+    use std::os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixListener},
+    };
+    // An abstract socket goes away with the process, so a crash never leaves a stale lock.
+    let address = SocketAddr::from_abstract_name(instance_name(config_path))?;
+    match UnixListener::bind_addr(&address) {
+        Ok(listener) => {
+            // The socket is intentionally kept bound for the lifetime of the process.
+            std::mem::forget(listener);
+            Ok(true)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Ok(false),
+        Err(e) => Err(e).context("Binding instance socket"),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_first_instance(config_path: &Path) -> Result<bool> {
+    use std::os::fd::AsRawFd;
+    // The kernel drops the lock with the process, so a crash never leaves a stale lock.
+    let path = std::env::temp_dir().join(format!("{}.lock", instance_name(config_path)));
+    let file = std::fs::File::create(&path).context("Creating instance lock file")?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() == std::io::ErrorKind::WouldBlock {
+            return Ok(false);
+        }
+        return Err(e).context("Locking instance lock file");
+    }
+    // The file is intentionally kept open for the lifetime of the process.
+    std::mem::forget(file);
+    Ok(true)
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{format_duration, imaging, index::Index};
+
+pub fn write_daily_journal(target_folder: &Path, day: NaiveDate) -> Result<PathBuf> {
+    let index = Index::open(target_folder)?;
+    let sessions = index.sessions_on(day)?;
+    let playtime = index.playtime_on(day)?;
+
+    let mut text = format!("# {day}\n");
+    if sessions.is_empty() && playtime.is_empty() {
+        text += "\nNo games played.\n";
+    }
+
+    let mut games: Vec<&str> = playtime.iter().map(|(g, _)| g.as_str()).collect();
+    for session in &sessions {
+        if !games.contains(&session.game.as_str()) {
+            games.push(&session.game);
+        }
+    }
+
+    for game in games {
+        writeln!(text, "\n## {game}\n")?;
+        if let Some((_, played)) = playtime.iter().find(|(g, _)| g == game) {
+            writeln!(text, "Played {}\n", format_duration(*played))?;
+        }
+        for session in sessions.iter().filter(|s| s.game == game) {
+            writeln!(
+                text,
+                "### {} – {} ({} played, {} screenshots)\n",
+                session.start.format("%H:%M"),
+                session.end.format("%H:%M"),
+                format_duration(session.played),
+                session.screenshot_count,
+            )?;
+            for path in index.session_screenshots(&session.game, &session.id)? {
+                let thumbnail = imaging::thumbnail_path(Path::new(&path));
+                let thumbnail = if target_folder.join(&thumbnail).exists() {
+                    thumbnail.to_string_lossy().replace('\\', "/")
+                } else {
+                    path.clone()
+                };
+                let path = path.replace(' ', "%20");
+                let thumbnail = thumbnail.replace(' ', "%20");
+                writeln!(
+                    text,
+                    "[<img src=\"../{thumbnail}\" width=\"320\">](../{path})"
+                )?;
+            }
+        }
+    }
+
+    let journal_dir = target_folder.join("journal");
+    fs::create_dir_all(&journal_dir)?;
+    let path = journal_dir.join(format!("{day}.md"));
+    fs::write(&path, text)?;
+    Ok(path)
+}
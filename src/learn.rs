@@ -0,0 +1,118 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::{info, warn};
+
+/// Captures of an app without a rule before a rule is suggested for it.
+const SUGGEST_AFTER: u32 = 3;
+
+/// An app that was captured without matching any rule.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SeenApp {
+    /// The process name, which a rule's `name` matches.
+    pub app: String,
+    /// The folder its screenshots were saved to.
+    pub name: String,
+    pub captures: u32,
+}
+
+struct Seen {
+    path: Option<PathBuf>,
+    apps: BTreeMap<String, SeenApp>,
+    generation: u64,
+}
+
+static SEEN: Mutex<Seen> = Mutex::new(Seen {
+    path: None,
+    apps: BTreeMap::new(),
+    generation: 0,
+});
+
+fn save(seen: &Seen) -> Result<()> {
+    if let Some(path) = &seen.path {
+        let apps: Vec<&SeenApp> = seen.apps.values().collect();
+        fs::write(path, serde_json::to_string_pretty(&apps)?)?;
+    }
+    Ok(())
+}
+
+/// Applies a change and saves the list, `change` returns whether the suggestions changed.
+fn update(change: impl FnOnce(&mut BTreeMap<String, SeenApp>) -> bool) {
+    let mut seen = SEEN.lock().unwrap();
+    if change(&mut seen.apps) {
+        seen.generation += 1;
+    }
+    if let Err(e) = save(&seen) {
+        warn!("Could not save seen apps: {e:?}");
+    }
+}
+
+/// Reads the apps seen in earlier runs from the journal folder.
+pub fn load(target_folder: &Path) {
+    let path = target_folder.join("seen_apps.json");
+    let apps: Vec<SeenApp> = match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            warn!(?path, "Could not read seen apps: {e:?}");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    };
+    let mut seen = SEEN.lock().unwrap();
+    seen.apps = apps.into_iter().map(|app| (app.app.clone(), app)).collect();
+    seen.path = Some(path);
+    seen.generation += 1;
+}
+
+/// Counts a capture of an app that no rule matched.
+pub fn record(app: &str, name: &str) {
+    update(|apps| {
+        let seen = apps.entry(app.to_owned()).or_insert_with(|| SeenApp {
+            app: app.to_owned(),
+            name: name.to_owned(),
+            captures: 0,
+        });
+        let renamed = seen.name != name;
+        seen.name = name.to_owned();
+        seen.captures += 1;
+        if seen.captures == SUGGEST_AFTER {
+            info!(app, "Suggesting a rule for a frequently captured app");
+        }
+        seen.captures == SUGGEST_AFTER || (renamed && seen.captures > SUGGEST_AFTER)
+    });
+}
+
+/// Asks about the app again after a few more captures.
+pub fn later(app: &str) {
+    update(|apps| {
+        if let Some(seen) = apps.get_mut(app) {
+            seen.captures = 0;
+        }
+        true
+    });
+}
+
+/// Stops suggesting the app, once it has a rule.
+pub fn forget(app: &str) {
+    update(|apps| apps.remove(app).is_some());
+}
+
+/// Changes whenever the list does.
+pub fn generation() -> u64 {
+    SEEN.lock().unwrap().generation
+}
+
+/// Apps captured often enough without a rule to suggest one.
+pub fn suggestions() -> Vec<SeenApp> {
+    SEEN.lock()
+        .unwrap()
+        .apps
+        .values()
+        .filter(|seen| seen.captures >= SUGGEST_AFTER)
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,975 @@
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+compile_error!(
+    "autogamejournal supports Windows, Linux and macOS, see the Platform support section of the README"
+);
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use clap::Parser;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc, Arc, Mutex, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+mod activity;
+mod animation;
+mod api;
+mod archive;
+mod autostart;
+mod calendar;
+mod caption;
+mod capture;
+mod cli;
+mod config;
+mod contact_sheet;
+mod crash;
+mod discord;
+#[cfg(windows)]
+mod duplication;
+mod elevation;
+mod encoder;
+mod epic;
+mod foreground;
+#[cfg(any(windows, target_os = "linux"))]
+mod gamepad;
+#[cfg(windows)]
+mod gdi;
+mod gog;
+#[cfg(windows)]
+mod graphics_capture;
+#[cfg(windows)]
+mod hdr;
+mod hooks;
+mod imaging;
+mod index;
+mod instance;
+mod journal;
+mod learn;
+mod library;
+mod maintenance;
+mod metrics;
+mod notification;
+mod ocr;
+mod platform;
+mod power;
+mod recent;
+mod region;
+mod rules;
+#[cfg(windows)]
+mod rules_editor;
+mod schedule;
+mod screenshot;
+mod script;
+mod session;
+#[cfg(windows)]
+mod settings;
+#[cfg(windows)]
+mod setup;
+mod spool;
+mod steam;
+mod template;
+mod timelapse;
+mod tray;
+mod upload;
+#[cfg(windows)]
+mod viewer;
+mod webhook;
+#[cfg(windows)]
+mod widgets;
+mod workstation;
+
+use capture::{Capturer, DesktopCapturer, Frame};
+use config::{
+    exe_dir, existing_config, find_config, load_config, read_document, rule_tables, write_document,
+    ByteSize, CaptureBackend, CaptureMode, CaptureSettings, Config, ImageFormat, LogLevel,
+    MatchMode, RuleEntry, TrayAction,
+};
+use platform::{edit_file, get_last_input_time, open_file, open_folder};
+use rules::{find_rule, get_valid_window, GameWindow, RuleTarget};
+use screenshot::{take_screenshot, Journal, Outcome};
+
+const RESTART_WAIT: Duration = Duration::from_secs(10);
+const ADAPTIVE_RECHECK: Duration = Duration::from_secs(10);
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Parser, Clone)]
+#[command(version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<cli::CliCommand>,
+    /// Path to the config file
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Override the folder screenshots are saved to
+    #[arg(long, global = true)]
+    target_folder: Option<PathBuf>,
+    /// Override the delay between screenshots, in seconds
+    #[arg(long, global = true)]
+    delay: Option<u64>,
+    /// Start with capturing paused
+    #[arg(long)]
+    paused: bool,
+    /// Log which rule matches the foreground window and whether it would be captured,
+    /// without saving anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Log debug messages, or trace messages when given twice
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    #[arg(long, hide = true)]
+    autostart: bool,
+    #[arg(long, hide = true)]
+    restarted: bool,
+}
+
+impl Args {
+    fn apply(&self, config: &mut Config) {
+        if let Some(target_folder) = &self.target_folder {
+            config.target_folder =
+                std::path::absolute(target_folder).unwrap_or_else(|_| target_folder.clone());
+        }
+        if let Some(delay) = self.delay {
+            config.screenshot_delay = delay;
+        }
+        match self.verbose {
+            0 => {}
+            1 => config.log_level = LogLevel::Debug,
+            _ => config.log_level = LogLevel::Trace,
+        }
+    }
+}
+
+enum Command {
+    CaptureNow,
+    CaptureLastGame,
+    ForegroundChanged,
+    Sleep,
+    Wake,
+    Shutdown,
+}
+
+struct Status {
+    game: Option<String>,
+    playtime: Duration,
+    app: Option<String>,
+    last_capture: Option<(DateTime<Local>, bool)>,
+    session_played: Duration,
+    day: NaiveDate,
+    screenshots_today: u32,
+    screenshots_taken: u64,
+    capture_errors: u64,
+}
+
+impl Status {
+    fn load(target_folder: &Path) -> Self {
+        let day = Local::now().date_naive();
+        let screenshots_today = index::Index::open(target_folder)
+            .and_then(|index| index.screenshot_count_on(day))
+            .unwrap_or_else(|e| {
+                warn!("Could not read today's screenshot count from index: {e:?}");
+                0
+            });
+        Self {
+            game: None,
+            playtime: Duration::ZERO,
+            app: None,
+            last_capture: None,
+            session_played: Duration::ZERO,
+            day,
+            screenshots_today,
+            screenshots_taken: 0,
+            capture_errors: 0,
+        }
+    }
+
+    fn roll_over(&mut self) {
+        let today = Local::now().date_naive();
+        if self.day != today {
+            self.day = today;
+            self.screenshots_today = 0;
+        }
+    }
+
+    fn record_capture(&mut self, success: bool) {
+        self.roll_over();
+        self.last_capture = Some((Local::now(), success));
+        if success {
+            self.screenshots_today += 1;
+            self.screenshots_taken += 1;
+        } else {
+            self.capture_errors += 1;
+        }
+    }
+}
+
+struct State {
+    args: Args,
+    config: RwLock<Config>,
+    status: Mutex<Status>,
+    paused: AtomicBool,
+    dry_run: AtomicBool,
+    snoozed_until: Mutex<Option<DateTime<Local>>>,
+    last_gamepad_input: AtomicU32,
+    activity: AtomicU32,
+    commands: mpsc::Sender<Command>,
+}
+
+type LogLevelHandle = tracing_subscriber::reload::Handle<
+    tracing_subscriber::filter::LevelFilter,
+    tracing_subscriber::Registry,
+>;
+
+static LOG_LEVEL: OnceLock<LogLevelHandle> = OnceLock::new();
+
+fn init_logging(log_dir: &Path, level: LogLevel) -> Result<()> {
+    use tracing_appender::rolling::{Builder, Rotation};
+    use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, reload};
+    let appender = Builder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("autogamejournal")
+        .filename_suffix("log")
+        .max_log_files(7)
+        .build(log_dir)?;
+    let (filter, handle) = reload::Layer::new(LevelFilter::from(level));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(appender).with_ansi(false))
+        .with(fmt::layer())
+        .try_init()?;
+    let _ = LOG_LEVEL.set(handle);
+    Ok(())
+}
+
+fn set_log_level(level: LogLevel) {
+    if let Some(handle) = LOG_LEVEL.get() {
+        if let Err(e) = handle.reload(tracing_subscriber::filter::LevelFilter::from(level)) {
+            warn!("Could not change log level: {e:?}");
+        }
+    }
+}
+
+fn reload_config(path: &Path, state: &State) {
+    match load_config(path, &state.args) {
+        Ok(c) => {
+            set_log_level(c.log_level);
+            *state.config.write().unwrap() = c;
+            info!("Reloaded config");
+        }
+        Err(e) => {
+            error!("Failed to reload config: {e:?}");
+            if let Err(e) =
+                notification::show_toast("autogamejournal: config not reloaded", &format!("{e:#}"))
+            {
+                warn!("Could not show notification: {e:?}");
+            }
+        }
+    }
+}
+
+fn config_watch_thread(path: PathBuf, state: Arc<State>) -> ! {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let watched = |state: &State| {
+        let script = state.config.read().unwrap().rules_script.clone();
+        (modified(&path), script.and_then(|script| modified(&script)))
+    };
+    let mut last_modified = watched(&state);
+
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let current = watched(&state);
+        if current != last_modified {
+            reload_config(&path, &state);
+            last_modified = watched(&state);
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn format_interval(seconds: u64) -> String {
+    match seconds {
+        s if s >= 3600 && s % 3600 == 0 => format!("{} h", s / 3600),
+        s if s >= 60 && s % 60 == 0 => format!("{} min", s / 60),
+        s => format!("{s} s"),
+    }
+}
+
+/// End of the current snooze, clearing it once it has passed.
+fn snoozed_until(state: &State) -> Option<DateTime<Local>> {
+    let mut snoozed_until = state.snoozed_until.lock().unwrap();
+    if snoozed_until.is_some_and(|until| until <= Local::now()) {
+        info!("Snooze ended, capturing again");
+        *snoozed_until = None;
+    }
+    *snoozed_until
+}
+
+fn snooze(state: &State, until: Option<DateTime<Local>>) {
+    match until {
+        Some(until) => info!("Snoozing until {until}"),
+        None => info!("Snooze cancelled"),
+    }
+    *state.snoozed_until.lock().unwrap() = until;
+}
+
+fn tomorrow() -> Option<DateTime<Local>> {
+    let day = Local::now().date_naive().succ_opt()?;
+    day.and_hms_opt(0, 0, 0)?
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+/// Why capturing is currently suspended, if it is.
+fn pause_reason(state: &State) -> Option<String> {
+    if state.paused.load(Ordering::Relaxed) {
+        Some("paused".to_owned())
+    } else if let Some(until) = snoozed_until(state) {
+        Some(format!("snoozed until {}", until.format("%H:%M")))
+    } else if let Some(reason) = workstation::suspend_reason() {
+        Some(reason.to_string())
+    } else if state.config.read().unwrap().schedule.is_quiet(Local::now()) {
+        Some("quiet hours".to_owned())
+    } else if state.config.read().unwrap().on_battery.pause && power::on_battery() {
+        Some("on battery".to_owned())
+    } else {
+        None
+    }
+}
+
+fn last_input_time(state: &State) -> Result<u32> {
+    let input = get_last_input_time()?;
+    let gamepad = state.last_gamepad_input.load(Ordering::Relaxed);
+    if gamepad != 0 && platform::tick_after(gamepad, input) {
+        Ok(gamepad)
+    } else {
+        Ok(input)
+    }
+}
+
+fn record_capture(state: &State, success: bool) {
+    state.status.lock().unwrap().record_capture(success);
+}
+
+fn update_status(state: &State, sessions: &session::SessionTracker) {
+    let mut status = state.status.lock().unwrap();
+    status.game = sessions.current().map(|s| s.game.clone());
+    status.playtime = sessions
+        .current()
+        .map(|s| s.total_playtime())
+        .unwrap_or_default();
+    status.session_played = sessions.current().map(|s| s.played).unwrap_or_default();
+}
+
+struct ExitFrame {
+    window: GameWindow,
+    frame: Frame,
+}
+
+fn capture_exit_frame(config: &Config, window: GameWindow) -> Option<ExitFrame> {
+    if !config.capture_on_exit || config.schedule.is_quiet(Local::now()) {
+        return None;
+    }
+    let settings = CaptureSettings {
+        mode: CaptureMode::Window,
+        ..config.capture_settings(&window.rule)
+    };
+    match DesktopCapturer.capture(window.id, &settings) {
+        Ok(frame) if settings.skip_blank && imaging::is_blank(&frame.image) => {
+            debug!(game = %window.name, "Exit frame is blank");
+            None
+        }
+        Ok(frame) => Some(ExitFrame { window, frame }),
+        Err(e) => {
+            debug!(game = %window.name, "Could not capture exit frame: {e:?}");
+            None
+        }
+    }
+}
+
+fn flush_exit_frame(
+    state: &State,
+    config: &Config,
+    sessions: &mut session::SessionTracker,
+    exit_frame: &mut Option<ExitFrame>,
+    last_hashes: &mut HashMap<String, u64>,
+) {
+    if let Some(exit) = exit_frame.take() {
+        let hash = imaging::dhash(&exit.frame.image);
+        match sessions.store(config, &exit.window, exit.frame, hash) {
+            Ok(_) => {
+                record_capture(state, true);
+                last_hashes.insert(exit.window.name, hash);
+            }
+            Err(e) => {
+                record_capture(state, false);
+                error!(game = %exit.window.name, "Could not save exit screenshot: {e:?}");
+            }
+        }
+    }
+}
+
+fn screenshot_thread(state: Arc<State>, commands: &mpsc::Receiver<Command>) {
+    let mut last_input = None;
+    let mut sessions = session::SessionTracker::default();
+    let mut last_hashes = HashMap::new();
+    let mut next_tick = Instant::now();
+    let mut last_tick = Instant::now();
+    let mut launch_capture: Option<Instant> = None;
+    let mut focused_window: Option<GameWindow> = None;
+    let mut last_window: Option<GameWindow> = None;
+    let mut recent_captures: HashMap<String, VecDeque<Instant>> = HashMap::new();
+    let mut exit_frame: Option<ExitFrame> = None;
+    let elevated = elevation::is_elevated();
+    let mut elevation_warned = HashSet::new();
+
+    loop {
+        let (delay, adaptive) = {
+            let config = state.config.read().unwrap();
+            let rule = focused_window.as_ref().map(|w| &w.rule);
+            let activity = state.activity.load(Ordering::Relaxed) as f64 / 1000.0;
+            let mut delay = config.capture_delay(rule, activity);
+            if let Some(min) = config.on_battery.screenshot_delay {
+                if power::on_battery() {
+                    delay = delay.max(Duration::from_secs(min));
+                }
+            }
+            (delay, config.is_adaptive(rule))
+        };
+        if focused_window.is_some() {
+            next_tick = next_tick.min(last_tick + delay);
+        }
+        let deadline = launch_capture.map_or(next_tick, |launch| launch.min(next_tick));
+        let mut timeout = deadline.saturating_duration_since(Instant::now());
+        if adaptive {
+            timeout = timeout.min(ADAPTIVE_RECHECK);
+        }
+        let command = commands.recv_timeout(timeout).ok();
+        if command.is_none() && Instant::now() < deadline {
+            continue;
+        }
+        if matches!(command, Some(Command::Shutdown)) {
+            let config = state.config.read().unwrap().clone();
+            flush_exit_frame(
+                &state,
+                &config,
+                &mut sessions,
+                &mut exit_frame,
+                &mut last_hashes,
+            );
+            sessions.end(&config);
+            encoder::flush();
+            info!("Screenshot thread stopped");
+            return;
+        }
+        if matches!(command, Some(Command::Sleep | Command::Wake)) {
+            let config = state.config.read().unwrap().clone();
+            flush_exit_frame(
+                &state,
+                &config,
+                &mut sessions,
+                &mut exit_frame,
+                &mut last_hashes,
+            );
+            sessions.end(&config);
+            update_status(&state, &sessions);
+            focused_window = None;
+            launch_capture = None;
+            last_input = None;
+            last_tick = Instant::now();
+            next_tick = last_tick + delay;
+            continue;
+        }
+        let manual = matches!(command, Some(Command::CaptureNow));
+        let foreground = matches!(command, Some(Command::ForegroundChanged));
+        let config = state.config.read().unwrap().clone();
+        let launch =
+            command.is_none() && launch_capture.is_some_and(|launch| launch <= Instant::now());
+        if launch {
+            launch_capture = None;
+        } else if command.is_none() {
+            last_tick = Instant::now();
+            next_tick = last_tick + IDLE_INTERVAL.max(delay);
+        }
+        if sessions.timed_out(&config) {
+            flush_exit_frame(
+                &state,
+                &config,
+                &mut sessions,
+                &mut exit_frame,
+                &mut last_hashes,
+            );
+        }
+        sessions.check_timeout(&config);
+        sessions.save_unsaved(&config);
+        update_status(&state, &sessions);
+        if let Some(reason) = workstation::suspend_reason() {
+            debug!("Capturing suspended: {reason}");
+            focused_window = None;
+            sessions.unfocused();
+            continue;
+        }
+        let dry_run = state.dry_run.load(Ordering::Relaxed);
+        if matches!(command, Some(Command::CaptureLastGame)) && dry_run {
+            info!("Dry run, not capturing the last game");
+            continue;
+        }
+        if matches!(command, Some(Command::CaptureLastGame)) {
+            let result = last_window
+                .as_ref()
+                .context("No game window has been focused yet")
+                .and_then(|window| {
+                    let settings = config.capture_settings(&window.rule);
+                    let frame = DesktopCapturer.capture(window.id, &settings)?;
+                    let hash = imaging::dhash(&frame.image);
+                    sessions.store(&config, window, frame, hash)?;
+                    last_hashes.insert(window.name.clone(), hash);
+                    Ok(window.name.clone())
+                });
+            record_capture(&state, result.is_ok());
+            let message = match result {
+                Ok(game) => format!("Saved screenshot of {game}"),
+                Err(e) => {
+                    warn!("Could not take screenshot: {e:?}");
+                    format!("Could not take screenshot: {e}")
+                }
+            };
+            if config.notifications {
+                if let Err(e) = notification::show_toast("autogamejournal", &message) {
+                    warn!("Could not show notification: {e:?}");
+                }
+            }
+            continue;
+        }
+        if (state.paused.load(Ordering::Relaxed) || snoozed_until(&state).is_some()) && !manual {
+            continue;
+        }
+        let window = match get_valid_window(&config, manual) {
+            Err(e) if dry_run => {
+                info!("Dry run, would not capture: {e:#}");
+                continue;
+            }
+            Err(e) => {
+                debug!("No valid window: {e:?}");
+                if let Some(window) = focused_window.take() {
+                    exit_frame = capture_exit_frame(&config, window);
+                }
+                sessions.unfocused();
+                continue;
+            }
+            Ok(o) => o,
+        };
+        if dry_run {
+            let rule = (window.rule.has_name_matcher() || window.rule.has_qualifier())
+                .then(|| window.rule.label());
+            let skipped = if !manual && config.schedule.is_quiet(Local::now()) {
+                Some("quiet hours")
+            } else if !manual && config.on_battery.pause && power::on_battery() {
+                Some("on battery")
+            } else {
+                None
+            };
+            match skipped {
+                Some(reason) => info!(
+                    process = %window.process, ?rule, game = %window.name,
+                    "Dry run, would not capture: {reason}"
+                ),
+                None => info!(
+                    process = %window.process, ?rule, game = %window.name,
+                    "Dry run, would capture"
+                ),
+            }
+            continue;
+        }
+        if let Some(previous) = focused_window.take().filter(|w| w.name != window.name) {
+            exit_frame = capture_exit_frame(&config, previous);
+        }
+        if exit_frame
+            .as_ref()
+            .is_some_and(|frame| frame.window.name == window.name)
+        {
+            exit_frame = None;
+        } else {
+            flush_exit_frame(
+                &state,
+                &config,
+                &mut sessions,
+                &mut exit_frame,
+                &mut last_hashes,
+            );
+        }
+
+        let was_focused = sessions.is_focused();
+        if sessions.focused(&window, &config) && config.capture_on_launch {
+            launch_capture =
+                Some(Instant::now() + Duration::from_secs(config.launch_capture_delay));
+        }
+        if window.elevated && !elevated && elevation_warned.insert(window.name.clone()) {
+            warn!(game = %window.name, "Game is running as administrator and may not be captured");
+            if config.notifications {
+                let _ = notification::show_toast(
+                    &format!("{} is running as administrator", window.name),
+                    "Screenshots may fail. Use \"Restart as administrator\" in the tray menu to capture it.",
+                );
+            }
+        }
+        focused_window = Some(window.clone());
+        last_window = Some(window.clone());
+        state.status.lock().unwrap().app = Some(window.process.clone());
+        update_status(&state, &sessions);
+        if foreground {
+            if !was_focused {
+                debug!(game = %window.name, "Game gained focus");
+                last_tick = Instant::now();
+                next_tick = last_tick + delay;
+            }
+            continue;
+        }
+        let forced = manual || launch;
+        if !forced {
+            last_tick = Instant::now();
+            next_tick = last_tick + delay;
+        }
+        if !manual && config.schedule.is_quiet(Local::now()) {
+            debug!("Quiet hours, skipping capture");
+            continue;
+        }
+        if !manual && config.on_battery.pause && power::on_battery() {
+            debug!("On battery, skipping capture");
+            continue;
+        }
+        let recent = recent_captures.entry(window.name.clone()).or_default();
+        while recent
+            .front()
+            .is_some_and(|time| time.elapsed() > Duration::from_secs(3600))
+        {
+            recent.pop_front();
+        }
+        if !manual {
+            if window
+                .rule
+                .max_per_hour
+                .is_some_and(|max| recent.len() >= max)
+            {
+                debug!(game = %window.name, "Hourly screenshot cap reached");
+                continue;
+            }
+            if window.rule.max_per_session.is_some_and(|max| {
+                sessions
+                    .current()
+                    .is_some_and(|s| s.screenshot_count >= max)
+            }) {
+                debug!(game = %window.name, "Session screenshot cap reached");
+                continue;
+            }
+        }
+
+        match last_input_time(&state) {
+            Ok(time) => {
+                let idle = last_input.is_some_and(|last| !platform::tick_after(time, last));
+                if idle && !forced {
+                    debug!("No input since last screenshot");
+                    continue;
+                }
+                last_input = Some(time);
+            }
+            Err(e) => {
+                warn!("Failed to get last input: {e:?}");
+            }
+        }
+
+        match take_screenshot(
+            &config,
+            &DesktopCapturer,
+            &mut sessions,
+            &window,
+            &mut last_hashes,
+            manual,
+            forced,
+        ) {
+            Ok(Outcome::Saved(_)) => {
+                record_capture(&state, true);
+                recent.push_back(Instant::now());
+            }
+            Ok(Outcome::Blank) => debug!(game = %window.name, "Frame is blank, skipping"),
+            Ok(Outcome::Duplicate) => {
+                debug!(game = %window.name, "Frame is a near-duplicate of the last screenshot")
+            }
+            Err(e) => {
+                record_capture(&state, false);
+                warn!(game = %window.name, "Could not take screenshot: {e:?}");
+            }
+        }
+    }
+}
+
+fn fatal_error(error: anyhow::Error) -> ! {
+    let message = format!("{error:#}");
+    eprintln!("{message}");
+    #[cfg(windows)]
+    {
+        use winsafe::{co, prelude::*, HWND};
+        let _ = HWND::NULL.MessageBox(&message, "autogamejournal", co::MB::ICONERROR);
+    }
+    std::process::exit(1);
+}
+
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(windows)]
+fn enable_dpi_awareness() {
+    use windows::Win32::UI::HiDpi::{
+        SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    };
+    if let Err(e) =
+        unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) }
+    {
+        eprintln!("Could not enable DPI awareness: {e:?}");
+    }
+}
+
+fn run_command(args: &Args, command: &cli::CliCommand) -> Result<()> {
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(match args.verbose {
+            0 => tracing::Level::WARN,
+            1 => tracing::Level::DEBUG,
+            _ => tracing::Level::TRACE,
+        })
+        .with_writer(std::io::stderr)
+        .try_init();
+    if command.opens_window() {
+        return run_window(args, command);
+    }
+    let config_path = match &args.config {
+        Some(path) => std::path::absolute(path)?,
+        None => find_config()?,
+    };
+    cli::execute(command, &load_config(&config_path, args)?)
+}
+
+#[cfg(windows)]
+fn run_window(args: &Args, command: &cli::CliCommand) -> Result<()> {
+    if let cli::CliCommand::Setup = command {
+        let config_path = match &args.config {
+            Some(path) => std::path::absolute(path)?,
+            None => existing_config().map_or_else(config::new_config_path, Ok)?,
+        };
+        return setup::run(&config_path);
+    }
+    let config_path = match &args.config {
+        Some(path) => std::path::absolute(path)?,
+        None => find_config()?,
+    };
+    match command {
+        cli::CliCommand::Settings => settings::run(&config_path),
+        cli::CliCommand::Rules => rules_editor::run(&config_path),
+        _ => viewer::run(&load_config(&config_path, args)?),
+    }
+}
+
+/// The settings, rules editor, setup wizard and journal viewer are Windows dialogs.
+#[cfg(not(windows))]
+fn run_window(_args: &Args, _command: &cli::CliCommand) -> Result<()> {
+    anyhow::bail!("This window is only available on Windows, edit the config file instead")
+}
+
+/// Runs the setup wizard in its own process and waits for it, falling back to the default
+/// config if it can't be shown. Returns `None` when the wizard was cancelled.
+#[cfg(windows)]
+fn first_run_config() -> Result<Option<PathBuf>> {
+    let path = config::new_config_path()?;
+    let exe = std::env::current_exe().context("Getting executable path")?;
+    let status = std::process::Command::new(exe)
+        .arg("--config")
+        .arg(&path)
+        .arg("setup")
+        .status()
+        .context("Starting the setup wizard")?;
+    if path.exists() {
+        return Ok(Some(path));
+    }
+    if !status.success() {
+        return find_config().map(Some);
+    }
+    Ok(None)
+}
+
+/// There is no setup wizard outside Windows, so the first run writes the default config.
+#[cfg(not(windows))]
+fn first_run_config() -> Result<Option<PathBuf>> {
+    find_config().map(Some)
+}
+
+pub fn run() {
+    #[cfg(windows)]
+    attach_console();
+    let args = Args::try_parse().unwrap_or_else(|e| {
+        if !e.use_stderr() {
+            e.exit();
+        }
+        fatal_error(e.into())
+    });
+    if let Some(command) = &args.command {
+        if command.opens_window() {
+            if let Err(e) = run_command(&args, command) {
+                fatal_error(e);
+            }
+            return;
+        }
+    }
+    #[cfg(windows)]
+    enable_dpi_awareness();
+    if let Some(command) = &args.command {
+        if !matches!(command, cli::CliCommand::Run) {
+            if let Err(e) = run_command(&args, command) {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+    if args.autostart {
+        if let Some(dir) = exe_dir() {
+            let _ = std::env::set_current_dir(dir);
+        }
+    }
+    let config_path = match &args.config {
+        Some(path) => std::path::absolute(path).unwrap_or_else(|e| fatal_error(e.into())),
+        None => match existing_config().map_or_else(first_run_config, |path| Ok(Some(path))) {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(e) => fatal_error(e),
+        },
+    };
+    let mut first_instance = instance::is_first_instance(&config_path);
+    if args.restarted {
+        let deadline = Instant::now() + RESTART_WAIT;
+        while matches!(first_instance, Ok(false)) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(250));
+            first_instance = instance::is_first_instance(&config_path);
+        }
+    }
+    match first_instance {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ =
+                notification::show_toast("autogamejournal", "autogamejournal is already running");
+            return;
+        }
+        Err(e) => eprintln!("Could not check for another instance: {e:?}"),
+    }
+    let config = load_config(&config_path, &args).unwrap_or_else(|e| fatal_error(e));
+    let log_dir = config_path.parent().unwrap_or(Path::new(".")).join("logs");
+    if let Err(e) = init_logging(&log_dir, config.log_level) {
+        eprintln!("Failed to initialize logging: {e:?}");
+    }
+    crash::install_hook(&log_dir);
+
+    if let Some(enabled) = config.autostart {
+        if enabled != autostart::is_enabled() {
+            if let Err(e) = autostart::set_enabled(enabled) {
+                error!("Could not update autostart: {e:?}");
+            }
+        }
+    }
+
+    let (command_sender, command_receiver) = mpsc::channel();
+    let status = Status::load(&config.target_folder);
+    recent::load(&config.target_folder);
+    upload::load(&config.target_folder);
+    learn::load(&config.target_folder);
+    let state = Arc::new(State {
+        paused: AtomicBool::new(args.paused),
+        dry_run: AtomicBool::new(args.dry_run),
+        snoozed_until: Mutex::new(None),
+        args,
+        config: RwLock::new(config),
+        status: Mutex::new(status),
+        last_gamepad_input: AtomicU32::new(0),
+        activity: AtomicU32::new(0),
+        commands: command_sender,
+    });
+
+    let screenshot_thread = std::thread::spawn({
+        let state = state.clone();
+        move || {
+            crash::supervise("Screenshot thread", || {
+                screenshot_thread(state.clone(), &command_receiver)
+            })
+        }
+    });
+    let _foreground_thread = std::thread::spawn({
+        let sender = state.commands.clone();
+        move || {
+            if let Err(e) = foreground::foreground_hook_thread(sender) {
+                error!("Foreground hook failed, falling back to polling: {e:?}");
+            }
+        }
+    });
+    let _workstation_thread = std::thread::spawn({
+        let sender = state.commands.clone();
+        move || {
+            if let Err(e) = workstation::workstation_thread(sender) {
+                error!("Could not watch for session and power changes: {e:?}");
+            }
+        }
+    });
+    let _library_thread = std::thread::spawn(library::scan);
+    #[cfg(any(windows, target_os = "linux"))]
+    let _gamepad_thread = std::thread::spawn({
+        let state = state.clone();
+        move || gamepad::gamepad_thread(state)
+    });
+    let _activity_thread = std::thread::spawn({
+        let state = state.clone();
+        move || activity::activity_thread(state)
+    });
+    let _spool_thread = std::thread::spawn({
+        let state = state.clone();
+        move || spool::spool_thread(state)
+    });
+    let _maintenance_thread = std::thread::spawn({
+        let state = state.clone();
+        move || maintenance::maintenance_thread(state)
+    });
+    let _upload_thread = std::thread::spawn({
+        let state = state.clone();
+        move || upload::upload_thread(state)
+    });
+    let _ocr_thread = std::thread::spawn({
+        let state = state.clone();
+        move || ocr::ocr_thread(state)
+    });
+    let _webhook_thread = std::thread::spawn({
+        let state = state.clone();
+        move || webhook::webhook_thread(state)
+    });
+    if let Some(port) = state.config.read().unwrap().api_port {
+        std::thread::spawn({
+            let state = state.clone();
+            move || {
+                if let Err(e) = api::api_thread(state, port) {
+                    error!("HTTP API stopped: {e:?}");
+                }
+            }
+        });
+    }
+    let _watch_thread = std::thread::spawn({
+        let state = state.clone();
+        let config_path = config_path.clone();
+        move || config_watch_thread(config_path, state)
+    });
+    tray::run_tray(state, config_path, log_dir, screenshot_thread);
+}
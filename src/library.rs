@@ -0,0 +1,73 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, RwLock},
+};
+use tracing::{debug, info};
+
+use crate::{epic, gog, steam};
+
+static LIBRARY: RwLock<Vec<InstalledGame>> = RwLock::new(Vec::new());
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    Steam,
+    Epic,
+    /// GOG Galaxy only exists on Windows.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Gog,
+}
+
+#[derive(Clone)]
+pub struct InstalledGame {
+    pub store: Store,
+    pub id: String,
+    pub name: String,
+    pub install_dir: PathBuf,
+}
+
+pub fn scan() {
+    let games: Vec<_> = steam::installed_games()
+        .into_iter()
+        .chain(epic::installed_games())
+        .chain(gog::installed_games())
+        .collect();
+    info!("Found {} installed games", games.len());
+    *LIBRARY.write().unwrap() = games;
+    cache().lock().unwrap().clear();
+}
+
+fn lowercase(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+pub fn find_installed(games: &[InstalledGame], exe: &Path) -> Option<InstalledGame> {
+    let exe = lowercase(exe);
+    games
+        .iter()
+        .filter(|game| !game.install_dir.as_os_str().is_empty())
+        .find(|game| exe.starts_with(lowercase(&game.install_dir)))
+        .cloned()
+}
+
+fn lookup(exe: &Path) -> Option<InstalledGame> {
+    let game = find_installed(&LIBRARY.read().unwrap(), exe).or_else(|| steam::find_near(exe))?;
+    debug!(store = ?game.store, id = %game.id, name = %game.name, "Found installed game for {exe:?}");
+    Some(game)
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, Option<InstalledGame>>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Option<InstalledGame>>>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+pub fn find_game(exe: &Path) -> Option<InstalledGame> {
+    cache()
+        .lock()
+        .unwrap()
+        .entry(exe.to_owned())
+        .or_insert_with(|| lookup(exe))
+        .clone()
+}
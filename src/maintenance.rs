@@ -0,0 +1,200 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Condvar, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    archive, contact_sheet, find_rule, imaging, index::Index, Config, RuleEntry, RuleTarget, State,
+};
+
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+static QUOTA_CHECK: Mutex<bool> = Mutex::new(false);
+static QUOTA_REQUESTED: Condvar = Condvar::new();
+
+pub struct ScreenshotFile {
+    pub path: PathBuf,
+    pub game: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+fn is_screenshot(path: &Path) -> bool {
+    !contact_sheet::is_contact_sheet(path)
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e.to_lowercase().as_str(), "jpg" | "png" | "webp"))
+}
+
+pub fn walk_files(dir: &Path, files: &mut Vec<(PathBuf, fs::Metadata)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            walk_files(&entry.path(), files)?;
+        } else {
+            files.push((entry.path(), metadata));
+        }
+    }
+    Ok(())
+}
+
+pub fn open_index(target_folder: &Path) -> Option<Index> {
+    Index::open(target_folder)
+        .map_err(|e| warn!("Could not open index: {e:?}"))
+        .ok()
+}
+
+pub fn remove_screenshot(index: Option<&Index>, path: &Path) -> Result<()> {
+    fs::remove_file(path)?;
+    let _ = fs::remove_file(imaging::thumbnail_path(path));
+    if let Some(index) = index {
+        index.remove_screenshot(path)?;
+    }
+    Ok(())
+}
+
+fn folder_rule<'a>(config: &'a Config, game: &str) -> Option<&'a RuleEntry> {
+    config
+        .rules
+        .iter()
+        .find(|r| r.override_name.as_deref() == Some(game))
+        .or_else(|| find_rule(&config.rules, &RuleTarget::name(game)))
+}
+
+pub fn list_screenshots(target_folder: &Path) -> Result<(Vec<ScreenshotFile>, u64)> {
+    let mut files = Vec::new();
+    walk_files(target_folder, &mut files)?;
+    let total = files.iter().map(|(_, m)| m.len()).sum();
+    let screenshots = files
+        .into_iter()
+        .filter(|(path, _)| is_screenshot(path))
+        .filter_map(|(path, metadata)| {
+            let game = path
+                .strip_prefix(target_folder)
+                .ok()?
+                .components()
+                .next()?
+                .as_os_str()
+                .to_str()?
+                .to_owned();
+            Some(ScreenshotFile {
+                game,
+                size: metadata.len(),
+                modified: metadata.modified().ok()?,
+                path,
+            })
+        })
+        .collect();
+    Ok((screenshots, total))
+}
+
+/// Has the maintenance thread check the quota, after a screenshot was saved.
+pub fn request_quota_check() {
+    *QUOTA_CHECK.lock().unwrap() = true;
+    QUOTA_REQUESTED.notify_one();
+}
+
+/// Prunes the oldest screenshots until they fit in `max_total_size`. Only screenshots count
+/// towards the quota, other files in the folder like archives and clips are left alone.
+pub fn enforce_quota(config: &Config) -> Result<()> {
+    let Some(max_size) = config.max_total_size else {
+        return Ok(());
+    };
+    let (mut screenshots, _) = list_screenshots(&config.target_folder)?;
+    let mut total: u64 = screenshots.iter().map(|s| s.size).sum();
+    if total <= max_size.0 {
+        return Ok(());
+    }
+
+    screenshots.retain(|s| {
+        !config.prune_marked_only || folder_rule(config, &s.game).is_some_and(|r| r.prunable)
+    });
+    screenshots.sort_by_key(|s| s.modified);
+    let index = open_index(&config.target_folder);
+    for screenshot in screenshots {
+        if total <= max_size.0 {
+            break;
+        }
+        match remove_screenshot(index.as_ref(), &screenshot.path) {
+            Ok(()) => {
+                total -= screenshot.size;
+                info!(path = ?screenshot.path, "Pruned screenshot to stay under quota");
+            }
+            Err(e) => warn!(path = ?screenshot.path, "Could not prune screenshot: {e:?}"),
+        }
+    }
+    if total > max_size.0 {
+        warn!("Target folder is still over quota after pruning");
+    }
+    Ok(())
+}
+
+pub fn enforce_retention(config: &Config) -> Result<()> {
+    let (screenshots, _) = list_screenshots(&config.target_folder)?;
+    let now = SystemTime::now();
+    let index = open_index(&config.target_folder);
+    for screenshot in screenshots {
+        let retention_days = folder_rule(config, &screenshot.game)
+            .and_then(|r| r.retention_days)
+            .or(config.retention_days);
+        let Some(days) = retention_days.filter(|&d| d > 0) else {
+            continue;
+        };
+        let age = now.duration_since(screenshot.modified).unwrap_or_default();
+        if age <= Duration::from_secs(days * 24 * 60 * 60) {
+            continue;
+        }
+        match remove_screenshot(index.as_ref(), &screenshot.path) {
+            Ok(()) => info!(path = ?screenshot.path, "Removed screenshot past retention"),
+            Err(e) => warn!(path = ?screenshot.path, "Could not remove screenshot: {e:?}"),
+        }
+    }
+    Ok(())
+}
+
+/// Runs retention and archiving every hour, and the quota when requested, one at a time so
+/// they never delete the same files.
+pub fn maintenance_thread(state: Arc<State>) -> ! {
+    let mut next_run = Instant::now();
+    loop {
+        let timeout = next_run.saturating_duration_since(Instant::now());
+        let (mut quota_check, _) = QUOTA_REQUESTED
+            .wait_timeout_while(QUOTA_CHECK.lock().unwrap(), timeout, |requested| {
+                !*requested
+            })
+            .unwrap();
+        let check_quota = std::mem::take(&mut *quota_check);
+        drop(quota_check);
+
+        // A dry run only logs, so it leaves the journal as it is.
+        if state.dry_run.load(Ordering::Relaxed) {
+            debug!("Dry run, skipping maintenance");
+            next_run = Instant::now() + MAINTENANCE_INTERVAL;
+            continue;
+        }
+        let config = state.config.read().unwrap().clone();
+        if check_quota {
+            if let Err(e) = enforce_quota(&config) {
+                warn!("Could not enforce disk quota: {e:?}");
+            }
+        }
+        if Instant::now() >= next_run {
+            if let Err(e) = enforce_retention(&config) {
+                warn!("Could not enforce retention policy: {e:?}");
+            }
+            if let Err(e) = archive::archive_old_screenshots(&config) {
+                warn!("Could not archive old screenshots: {e:?}");
+            }
+            next_run = Instant::now() + MAINTENANCE_INTERVAL;
+        }
+    }
+}
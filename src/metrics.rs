@@ -0,0 +1,116 @@
+use std::{
+    fmt::Write,
+    path::Path,
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{maintenance, State};
+
+const DISK_USAGE_TTL: Duration = Duration::from_secs(5 * 60);
+
+static DISK_USAGE: Mutex<Option<(Instant, u64)>> = Mutex::new(None);
+
+fn disk_usage(target_folder: &Path) -> u64 {
+    let mut cached = DISK_USAGE.lock().unwrap();
+    if let Some((time, bytes)) = *cached {
+        if time.elapsed() < DISK_USAGE_TTL {
+            return bytes;
+        }
+    }
+    let bytes = maintenance::list_screenshots(target_folder)
+        .map(|(_, total)| total)
+        .unwrap_or_else(|e| {
+            warn!("Could not measure target folder size: {e:?}");
+            0
+        });
+    *cached = Some((Instant::now(), bytes));
+    bytes
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn metric(text: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+    let _ = writeln!(text, "# HELP autogamejournal_{name} {help}");
+    let _ = writeln!(text, "# TYPE autogamejournal_{name} {kind}");
+    for (labels, value) in samples {
+        let _ = writeln!(text, "autogamejournal_{name}{labels} {value}");
+    }
+}
+
+pub fn render(state: &State) -> String {
+    let target_folder = state.config.read().unwrap().target_folder.clone();
+    let (game, session_seconds, taken, errors, today) = {
+        let mut status = state.status.lock().unwrap();
+        status.roll_over();
+        (
+            status.game.clone(),
+            status.session_played.as_secs_f64(),
+            status.screenshots_taken,
+            status.capture_errors,
+            status.screenshots_today,
+        )
+    };
+    let paused = state.paused.load(Ordering::Relaxed);
+
+    let mut text = String::new();
+    metric(
+        &mut text,
+        "screenshots_taken_total",
+        "counter",
+        "Screenshots saved since the app started.",
+        &[(String::new(), taken as f64)],
+    );
+    metric(
+        &mut text,
+        "capture_errors_total",
+        "counter",
+        "Failed capture attempts since the app started.",
+        &[(String::new(), errors as f64)],
+    );
+    metric(
+        &mut text,
+        "screenshots_today",
+        "gauge",
+        "Screenshots saved today.",
+        &[(String::new(), today as f64)],
+    );
+    metric(
+        &mut text,
+        "current_game",
+        "gauge",
+        "The game currently being journaled.",
+        &game
+            .iter()
+            .map(|game| (format!("{{game=\"{}\"}}", escape_label(game)), 1.0))
+            .collect::<Vec<_>>(),
+    );
+    metric(
+        &mut text,
+        "session_played_seconds",
+        "gauge",
+        "Time played in the current session.",
+        &[(String::new(), session_seconds)],
+    );
+    metric(
+        &mut text,
+        "paused",
+        "gauge",
+        "Whether capturing is paused.",
+        &[(String::new(), if paused { 1.0 } else { 0.0 })],
+    );
+    metric(
+        &mut text,
+        "disk_usage_bytes",
+        "gauge",
+        "Size of the target folder.",
+        &[(String::new(), disk_usage(&target_folder) as f64)],
+    );
+    text
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+#[cfg(windows)]
+use windows::{
+    core::HSTRING,
+    Data::Xml::Dom::XmlDocument,
+    UI::Notifications::{ToastNotification, ToastNotificationManager},
+};
+
+#[cfg(windows)]
+const APP_ID: &str =
+    "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+#[cfg(windows)]
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(windows)]
+pub fn show_toast(title: &str, text: &str) -> Result<()> {
+    let xml = XmlDocument::new()?;
+    xml.LoadXml(&HSTRING::from(format!(
+        "<toast><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual></toast>",
+        escape_xml(title),
+        escape_xml(text)
+    )))?;
+    let toast = ToastNotification::CreateToastNotification(&xml)?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&toast)?;
+    Ok(())
+}
+
+/// Shows a desktop notification through `notify-send`, which ships with libnotify.
+#[cfg(target_os = "linux")]
+pub fn show_toast(title: &str, text: &str) -> Result<()> {
+    use anyhow::Context;
+    std::process::Command::new("notify-send")
+        .args(["--app-name", "autogamejournal", "--", title, text])
+        .spawn()
+        .context("Starting notify-send")?;
+    Ok(())
+}
+
+/// Shows a notification through AppleScript, which needs no app bundle.
+#[cfg(target_os = "macos")]
+pub fn show_toast(title: &str, text: &str) -> Result<()> {
+    use anyhow::Context;
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(format!(
+            "display notification {} with title {}",
+            quote(text),
+            quote(title)
+        ))
+        .spawn()
+        .context("Starting osascript")?;
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+};
+use tracing::{debug, warn};
+#[cfg(windows)]
+use windows::{
+    core::HSTRING,
+    Graphics::Imaging::{
+        BitmapAlphaMode, BitmapDecoder, BitmapInterpolationMode, BitmapPixelFormat,
+        BitmapTransform, ColorManagementMode, ExifOrientationMode,
+    },
+    Media::Ocr::OcrEngine,
+    Storage::{FileAccessMode, StorageFile},
+};
+
+use crate::{index, State};
+
+static QUEUE: Mutex<VecDeque<PathBuf>> = Mutex::new(VecDeque::new());
+static QUEUED: Condvar = Condvar::new();
+
+pub fn enqueue(path: &Path) {
+    QUEUE.lock().unwrap().push_back(path.to_owned());
+    QUEUED.notify_one();
+}
+
+#[cfg(windows)]
+pub fn recognize(path: &Path) -> Result<String> {
+    let engine = OcrEngine::TryCreateFromUserProfileLanguages()
+        .context("No OCR language is installed for the user profile")?;
+    let path = std::path::absolute(path)?;
+    let path = HSTRING::from(path.to_string_lossy().replace('/', "\\"));
+    let file = StorageFile::GetFileFromPathAsync(&path)?.get()?;
+    let stream = file.OpenAsync(FileAccessMode::Read)?.get()?;
+    let decoder = BitmapDecoder::CreateAsync(&stream)?.get()?;
+
+    let transform = BitmapTransform::new()?;
+    let (width, height) = (decoder.PixelWidth()?, decoder.PixelHeight()?);
+    let max = OcrEngine::MaxImageDimension()?;
+    let largest = width.max(height);
+    if largest > max {
+        transform.SetScaledWidth(width * max / largest)?;
+        transform.SetScaledHeight(height * max / largest)?;
+        transform.SetInterpolationMode(BitmapInterpolationMode::Fant)?;
+    }
+    let bitmap = decoder
+        .GetSoftwareBitmapTransformedAsync(
+            BitmapPixelFormat::Bgra8,
+            BitmapAlphaMode::Premultiplied,
+            &transform,
+            ExifOrientationMode::IgnoreExifOrientation,
+            ColorManagementMode::DoNotColorManage,
+        )?
+        .get()?;
+    let result = engine.RecognizeAsync(&bitmap)?.get()?;
+    Ok(result.Text()?.to_string_lossy())
+}
+
+/// Reads the text with the tesseract command line tool, which has to be installed.
+#[cfg(not(windows))]
+pub fn recognize(path: &Path) -> Result<String> {
+    let output = std::process::Command::new("tesseract")
+        .arg(path)
+        .arg("stdout")
+        .output()
+        .context("Running tesseract, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn next_path() -> PathBuf {
+    let mut queue = QUEUE.lock().unwrap();
+    loop {
+        if let Some(path) = queue.pop_front() {
+            return path;
+        }
+        queue = QUEUED.wait(queue).unwrap();
+    }
+}
+
+pub fn ocr_thread(state: Arc<State>) -> ! {
+    loop {
+        let path = next_path();
+        let target_folder = state.config.read().unwrap().target_folder.clone();
+        match recognize(&path) {
+            Ok(text) => {
+                debug!(?path, chars = text.len(), "Recognized text");
+                index::update(&target_folder, |index| index.record_text(&path, &text));
+            }
+            Err(e) => warn!(?path, "Could not recognize text: {e:?}"),
+        }
+    }
+}
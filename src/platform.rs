@@ -0,0 +1,140 @@
+use anyhow::Result;
+use std::path::PathBuf;
+#[cfg(not(windows))]
+use std::{
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+#[cfg(target_os = "linux")]
+mod xdg;
+
+#[cfg(target_os = "macos")]
+pub use macos::*;
+#[cfg(windows)]
+pub use win32::*;
+#[cfg(target_os = "linux")]
+pub use {linux::*, xdg::*};
+
+/// How much the idle time can drift between two queries without any input.
+#[cfg(not(windows))]
+const INPUT_JITTER: u32 = 100;
+
+/// A rectangle in screen coordinates, right and bottom exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+impl Rect {
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.left < other.right
+            && self.right > other.left
+            && self.top < other.bottom
+            && self.bottom > other.top
+    }
+
+    /// The smallest rectangle containing both.
+    pub fn union(self, other: Rect) -> Rect {
+        Rect {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+pub trait WindowInfo {
+    fn id(&self) -> u32;
+    fn pid(&self) -> u32;
+    fn process_name(&self) -> Result<String>;
+    fn process_path(&self) -> Option<PathBuf>;
+    fn class(&self) -> Option<String>;
+    fn title(&self) -> Result<String>;
+    fn bounds(&self) -> Result<Rect>;
+    fn monitor(&self) -> Result<Rect>;
+    fn is_elevated(&self) -> bool;
+
+    fn is_fullscreen(&self, tolerance: i32) -> Result<bool> {
+        Ok(covers(&self.bounds()?, &self.monitor()?, tolerance))
+    }
+}
+
+pub fn covers(rect: &Rect, screen: &Rect, tolerance: i32) -> bool {
+    rect.left <= screen.left + tolerance
+        && rect.right >= screen.right - tolerance
+        && rect.top <= screen.top + tolerance
+        && rect.bottom >= screen.bottom - tolerance
+}
+
+pub fn tick_after(tick: u32, other: u32) -> bool {
+    (tick.wrapping_sub(other) as i32) > 0
+}
+
+/// Milliseconds since the first call, wrapping around like the Windows tick count.
+#[cfg(not(windows))]
+pub fn tick_count() -> u32 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u32
+}
+
+/// Turns an idle time into the tick of the last input. Idle times are sampled at a different
+/// moment than the tick count, so small drifts are ignored to keep the result stable.
+#[cfg(not(windows))]
+fn last_input_from_idle(idle: u32) -> u32 {
+    static LAST_INPUT: Mutex<Option<u32>> = Mutex::new(None);
+    let estimate = tick_count().wrapping_sub(idle);
+    let mut last_input = LAST_INPUT.lock().unwrap();
+    match *last_input {
+        Some(last) if !tick_after(estimate, last.wrapping_add(INPUT_JITTER)) => {}
+        _ => *last_input = Some(estimate),
+    }
+    last_input.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fullscreen_tolerance() {
+        let screen = Rect {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        let window = Rect {
+            left: 1922,
+            top: 2,
+            right: 3838,
+            bottom: 1078,
+        };
+        assert!(covers(&screen, &screen, 0));
+        assert!(!covers(&window, &screen, 0));
+        assert!(covers(&window, &screen, 2));
+    }
+
+    #[test]
+    fn ticks_wrap_around() {
+        assert!(tick_after(10, 5));
+        assert!(!tick_after(5, 5));
+        assert!(tick_after(3, u32::MAX - 3));
+        assert!(!tick_after(u32::MAX - 3, 3));
+    }
+}
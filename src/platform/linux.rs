@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use image::RgbImage;
+use std::{fs, path::PathBuf};
+use tracing::debug;
+
+use super::{
+    last_input_from_idle,
+    wayland::{self, Toplevel},
+    x11::{self, X11Window},
+    Rect, WindowInfo,
+};
+use crate::{CaptureBackend, CaptureMode, CaptureSettings};
+
+/// A window seen through X11, including XWayland, or through the Wayland compositor's IPC.
+pub enum DesktopWindow {
+    X11(X11Window),
+    Wayland(Toplevel),
+}
+
+impl DesktopWindow {
+    pub fn foreground() -> Result<Self> {
+        match wayland::compositor() {
+            Some(compositor) => compositor
+                .toplevels()?
+                .into_iter()
+                .find(|window| window.focused)
+                .map(Self::Wayland)
+                .context("Failed to get foreground window"),
+            None => X11Window::foreground().map(Self::X11),
+        }
+    }
+
+    /// Titled windows, roughly the ones shown in the taskbar.
+    pub fn visible() -> Result<Vec<Self>> {
+        match wayland::compositor() {
+            Some(compositor) => Ok(compositor
+                .toplevels()?
+                .into_iter()
+                .filter(|window| window.title().is_ok_and(|title| !title.is_empty()))
+                .map(Self::Wayland)
+                .collect()),
+            None => Ok(X11Window::visible()?.into_iter().map(Self::X11).collect()),
+        }
+    }
+
+    fn inner(&self) -> &dyn WindowInfo {
+        match self {
+            Self::X11(window) => window,
+            Self::Wayland(window) => window,
+        }
+    }
+}
+
+impl WindowInfo for DesktopWindow {
+    fn id(&self) -> u32 {
+        self.inner().id()
+    }
+
+    fn pid(&self) -> u32 {
+        self.inner().pid()
+    }
+
+    fn process_name(&self) -> Result<String> {
+        self.inner().process_name()
+    }
+
+    fn process_path(&self) -> Option<PathBuf> {
+        self.inner().process_path()
+    }
+
+    fn class(&self) -> Option<String> {
+        self.inner().class()
+    }
+
+    fn title(&self) -> Result<String> {
+        self.inner().title()
+    }
+
+    fn bounds(&self) -> Result<Rect> {
+        self.inner().bounds()
+    }
+
+    fn monitor(&self) -> Result<Rect> {
+        self.inner().monitor()
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.inner().is_elevated()
+    }
+}
+
+/// Wine and Proton games run inside the Wine loader, so the game is found by the Windows
+/// executable on its command line. Paths on the `Z:` drive are mapped back to the Linux path.
+fn wine_executable(pid: u32) -> Option<PathBuf> {
+    let exe = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+    if !exe.file_name()?.to_string_lossy().starts_with("wine") {
+        return None;
+    }
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let arg = cmdline
+        .split(|&byte| byte == 0)
+        .map(String::from_utf8_lossy)
+        .find(|arg| arg.to_ascii_lowercase().ends_with(".exe"))?;
+    match arg.strip_prefix("Z:").or_else(|| arg.strip_prefix("z:")) {
+        Some(path) => Some(PathBuf::from(path.replace('\\', "/"))),
+        None => Some(PathBuf::from(arg.rsplit(['\\', '/']).next()?)),
+    }
+}
+
+pub(super) fn process_name(pid: u32) -> Result<String> {
+    let path = match wine_executable(pid) {
+        Some(path) => path,
+        None => fs::read_link(format!("/proc/{pid}/exe")).context("PID not found")?,
+    };
+    Ok(path
+        .file_stem()
+        .context("Getting file stem")?
+        .to_string_lossy()
+        .into_owned())
+}
+
+pub(super) fn process_path(pid: u32) -> Option<PathBuf> {
+    wine_executable(pid)
+        .filter(|path| path.is_absolute())
+        .or_else(|| fs::read_link(format!("/proc/{pid}/exe")).ok())
+}
+
+/// Captures the window, or the monitors it is on. XWayland can only read its own windows, so on
+/// Wayland monitor captures go through the screenshot portal, falling back to the window alone
+/// when the portal is unavailable or screenshots were denied.
+pub fn capture(id: u32, settings: &CaptureSettings) -> Result<(RgbImage, CaptureBackend)> {
+    if id & wayland::ID_FLAG != 0 {
+        return Ok((wayland::capture(id, settings)?, CaptureBackend::Portal));
+    }
+    if wayland::is_session() && matches!(settings.mode, CaptureMode::Monitor) {
+        let captured = x11::screen_area(id, settings.span_monitors)
+            .and_then(|(area, screen)| wayland::capture_area(area, screen));
+        match captured {
+            Ok(image) => return Ok((image, CaptureBackend::Portal)),
+            Err(e) => debug!("Portal capture failed, capturing the window alone: {e:?}"),
+        }
+        let window_only = CaptureSettings {
+            mode: CaptureMode::Window,
+            ..*settings
+        };
+        return Ok((x11::capture(id, &window_only)?, CaptureBackend::X11));
+    }
+    Ok((x11::capture(id, settings)?, CaptureBackend::X11))
+}
+
+pub fn get_last_input_time() -> Result<u32> {
+    let idle = match wayland::idle_time() {
+        Some(idle) => idle,
+        None => x11::idle_time()?,
+    };
+    Ok(last_input_from_idle(idle))
+}
+
+/// Calls `changed` whenever another window is activated.
+pub fn watch_foreground(changed: impl FnMut()) -> Result<()> {
+    match wayland::compositor() {
+        Some(compositor) => compositor.watch_focus(changed),
+        None => x11::watch_foreground(changed),
+    }
+}
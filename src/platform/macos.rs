@@ -0,0 +1,359 @@
+use anyhow::{anyhow, bail, Context, Result};
+use core_foundation::{
+    base::{CFType, TCFType},
+    dictionary::{CFDictionary, CFDictionaryRef},
+    number::CFNumber,
+    string::{CFString, CFStringRef},
+};
+use core_graphics::{
+    display::CGDisplay,
+    geometry::{CGPoint, CGRect, CGSize},
+    image::CGImage,
+    window::{
+        copy_window_info, create_image, kCGNullWindowID, kCGWindowBounds,
+        kCGWindowImageBestResolution, kCGWindowImageBoundsIgnoreFraming, kCGWindowLayer,
+        kCGWindowListExcludeDesktopElements, kCGWindowListOptionIncludingWindow,
+        kCGWindowListOptionOnScreenOnly, kCGWindowName, kCGWindowNumber, kCGWindowOwnerName,
+        kCGWindowOwnerPID,
+    },
+};
+use image::{Rgb, RgbImage};
+use std::{
+    env,
+    ffi::OsString,
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use super::{last_input_from_idle, Rect, WindowInfo};
+use crate::{elevation, CaptureBackend, CaptureMode, CaptureSettings};
+
+/// The window server has no focus notifications without an app run loop, so focus is polled.
+const FOREGROUND_POLL: Duration = Duration::from_millis(250);
+/// kCGEventSourceStateCombinedSessionState
+const COMBINED_SESSION_STATE: i32 = 0;
+/// kCGAnyInputEventType
+const ANY_INPUT_EVENT: u32 = !0;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGPreflightScreenCaptureAccess() -> bool;
+    fn CGRequestScreenCaptureAccess() -> bool;
+    fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+}
+
+fn to_rect(rect: &CGRect) -> Rect {
+    Rect {
+        left: rect.origin.x.round() as i32,
+        top: rect.origin.y.round() as i32,
+        right: (rect.origin.x + rect.size.width).round() as i32,
+        bottom: (rect.origin.y + rect.size.height).round() as i32,
+    }
+}
+
+fn to_cg_rect(rect: &Rect) -> CGRect {
+    CGRect::new(
+        &CGPoint::new(rect.left.into(), rect.top.into()),
+        &CGSize::new(
+            (rect.right - rect.left).into(),
+            (rect.bottom - rect.top).into(),
+        ),
+    )
+}
+
+fn value(info: &CFDictionary<CFString, CFType>, key: CFStringRef) -> Option<CFType> {
+    let key = unsafe { CFString::wrap_under_get_rule(key) };
+    info.find(&key).map(|value| value.clone())
+}
+
+fn number(info: &CFDictionary<CFString, CFType>, key: CFStringRef) -> Option<i64> {
+    value(info, key)?.downcast::<CFNumber>()?.to_i64()
+}
+
+fn text(info: &CFDictionary<CFString, CFType>, key: CFStringRef) -> Option<String> {
+    Some(value(info, key)?.downcast::<CFString>()?.to_string())
+}
+
+/// The displays' bounds, in the same points as window bounds.
+fn monitors() -> Result<Vec<Rect>> {
+    let displays =
+        CGDisplay::active_displays().map_err(|e| anyhow!("Listing displays failed with {e}"))?;
+    let monitors: Vec<Rect> = displays
+        .into_iter()
+        .map(|display| to_rect(&CGDisplay::new(display).bounds()))
+        .collect();
+    if monitors.is_empty() {
+        bail!("No displays");
+    }
+    Ok(monitors)
+}
+
+fn monitor_of(bounds: &Rect) -> Result<Rect> {
+    let (x, y) = (
+        (bounds.left + bounds.right) / 2,
+        (bounds.top + bounds.bottom) / 2,
+    );
+    let monitors = monitors()?;
+    Ok(monitors
+        .iter()
+        .find(|m| m.left <= x && x < m.right && m.top <= y && y < m.bottom)
+        .unwrap_or(&monitors[0])
+        .to_owned())
+}
+
+fn process_path(pid: u32) -> Option<PathBuf> {
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let length = unsafe {
+        libc::proc_pidpath(
+            pid as libc::c_int,
+            buffer.as_mut_ptr().cast(),
+            buffer.len() as u32,
+        )
+    };
+    if length <= 0 {
+        return None;
+    }
+    buffer.truncate(length as usize);
+    Some(PathBuf::from(OsString::from_vec(buffer)))
+}
+
+/// An on-screen window from the window server's list, as it was when listed.
+pub struct DesktopWindow {
+    id: u32,
+    pid: u32,
+    owner: Option<String>,
+    title: Option<String>,
+    bounds: Rect,
+}
+
+impl DesktopWindow {
+    /// Application windows from front to back, leaving out the menu bar, the Dock and overlays.
+    fn list() -> Result<Vec<Self>> {
+        let windows = copy_window_info(
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+        )
+        .context("Listing windows")?;
+        let windows = windows.iter().filter_map(|item| {
+            let info: CFDictionary<CFString, CFType> =
+                unsafe { CFDictionary::wrap_under_get_rule(*item as CFDictionaryRef) };
+            if number(&info, unsafe { kCGWindowLayer }) != Some(0) {
+                return None;
+            }
+            let bounds = value(&info, unsafe { kCGWindowBounds })?.downcast::<CFDictionary>()?;
+            let bounds = to_rect(&CGRect::from_dict_representation(&bounds)?);
+            // Apps keep invisible helper windows of a pixel or so around.
+            if bounds.right - bounds.left <= 1 || bounds.bottom - bounds.top <= 1 {
+                return None;
+            }
+            Some(Self {
+                id: number(&info, unsafe { kCGWindowNumber })? as u32,
+                pid: number(&info, unsafe { kCGWindowOwnerPID }).unwrap_or(0) as u32,
+                owner: text(&info, unsafe { kCGWindowOwnerName }),
+                title: text(&info, unsafe { kCGWindowName }),
+                bounds,
+            })
+        });
+        Ok(windows.collect())
+    }
+
+    /// The frontmost application window. The window list is ordered front to back, so this
+    /// doesn't need AppKit, which only tracks the frontmost app while its run loop runs.
+    pub fn foreground() -> Result<Self> {
+        Self::list()?
+            .into_iter()
+            .next()
+            .context("Failed to get foreground window")
+    }
+
+    /// Titled windows on screen. Titles need the Screen Recording permission.
+    pub fn visible() -> Result<Vec<Self>> {
+        Ok(Self::list()?
+            .into_iter()
+            .filter(|window| window.title.as_ref().is_some_and(|title| !title.is_empty()))
+            .collect())
+    }
+}
+
+impl WindowInfo for DesktopWindow {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn process_name(&self) -> Result<String> {
+        match self.process_path() {
+            Some(path) => Ok(path
+                .file_stem()
+                .context("Getting file stem")?
+                .to_string_lossy()
+                .into_owned()),
+            None => self.owner.clone().context("PID not found"),
+        }
+    }
+
+    fn process_path(&self) -> Option<PathBuf> {
+        process_path(self.pid)
+    }
+
+    /// macOS has no window classes, the app's name is the closest.
+    fn class(&self) -> Option<String> {
+        self.owner.clone()
+    }
+
+    fn title(&self) -> Result<String> {
+        self.title
+            .clone()
+            .context("Window has no title, is Screen Recording allowed?")
+    }
+
+    fn bounds(&self) -> Result<Rect> {
+        Ok(self.bounds)
+    }
+
+    fn monitor(&self) -> Result<Rect> {
+        monitor_of(&self.bounds)
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.pid != 0 && elevation::is_process_elevated(self.pid)
+    }
+}
+
+/// Captures only show the desktop without the Screen Recording permission, so that is checked
+/// first. The system prompt is only shown once per run, it opens System Settings.
+fn check_screen_capture_access() -> Result<()> {
+    static REQUESTED: AtomicBool = AtomicBool::new(false);
+    if unsafe { CGPreflightScreenCaptureAccess() } {
+        return Ok(());
+    }
+    if !REQUESTED.swap(true, Ordering::Relaxed) {
+        unsafe { CGRequestScreenCaptureAccess() };
+    }
+    bail!("Screen Recording isn't allowed, allow it for autogamejournal in System Settings > Privacy & Security")
+}
+
+fn to_rgb(image: &CGImage) -> Result<RgbImage> {
+    if image.bits_per_pixel() != 32 {
+        bail!("Unexpected {} bit pixels", image.bits_per_pixel());
+    }
+    let stride = image.bytes_per_row();
+    let data = image.data();
+    let bytes = data.bytes();
+    // The window server hands out BGRA pixels in little endian order.
+    Ok(RgbImage::from_fn(
+        image.width() as u32,
+        image.height() as u32,
+        |x, y| {
+            let offset = y as usize * stride + x as usize * 4;
+            Rgb([bytes[offset + 2], bytes[offset + 1], bytes[offset]])
+        },
+    ))
+}
+
+/// Captures the window, or the monitors it is on, in pixels on Retina displays. The cursor is
+/// never included.
+pub fn capture(id: u32, settings: &CaptureSettings) -> Result<(RgbImage, CaptureBackend)> {
+    check_screen_capture_access()?;
+    let window = DesktopWindow::list()?
+        .into_iter()
+        .find(|window| window.id == id)
+        .context("Window no longer exists")?;
+    let image = match settings.mode {
+        CaptureMode::Window => create_image(
+            to_cg_rect(&window.bounds),
+            kCGWindowListOptionIncludingWindow,
+            id,
+            kCGWindowImageBoundsIgnoreFraming | kCGWindowImageBestResolution,
+        ),
+        CaptureMode::Monitor => {
+            let area = if settings.span_monitors {
+                monitors()?
+                    .into_iter()
+                    .filter(|monitor| monitor.overlaps(&window.bounds))
+                    .reduce(Rect::union)
+                    .context("Window is on no monitor")?
+            } else {
+                monitor_of(&window.bounds)?
+            };
+            create_image(
+                to_cg_rect(&area),
+                kCGWindowListOptionOnScreenOnly,
+                kCGNullWindowID,
+                kCGWindowImageBestResolution,
+            )
+        }
+    };
+    let image = image.context("The window server returned no image")?;
+    Ok((to_rgb(&image)?, CaptureBackend::CoreGraphics))
+}
+
+pub fn get_last_input_time() -> Result<u32> {
+    let idle =
+        unsafe { CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT) };
+    Ok(last_input_from_idle((idle * 1000.0) as u32))
+}
+
+/// Calls `changed` whenever another window comes to the front.
+pub fn watch_foreground(mut changed: impl FnMut()) -> Result<()> {
+    let mut last = DesktopWindow::foreground().ok().map(|window| window.id);
+    loop {
+        thread::sleep(FOREGROUND_POLL);
+        let current = DesktopWindow::foreground().ok().map(|window| window.id);
+        if current != last {
+            last = current;
+            changed();
+        }
+    }
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    Some(home()?.join("Library").join("Application Support"))
+}
+
+pub fn local_data_dir() -> Option<PathBuf> {
+    config_dir()
+}
+
+pub fn pictures_dir() -> Option<PathBuf> {
+    Some(home()?.join("Pictures"))
+}
+
+pub fn open_file(path: &Path) -> Result<()> {
+    Command::new("open")
+        .arg(path)
+        .spawn()
+        .context("Starting open")?;
+    Ok(())
+}
+
+pub fn edit_file(path: &Path) -> Result<()> {
+    Command::new("open")
+        .arg("-t")
+        .arg(path)
+        .spawn()
+        .context("Starting open")?;
+    Ok(())
+}
+
+pub fn open_folder(path: &Path) -> Result<()> {
+    open_file(&path.canonicalize()?)
+}
+
+/// The system default. Status bar items only report clicks as menu use, so this rarely matters.
+pub fn double_click_time() -> Duration {
+    Duration::from_millis(500)
+}
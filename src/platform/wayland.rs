@@ -0,0 +1,620 @@
+use anyhow::{bail, Context, Result};
+use gio::{
+    glib::{MainContext, ToVariant, Variant, VariantDict, VariantTy},
+    prelude::*,
+    BusType, Cancellable, DBusCallFlags, DBusSignalFlags,
+};
+use image::RgbImage;
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{BufRead, BufReader},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use super::{
+    linux::{process_name, process_path},
+    Rect, WindowInfo,
+};
+use crate::{elevation, CaptureMode, CaptureSettings};
+
+/// Set in the ids of windows found through the compositor. X11 ids use at most 29 bits.
+pub const ID_FLAG: u32 = 1 << 31;
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+/// The first screenshot can wait on the user answering a permission dialog.
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to wait before asking again after screenshots were denied.
+const DENIED_RETRY: Duration = Duration::from_secs(600);
+
+static DENIED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+
+pub fn is_session() -> bool {
+    env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+/// Compositors whose IPC tells which window is focused. Elsewhere only XWayland windows,
+/// which most games are, can be seen.
+#[derive(Clone, Copy)]
+pub enum Compositor {
+    Sway,
+    Hyprland,
+}
+
+pub fn compositor() -> Option<Compositor> {
+    if !is_session() {
+        None
+    } else if env::var_os("SWAYSOCK").is_some() {
+        Some(Compositor::Sway)
+    } else if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        Some(Compositor::Hyprland)
+    } else {
+        None
+    }
+}
+
+/// A window as the compositor reported it when queried.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toplevel {
+    id: u32,
+    pid: u32,
+    class: Option<String>,
+    title: String,
+    bounds: Rect,
+    monitor: Rect,
+    pub focused: bool,
+}
+
+impl WindowInfo for Toplevel {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn process_name(&self) -> Result<String> {
+        process_name(self.pid)
+    }
+
+    fn process_path(&self) -> Option<PathBuf> {
+        process_path(self.pid)
+    }
+
+    fn class(&self) -> Option<String> {
+        self.class.clone()
+    }
+
+    fn title(&self) -> Result<String> {
+        Ok(self.title.clone())
+    }
+
+    fn bounds(&self) -> Result<Rect> {
+        Ok(self.bounds)
+    }
+
+    fn monitor(&self) -> Result<Rect> {
+        Ok(self.monitor)
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.pid != 0 && elevation::is_process_elevated(self.pid)
+    }
+}
+
+/// Windows and monitors in the compositor's logical coordinates.
+#[derive(Default)]
+struct Layout {
+    toplevels: Vec<Toplevel>,
+    outputs: Vec<Rect>,
+}
+
+fn query(program: &str, args: &[&str]) -> Result<Value> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Running {program}"))?;
+    if !output.status.success() {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn rect(x: &Value, y: &Value, width: &Value, height: &Value) -> Rect {
+    let number = |value: &Value| value.as_i64().unwrap_or(0) as i32;
+    Rect {
+        left: number(x),
+        top: number(y),
+        right: number(x) + number(width),
+        bottom: number(y) + number(height),
+    }
+}
+
+fn sway_node(node: &Value, output: Rect, layout: &mut Layout) {
+    let bounds = rect(
+        &node["rect"]["x"],
+        &node["rect"]["y"],
+        &node["rect"]["width"],
+        &node["rect"]["height"],
+    );
+    let mut output = output;
+    // The __i3 output holds the scratchpad, which isn't on any screen.
+    if node["type"] == "output" && node["name"] != "__i3" {
+        layout.outputs.push(bounds);
+        output = bounds;
+    }
+    if let Some(pid) = node["pid"].as_u64() {
+        layout.toplevels.push(Toplevel {
+            id: node["id"].as_u64().unwrap_or(0) as u32 | ID_FLAG,
+            pid: pid as u32,
+            class: node["app_id"]
+                .as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .map(str::to_owned),
+            title: node["name"].as_str().unwrap_or_default().to_owned(),
+            bounds,
+            monitor: output,
+            focused: node["focused"].as_bool().unwrap_or(false),
+        });
+    }
+    let children = node["nodes"].as_array().into_iter().flatten();
+    for child in children.chain(node["floating_nodes"].as_array().into_iter().flatten()) {
+        sway_node(child, output, layout);
+    }
+}
+
+fn sway_layout(tree: &Value) -> Layout {
+    let mut layout = Layout::default();
+    sway_node(tree, Rect::default(), &mut layout);
+    layout
+}
+
+/// Hyprland addresses are pointers, folded into an id that can't clash with X11's.
+fn hyprland_id(address: &Value) -> u32 {
+    let address = address.as_str().unwrap_or_default();
+    let address = u64::from_str_radix(address.trim_start_matches("0x"), 16).unwrap_or(0);
+    (address as u32 ^ (address >> 32) as u32) | ID_FLAG
+}
+
+fn hyprland_layout(clients: &Value, monitors: &Value, active: &Value) -> Layout {
+    let mut outputs = Vec::new();
+    for monitor in monitors.as_array().into_iter().flatten() {
+        // Sizes are in pixels, positions in logical coordinates.
+        let scale = monitor["scale"].as_f64().unwrap_or(1.0);
+        let (mut width, mut height) = (
+            monitor["width"].as_f64().unwrap_or(0.0),
+            monitor["height"].as_f64().unwrap_or(0.0),
+        );
+        if monitor["transform"].as_i64().unwrap_or(0) % 2 == 1 {
+            (width, height) = (height, width);
+        }
+        let logical = |size: f64| Value::from((size / scale).round() as i64);
+        outputs.push((
+            monitor["id"].as_i64(),
+            rect(
+                &monitor["x"],
+                &monitor["y"],
+                &logical(width),
+                &logical(height),
+            ),
+        ));
+    }
+    let focused = hyprland_id(&active["address"]);
+    let toplevels = clients
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|client| client["mapped"] != false && client["hidden"] != true)
+        .map(|client| {
+            let id = hyprland_id(&client["address"]);
+            Toplevel {
+                id,
+                pid: client["pid"].as_u64().unwrap_or(0) as u32,
+                class: client["class"]
+                    .as_str()
+                    .filter(|class| !class.is_empty())
+                    .map(str::to_owned),
+                title: client["title"].as_str().unwrap_or_default().to_owned(),
+                bounds: rect(
+                    &client["at"][0],
+                    &client["at"][1],
+                    &client["size"][0],
+                    &client["size"][1],
+                ),
+                monitor: outputs
+                    .iter()
+                    .find(|(monitor, _)| *monitor == client["monitor"].as_i64())
+                    .map(|(_, output)| *output)
+                    .unwrap_or_default(),
+                focused: active["address"].is_string() && id == focused,
+            }
+        })
+        .collect();
+    Layout {
+        toplevels,
+        outputs: outputs.into_iter().map(|(_, output)| output).collect(),
+    }
+}
+
+impl Compositor {
+    fn layout(self) -> Result<Layout> {
+        match self {
+            Compositor::Sway => Ok(sway_layout(&query("swaymsg", &["-t", "get_tree", "-r"])?)),
+            Compositor::Hyprland => Ok(hyprland_layout(
+                &query("hyprctl", &["-j", "clients"])?,
+                &query("hyprctl", &["-j", "monitors"])?,
+                &query("hyprctl", &["-j", "activewindow"])?,
+            )),
+        }
+    }
+
+    pub fn toplevels(self) -> Result<Vec<Toplevel>> {
+        Ok(self.layout()?.toplevels)
+    }
+
+    /// Calls `changed` whenever another window is focused.
+    pub fn watch_focus(self, mut changed: impl FnMut()) -> Result<()> {
+        match self {
+            Compositor::Sway => {
+                let mut child = Command::new("swaymsg")
+                    .args(["-t", "subscribe", "-m", r#"["window"]"#])
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .context("Running swaymsg")?;
+                let events = BufReader::new(child.stdout.take().context("No swaymsg output")?);
+                for event in events.lines() {
+                    let event: Value = serde_json::from_str(&event?)?;
+                    if event["change"] == "focus" {
+                        changed();
+                    }
+                }
+                bail!("swaymsg stopped");
+            }
+            Compositor::Hyprland => {
+                let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
+                // Hyprland moved its sockets from /tmp to the runtime directory.
+                let runtime = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+                let socket = runtime
+                    .map(|dir| dir.join("hypr"))
+                    .into_iter()
+                    .chain([PathBuf::from("/tmp/hypr")])
+                    .map(|dir| dir.join(&signature).join(".socket2.sock"))
+                    .find(|path| path.exists())
+                    .context("Hyprland's event socket wasn't found")?;
+                let events = BufReader::new(UnixStream::connect(socket)?);
+                for event in events.lines() {
+                    if event?.starts_with("activewindowv2>>") {
+                        changed();
+                    }
+                }
+                bail!("Hyprland closed its event socket");
+            }
+        }
+    }
+}
+
+fn denied_recently() -> bool {
+    DENIED_AT
+        .lock()
+        .unwrap()
+        .is_some_and(|at| at.elapsed() < DENIED_RETRY)
+}
+
+/// Takes a screenshot of the whole desktop through the xdg-desktop-portal Screenshot interface.
+fn screenshot() -> Result<RgbImage> {
+    static TOKENS: AtomicU32 = AtomicU32::new(0);
+    if denied_recently() {
+        bail!("Screenshots were denied, asking again later");
+    }
+    let connection = gio::bus_get_sync(BusType::Session, None::<&Cancellable>)?;
+    let sender = connection.unique_name().context("Not connected to D-Bus")?;
+    let token = format!("autogamejournal{}", TOKENS.fetch_add(1, Ordering::Relaxed));
+    // Subscribing to the request before making it, so a quick answer isn't missed.
+    let request = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{token}",
+        sender.trim_start_matches(':').replace('.', "_")
+    );
+    let context = MainContext::new();
+    let (response, results) = context.with_thread_default(|| {
+        let answer = Rc::new(RefCell::new(None));
+        let subscription = connection.signal_subscribe(
+            Some(PORTAL),
+            Some("org.freedesktop.portal.Request"),
+            Some("Response"),
+            Some(&request),
+            None,
+            DBusSignalFlags::NONE,
+            {
+                let answer = answer.clone();
+                move |_, _, _, _, _, parameters| {
+                    let response = parameters.child_value(0).get::<u32>().unwrap_or(2);
+                    *answer.borrow_mut() = Some((response, parameters.child_value(1)));
+                }
+            },
+        );
+        let options = VariantDict::new(None);
+        options.insert("handle_token", token.as_str());
+        options.insert("interactive", false);
+        let called = connection.call_sync(
+            Some(PORTAL),
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Screenshot",
+            "Screenshot",
+            Some(&Variant::tuple_from_iter(["".to_variant(), options.end()])),
+            Some(VariantTy::new("(o)")?),
+            DBusCallFlags::NONE,
+            -1,
+            None::<&Cancellable>,
+        );
+        let deadline = Instant::now() + PORTAL_TIMEOUT;
+        while called.is_ok() && answer.borrow().is_none() && Instant::now() < deadline {
+            if !context.iteration(false) {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        connection.signal_unsubscribe(subscription);
+        called.context("Calling the screenshot portal")?;
+        let answer = answer.take();
+        answer.context("The screenshot portal didn't answer")
+    })??;
+    match response {
+        0 => {}
+        // 1 is the user refusing, 2 covers the permission being off in the desktop settings.
+        1 | 2 => {
+            *DENIED_AT.lock().unwrap() = Some(Instant::now());
+            warn!("Screenshots were denied, allow them for autogamejournal in the desktop's privacy settings");
+            bail!("Screenshots were denied");
+        }
+        other => bail!("The screenshot portal failed with {other}"),
+    }
+    let uri: String = VariantDict::new(Some(&results))
+        .lookup("uri")?
+        .context("The screenshot portal returned no file")?;
+    let path = gio::File::for_uri(&uri)
+        .path()
+        .context("The screenshot isn't a local file")?;
+    let image = image::open(&path)?.into_rgb8();
+    // The journal keeps its own copy, the portal's would pile up in the user's pictures.
+    let _ = fs::remove_file(&path);
+    Ok(image)
+}
+
+/// Crops a screenshot of `screen` to `area`, both in logical coordinates. The screenshot is in
+/// pixels, which differ from logical coordinates on scaled displays.
+fn crop_scaled(image: RgbImage, area: Rect, screen: Rect) -> RgbImage {
+    if area == screen {
+        return image;
+    }
+    let scale = f64::from(image.width()) / f64::from((screen.right - screen.left).max(1));
+    let to_pixels = |value: i32, origin: i32, limit: u32| {
+        ((f64::from(value - origin) * scale).round().max(0.0) as u32).min(limit)
+    };
+    let left = to_pixels(area.left, screen.left, image.width());
+    let top = to_pixels(area.top, screen.top, image.height());
+    let right = to_pixels(area.right, screen.left, image.width());
+    let bottom = to_pixels(area.bottom, screen.top, image.height());
+    image::imageops::crop_imm(
+        &image,
+        left,
+        top,
+        right.saturating_sub(left),
+        bottom.saturating_sub(top),
+    )
+    .to_image()
+}
+
+/// Captures part of the screen through the portal.
+pub fn capture_area(area: Rect, screen: Rect) -> Result<RgbImage> {
+    Ok(crop_scaled(screenshot()?, area, screen))
+}
+
+/// Captures a window found through the compositor, which has no way to read a single window,
+/// so the screen is captured and cropped to it.
+pub fn capture(id: u32, settings: &CaptureSettings) -> Result<RgbImage> {
+    let layout = compositor()
+        .context("The window's compositor is gone")?
+        .layout()?;
+    let window = layout
+        .toplevels
+        .iter()
+        .find(|window| window.id == id)
+        .context("Window no longer exists")?;
+    let area = match settings.mode {
+        CaptureMode::Window => window.bounds,
+        CaptureMode::Monitor if settings.span_monitors => layout
+            .outputs
+            .iter()
+            .copied()
+            .filter(|output| output.overlaps(&window.bounds))
+            .reduce(Rect::union)
+            .context("Window is on no monitor")?,
+        CaptureMode::Monitor => window.monitor,
+    };
+    let screen = layout
+        .outputs
+        .into_iter()
+        .reduce(Rect::union)
+        .context("No monitors")?;
+    capture_area(area, screen)
+}
+
+/// Milliseconds since the last input, from GNOME or KDE. `None` when neither answers, as on
+/// other compositors, where the X server's idle time only counts input to XWayland windows.
+pub fn idle_time() -> Option<u32> {
+    if !is_session() {
+        return None;
+    }
+    let connection = gio::bus_get_sync(BusType::Session, None::<&Cancellable>).ok()?;
+    let call = |destination, path, interface, method, reply| {
+        connection
+            .call_sync(
+                Some(destination),
+                path,
+                interface,
+                method,
+                None,
+                Some(VariantTy::new(reply).unwrap()),
+                DBusCallFlags::NONE,
+                1000,
+                None::<&Cancellable>,
+            )
+            .ok()
+    };
+    if let Some(reply) = call(
+        "org.gnome.Mutter.IdleMonitor",
+        "/org/gnome/Mutter/IdleMonitor/Core",
+        "org.gnome.Mutter.IdleMonitor",
+        "GetIdletime",
+        "(t)",
+    ) {
+        return reply.get::<(u64,)>().map(|(idle,)| idle as u32);
+    }
+    // KDE answers in milliseconds, despite the interface's documentation saying seconds.
+    call(
+        "org.freedesktop.ScreenSaver",
+        "/ScreenSaver",
+        "org.freedesktop.ScreenSaver",
+        "GetSessionIdleTime",
+        "(u)",
+    )?
+    .get::<(u32,)>()
+    .map(|(idle,)| idle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use serde_json::json;
+
+    #[test]
+    fn sway_tree() {
+        let tree = json!({
+            "type": "root",
+            "rect": {"x": 0, "y": 0, "width": 3840, "height": 1080},
+            "nodes": [
+                {"type": "output", "name": "__i3", "rect": {"x": 0, "y": 0, "width": 0, "height": 0}},
+                {
+                    "type": "output",
+                    "name": "DP-1",
+                    "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                    "nodes": [{
+                        "type": "workspace",
+                        "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                        "nodes": [{
+                            "type": "con",
+                            "id": 7,
+                            "pid": 1234,
+                            "app_id": null,
+                            "window_properties": {"class": "steam_app_123"},
+                            "name": "Game",
+                            "focused": true,
+                            "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                        }],
+                        "floating_nodes": [{
+                            "type": "floating_con",
+                            "id": 9,
+                            "pid": 99,
+                            "app_id": "foot",
+                            "name": "Terminal",
+                            "focused": false,
+                            "rect": {"x": 2000, "y": 100, "width": 600, "height": 400},
+                        }],
+                    }],
+                },
+            ],
+        });
+        let layout = sway_layout(&tree);
+        let output = Rect {
+            left: 1920,
+            top: 0,
+            right: 3840,
+            bottom: 1080,
+        };
+        assert_eq!(layout.outputs, [output]);
+        assert_eq!(layout.toplevels.len(), 2);
+        let game = &layout.toplevels[0];
+        assert_eq!(game.id, 7 | ID_FLAG);
+        assert_eq!(game.class.as_deref(), Some("steam_app_123"));
+        assert!(game.focused);
+        assert_eq!(game.monitor, output);
+        assert_eq!(layout.toplevels[1].class.as_deref(), Some("foot"));
+        assert!(!layout.toplevels[1].focused);
+    }
+
+    #[test]
+    fn hyprland_clients() {
+        let monitors = json!([
+            {"id": 0, "x": 0, "y": 0, "width": 3840, "height": 2160, "scale": 2.0, "transform": 0},
+            {"id": 1, "x": 1920, "y": 0, "width": 1080, "height": 1920, "scale": 1.0, "transform": 1},
+        ]);
+        let clients = json!([
+            {"address": "0x55d0a1b2c3d0", "mapped": true, "hidden": false, "at": [0, 0],
+             "size": [1920, 1080], "monitor": 0, "class": "game.exe", "title": "Game", "pid": 42},
+            {"address": "0x55d0a1b2c4e0", "mapped": true, "hidden": true, "at": [0, 0],
+             "size": [100, 100], "monitor": 0, "class": "", "title": "", "pid": 43},
+        ]);
+        let active = json!({"address": "0x55d0a1b2c3d0"});
+        let layout = hyprland_layout(&clients, &monitors, &active);
+        assert_eq!(
+            layout.outputs,
+            [
+                Rect {
+                    left: 0,
+                    top: 0,
+                    right: 1920,
+                    bottom: 1080,
+                },
+                Rect {
+                    left: 1920,
+                    top: 0,
+                    right: 3840,
+                    bottom: 1080,
+                },
+            ]
+        );
+        assert_eq!(layout.toplevels.len(), 1);
+        assert!(layout.toplevels[0].focused);
+        assert_eq!(layout.toplevels[0].monitor, layout.outputs[0]);
+        assert_ne!(layout.toplevels[0].id & ID_FLAG, 0);
+
+        let nothing_focused = hyprland_layout(&clients, &monitors, &json!({}));
+        assert!(!nothing_focused.toplevels[0].focused);
+    }
+
+    #[test]
+    fn crops_scaled_screenshots() {
+        let mut image = RgbImage::new(400, 200);
+        image.put_pixel(220, 20, Rgb([255, 0, 0]));
+        let screen = Rect {
+            left: -100,
+            top: 0,
+            right: 100,
+            bottom: 100,
+        };
+        let area = Rect {
+            left: 0,
+            top: 0,
+            right: 100,
+            bottom: 50,
+        };
+        let cropped = crop_scaled(image, area, screen);
+        assert_eq!(cropped.dimensions(), (200, 100));
+        assert_eq!(cropped.get_pixel(20, 20), &Rgb([255, 0, 0]));
+    }
+}
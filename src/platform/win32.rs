@@ -0,0 +1,232 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use winsafe::{co, prelude::*, GetLastError, HPROCESSLIST, HWND, RECT};
+
+use super::{Rect, WindowInfo};
+use crate::elevation;
+
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Self {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+pub struct DesktopWindow {
+    window: HWND,
+    pid: u32,
+}
+
+impl DesktopWindow {
+    pub fn foreground() -> Result<Self> {
+        let window = HWND::GetForegroundWindow().context("Failed to get foreground window")?;
+        let pid = get_window_pid(&window);
+        Ok(Self { window, pid })
+    }
+
+    /// Visible, titled top-level windows, roughly the ones shown in the taskbar.
+    pub fn visible() -> Result<Vec<Self>> {
+        let mut windows = Vec::new();
+        winsafe::EnumWindows(|window: HWND| {
+            let shown = window.IsWindowVisible()
+                && window.GetWindow(co::GW::OWNER).is_err()
+                && window.GetWindowText().is_ok_and(|title| !title.is_empty());
+            if shown {
+                let pid = get_window_pid(&window);
+                windows.push(Self { window, pid });
+            }
+            true
+        })?;
+        Ok(windows)
+    }
+}
+
+impl WindowInfo for DesktopWindow {
+    fn id(&self) -> u32 {
+        self.window.ptr() as u32
+    }
+
+    fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn process_name(&self) -> Result<String> {
+        get_process_name_from_pid(self.pid)
+    }
+
+    fn process_path(&self) -> Option<PathBuf> {
+        get_process_path(self.pid).ok()
+    }
+
+    fn class(&self) -> Option<String> {
+        self.window.GetClassName().ok()
+    }
+
+    fn title(&self) -> Result<String> {
+        Ok(self.window.GetWindowText()?)
+    }
+
+    fn bounds(&self) -> Result<Rect> {
+        Ok(get_window_bounds(&self.window)?.into())
+    }
+
+    fn monitor(&self) -> Result<Rect> {
+        let monitor = self.window.MonitorFromWindow(co::MONITOR::DEFAULTTONEAREST);
+        let mut monitor_info = winsafe::MONITORINFOEX::default();
+        monitor.GetMonitorInfo(&mut monitor_info)?;
+        Ok(monitor_info.rcMonitor.into())
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.pid != 0 && elevation::is_process_elevated(self.pid)
+    }
+}
+
+fn get_process_name_from_pid(pid: u32) -> Result<String> {
+    use winsafe::co::TH32CS;
+    let mut process_list = HPROCESSLIST::CreateToolhelp32Snapshot(TH32CS::SNAPPROCESS, None)?;
+    let process = process_list
+        .iter_processes()
+        .filter_map(|p| p.ok())
+        .find(|p| p.th32ProcessID == pid)
+        .context("PID not found")?;
+    let process_name = Path::new(&process.szExeFile())
+        .file_stem()
+        .context("Getting file stem")?
+        .to_str()
+        .context("File to String")?
+        .to_owned();
+    Ok(process_name)
+}
+
+fn get_process_path(pid: u32) -> Result<PathBuf> {
+    use winsafe::co::{PROCESS, PROCESS_NAME};
+    let process = winsafe::HPROCESS::OpenProcess(PROCESS::QUERY_LIMITED_INFORMATION, false, pid)?;
+    Ok(process
+        .QueryFullProcessImageName(PROCESS_NAME::WIN32)?
+        .into())
+}
+
+fn get_hosted_pid(frame: &HWND, frame_pid: u32) -> Option<u32> {
+    let mut hosted_pid = None;
+    frame.EnumChildWindows(|child| {
+        let (_, pid) = child.GetWindowThreadProcessId();
+        if pid != 0 && pid != frame_pid {
+            hosted_pid = Some(pid);
+            return false;
+        }
+        true
+    });
+    hosted_pid
+}
+
+fn get_window_pid(window: &HWND) -> u32 {
+    let (_, pid) = window.GetWindowThreadProcessId();
+    let is_frame_host = get_process_name_from_pid(pid)
+        .is_ok_and(|name| name.eq_ignore_ascii_case("ApplicationFrameHost"));
+    if is_frame_host {
+        if let Some(hosted_pid) = get_hosted_pid(window, pid) {
+            return hosted_pid;
+        }
+    }
+    pid
+}
+
+pub fn get_window_bounds(window: &HWND) -> Result<RECT> {
+    use windows::Win32::{
+        Foundation::{HWND as RawHwnd, RECT as RawRect},
+        Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+    };
+    let mut rect = RawRect::default();
+    let result = unsafe {
+        DwmGetWindowAttribute(
+            RawHwnd(window.ptr() as isize),
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RawRect as *mut _,
+            std::mem::size_of::<RawRect>() as u32,
+        )
+    };
+    match result {
+        Ok(()) => Ok(RECT {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }),
+        Err(_) => Ok(window.GetWindowRect()?),
+    }
+}
+
+pub fn tick_count() -> u32 {
+    unsafe { windows::Win32::System::SystemInformation::GetTickCount() }
+}
+
+pub fn get_last_input_time() -> Result<u32> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    if unsafe { !GetLastInputInfo(&mut info as *mut LASTINPUTINFO).as_bool() } {
+        bail!(GetLastError())
+    }
+    Ok(info.dwTime)
+}
+
+pub fn open_file(path: &Path) -> Result<()> {
+    use winsafe::co::SW;
+    let path = path.to_str().context("path to string")?;
+    HWND::NULL
+        .ShellExecute("open", path, None, None, SW::SHOWNORMAL)
+        .map_err(|e| anyhow!("ShellExecute failed: {e}"))?;
+    Ok(())
+}
+
+/// Opens a text file in its associated editor, or in Notepad when nothing is associated with it.
+pub fn edit_file(path: &Path) -> Result<()> {
+    if open_file(path).is_ok() {
+        return Ok(());
+    }
+    std::process::Command::new("notepad.exe")
+        .arg(path)
+        .spawn()
+        .context("Starting Notepad")?;
+    Ok(())
+}
+
+pub fn open_folder(path: &Path) -> Result<()> {
+    use winsafe::co::SW;
+    let path = path.canonicalize()?;
+    let path = path.to_str().context("path to string")?;
+    HWND::NULL
+        .ShellExecute("explore", path, None, None, SW::SHOWNORMAL)
+        .map_err(|e| anyhow!("ShellExecute failed: {e}"))?;
+    Ok(())
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+pub fn local_data_dir() -> Option<PathBuf> {
+    winsafe::SHGetKnownFolderPath(&co::KNOWNFOLDERID::LocalAppData, co::KF::DEFAULT, None)
+        .map(PathBuf::from)
+        .ok()
+}
+
+pub fn pictures_dir() -> Option<PathBuf> {
+    winsafe::SHGetKnownFolderPath(&co::KNOWNFOLDERID::Pictures, co::KF::DEFAULT, None)
+        .map(PathBuf::from)
+        .ok()
+}
+
+pub fn double_click_time() -> Duration {
+    Duration::from_millis(winsafe::GetDoubleClickTime().into())
+}
@@ -0,0 +1,439 @@
+use anyhow::{bail, Context, Result};
+use image::{Rgb, RgbImage};
+use std::{
+    cell::OnceCell,
+    ffi::CString,
+    os::raw::{c_int, c_long, c_ulong},
+    path::PathBuf,
+    rc::Rc,
+    sync::Once,
+};
+use x11_dl::{xfixes, xinerama, xlib, xss};
+
+use super::{
+    linux::{process_name, process_path},
+    Rect, WindowInfo,
+};
+use crate::{elevation, CaptureMode, CaptureSettings};
+
+struct Connection {
+    xlib: xlib::Xlib,
+    xinerama: Option<xinerama::Xlib>,
+    xss: Option<xss::Xss>,
+    xfixes: Option<xfixes::Xlib>,
+    display: *mut xlib::Display,
+    root: xlib::Window,
+}
+
+unsafe extern "C" fn ignore_error(_: *mut xlib::Display, _: *mut xlib::XErrorEvent) -> c_int {
+    0
+}
+
+impl Connection {
+    fn open() -> Result<Self> {
+        let xlib = xlib::Xlib::open().context("Loading libX11")?;
+        static ERROR_HANDLER: Once = Once::new();
+        // Windows can close between any two requests, and the default handler exits the process.
+        ERROR_HANDLER.call_once(|| unsafe {
+            (xlib.XSetErrorHandler)(Some(ignore_error));
+        });
+        let display = unsafe { (xlib.XOpenDisplay)(std::ptr::null()) };
+        if display.is_null() {
+            bail!("Could not connect to the X server, is DISPLAY set?");
+        }
+        let root = unsafe { (xlib.XDefaultRootWindow)(display) };
+        Ok(Self {
+            xlib,
+            xinerama: xinerama::Xlib::open().ok(),
+            xss: xss::Xss::open().ok(),
+            xfixes: xfixes::Xlib::open().ok(),
+            display,
+            root,
+        })
+    }
+
+    fn atom(&self, name: &str) -> xlib::Atom {
+        let name = CString::new(name).unwrap();
+        unsafe { (self.xlib.XInternAtom)(self.display, name.as_ptr(), xlib::False) }
+    }
+
+    /// Reads a property of `format` bits per item. 32 bit items come back as C longs.
+    fn property<T: Copy>(&self, window: xlib::Window, name: &str, format: c_int) -> Option<Vec<T>> {
+        let (mut kind, mut actual_format, mut count, mut remaining) = (0, 0, 0, 0);
+        let mut data = std::ptr::null_mut();
+        let status = unsafe {
+            (self.xlib.XGetWindowProperty)(
+                self.display,
+                window,
+                self.atom(name),
+                0,
+                c_long::from(u16::MAX),
+                xlib::False,
+                xlib::AnyPropertyType as c_ulong,
+                &mut kind,
+                &mut actual_format,
+                &mut count,
+                &mut remaining,
+                &mut data,
+            )
+        };
+        if status != c_int::from(xlib::Success) || data.is_null() {
+            return None;
+        }
+        let items = (actual_format == format).then(|| {
+            unsafe { std::slice::from_raw_parts(data.cast::<T>(), count as usize) }.to_vec()
+        });
+        unsafe { (self.xlib.XFree)(data.cast()) };
+        items
+    }
+
+    fn number(&self, window: xlib::Window, name: &str) -> Option<c_ulong> {
+        self.property::<c_ulong>(window, name, 32)?.first().copied()
+    }
+
+    fn text(&self, window: xlib::Window, name: &str) -> Option<String> {
+        let bytes = self.property::<u8>(window, name, 8)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn window_rect(&self, window: xlib::Window) -> Result<Rect> {
+        let mut attributes: xlib::XWindowAttributes = unsafe { std::mem::zeroed() };
+        if unsafe { (self.xlib.XGetWindowAttributes)(self.display, window, &mut attributes) } == 0 {
+            bail!("Window no longer exists");
+        }
+        let (mut left, mut top, mut child) = (0, 0, 0);
+        unsafe {
+            (self.xlib.XTranslateCoordinates)(
+                self.display,
+                window,
+                self.root,
+                0,
+                0,
+                &mut left,
+                &mut top,
+                &mut child,
+            )
+        };
+        Ok(Rect {
+            left,
+            top,
+            right: left + attributes.width,
+            bottom: top + attributes.height,
+        })
+    }
+
+    /// The monitors from Xinerama, or the whole screen when it isn't available.
+    fn monitors(&self) -> Result<Vec<Rect>> {
+        let mut monitors = Vec::new();
+        if let Some(xinerama) = &self.xinerama {
+            let mut count = 0;
+            let screens = unsafe { (xinerama.XineramaQueryScreens)(self.display, &mut count) };
+            if !screens.is_null() {
+                let screens = unsafe { std::slice::from_raw_parts(screens, count as usize) };
+                monitors.extend(screens.iter().map(|screen| Rect {
+                    left: screen.x_org.into(),
+                    top: screen.y_org.into(),
+                    right: i32::from(screen.x_org) + i32::from(screen.width),
+                    bottom: i32::from(screen.y_org) + i32::from(screen.height),
+                }));
+                unsafe { (self.xlib.XFree)(screens.as_ptr() as *mut _) };
+            }
+        }
+        if monitors.is_empty() {
+            monitors.push(self.window_rect(self.root)?);
+        }
+        Ok(monitors)
+    }
+
+    /// The monitor the window is on, or all the monitors it touches when spanning.
+    fn monitor_area(&self, bounds: &Rect, span_monitors: bool) -> Result<Rect> {
+        if !span_monitors {
+            return self.monitor_of(bounds);
+        }
+        self.monitors()?
+            .into_iter()
+            .filter(|monitor| monitor.overlaps(bounds))
+            .reduce(Rect::union)
+            .context("Window is on no monitor")
+    }
+
+    fn monitor_of(&self, bounds: &Rect) -> Result<Rect> {
+        let (x, y) = (
+            (bounds.left + bounds.right) / 2,
+            (bounds.top + bounds.bottom) / 2,
+        );
+        let monitors = self.monitors()?;
+        Ok(monitors
+            .iter()
+            .find(|m| m.left <= x && x < m.right && m.top <= y && y < m.bottom)
+            .unwrap_or(&monitors[0])
+            .to_owned())
+    }
+
+    fn image(&self, drawable: xlib::Drawable, area: Rect) -> Result<RgbImage> {
+        let (width, height) = (
+            (area.right - area.left) as u32,
+            (area.bottom - area.top) as u32,
+        );
+        let image = unsafe {
+            (self.xlib.XGetImage)(
+                self.display,
+                drawable,
+                area.left,
+                area.top,
+                width,
+                height,
+                !0,
+                xlib::ZPixmap,
+            )
+        };
+        if image.is_null() {
+            bail!("XGetImage failed");
+        }
+        let rgb = unsafe { self.to_rgb(image) };
+        unsafe { (self.xlib.XDestroyImage)(image) };
+        Ok(rgb)
+    }
+
+    /// Draws the pointer over an image of `area`, X never includes it in captures.
+    fn draw_cursor(&self, image: &mut RgbImage, area: Rect) {
+        let Some(xfixes) = &self.xfixes else {
+            return;
+        };
+        let cursor = unsafe { (xfixes.XFixesGetCursorImage)(self.display) };
+        if cursor.is_null() {
+            return;
+        }
+        let info = unsafe { &*cursor };
+        let (width, height) = (usize::from(info.width), usize::from(info.height));
+        // Pixels are premultiplied ARGB, one per C long.
+        let pixels = unsafe { std::slice::from_raw_parts(info.pixels, width * height) };
+        let left = i32::from(info.x) - i32::from(info.xhot) - area.left;
+        let top = i32::from(info.y) - i32::from(info.yhot) - area.top;
+        for (index, &pixel) in pixels.iter().enumerate() {
+            let (x, y) = (left + (index % width) as i32, top + (index / width) as i32);
+            if x < 0 || y < 0 || x as u32 >= image.width() || y as u32 >= image.height() {
+                continue;
+            }
+            let alpha = (pixel >> 24 & 0xff) as u32;
+            let target = image.get_pixel_mut(x as u32, y as u32);
+            for (channel, shift) in target.0.iter_mut().zip([16, 8, 0]) {
+                let source = (pixel >> shift & 0xff) as u32;
+                *channel = (source + u32::from(*channel) * (255 - alpha) / 255).min(255) as u8;
+            }
+        }
+        unsafe { (self.xlib.XFree)(cursor.cast()) };
+    }
+
+    unsafe fn to_rgb(&self, image: *mut xlib::XImage) -> RgbImage {
+        let info = &*image;
+        let channel = |value: c_ulong, mask: c_ulong| {
+            if mask == 0 {
+                return 0;
+            }
+            let shift = mask.trailing_zeros();
+            (((value & mask) >> shift) * 255 / (mask >> shift)) as u8
+        };
+        // 32 bit pixels in the host byte order can be read directly, which is much faster.
+        let direct = info.bits_per_pixel == 32 && info.byte_order == xlib::LSBFirst;
+        RgbImage::from_fn(info.width as u32, info.height as u32, |x, y| {
+            let value = if direct {
+                let offset = y as usize * info.bytes_per_line as usize + x as usize * 4;
+                c_ulong::from(info.data.add(offset).cast::<u32>().read_unaligned())
+            } else {
+                (self.xlib.XGetPixel)(image, x as c_int, y as c_int)
+            };
+            Rgb([
+                channel(value, info.red_mask),
+                channel(value, info.green_mask),
+                channel(value, info.blue_mask),
+            ])
+        })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { (self.xlib.XCloseDisplay)(self.display) };
+    }
+}
+
+thread_local! {
+    static CONNECTION: OnceCell<Rc<Connection>> = const { OnceCell::new() };
+}
+
+/// Xlib isn't thread safe, so each thread talks to the X server over its own connection.
+fn connection() -> Result<Rc<Connection>> {
+    CONNECTION.with(|cell| {
+        if let Some(connection) = cell.get() {
+            return Ok(connection.clone());
+        }
+        let connection = Rc::new(Connection::open()?);
+        let _ = cell.set(connection.clone());
+        Ok(connection)
+    })
+}
+
+pub struct X11Window {
+    connection: Rc<Connection>,
+    window: xlib::Window,
+    pid: u32,
+}
+
+impl X11Window {
+    fn new(connection: Rc<Connection>, window: xlib::Window) -> Self {
+        let pid = connection.number(window, "_NET_WM_PID").unwrap_or(0) as u32;
+        Self {
+            connection,
+            window,
+            pid,
+        }
+    }
+
+    pub fn foreground() -> Result<Self> {
+        let connection = connection()?;
+        let window = connection
+            .number(connection.root, "_NET_ACTIVE_WINDOW")
+            .filter(|&window| window != 0)
+            .context("Failed to get foreground window")?;
+        Ok(Self::new(connection, window))
+    }
+
+    /// Titled windows the window manager lists, roughly the ones shown in the taskbar.
+    pub fn visible() -> Result<Vec<Self>> {
+        let connection = connection()?;
+        let windows = connection
+            .property::<c_ulong>(connection.root, "_NET_CLIENT_LIST", 32)
+            .context("The window manager doesn't list its windows")?;
+        Ok(windows
+            .into_iter()
+            .map(|window| Self::new(connection.clone(), window))
+            .filter(|window| window.title().is_ok_and(|title| !title.is_empty()))
+            .collect())
+    }
+}
+
+impl WindowInfo for X11Window {
+    fn id(&self) -> u32 {
+        self.window as u32
+    }
+
+    fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn process_name(&self) -> Result<String> {
+        process_name(self.pid)
+    }
+
+    fn process_path(&self) -> Option<PathBuf> {
+        process_path(self.pid)
+    }
+
+    fn class(&self) -> Option<String> {
+        // WM_CLASS holds the instance name and then the class name.
+        let text = self.connection.text(self.window, "WM_CLASS")?;
+        let mut names = text.split('\0').filter(|name| !name.is_empty());
+        let instance = names.next()?;
+        Some(names.next().unwrap_or(instance).to_owned())
+    }
+
+    fn title(&self) -> Result<String> {
+        self.connection
+            .text(self.window, "_NET_WM_NAME")
+            .or_else(|| self.connection.text(self.window, "WM_NAME"))
+            .context("Window has no title")
+    }
+
+    fn bounds(&self) -> Result<Rect> {
+        self.connection.window_rect(self.window)
+    }
+
+    fn monitor(&self) -> Result<Rect> {
+        self.connection.monitor_of(&self.bounds()?)
+    }
+
+    fn is_elevated(&self) -> bool {
+        self.pid != 0 && elevation::is_process_elevated(self.pid)
+    }
+}
+
+/// Captures the window, or the monitors it is on. Monitor captures read the screen so they
+/// include anything drawn over the game, like the Windows monitor capture.
+pub fn capture(id: u32, settings: &CaptureSettings) -> Result<RgbImage> {
+    let connection = connection()?;
+    let window = xlib::Window::from(id);
+    let bounds = connection.window_rect(window)?;
+    let area = match settings.mode {
+        CaptureMode::Window => bounds,
+        CaptureMode::Monitor => connection.monitor_area(&bounds, settings.span_monitors)?,
+    };
+    let mut image = match settings.mode {
+        // Under a compositor this reads the window's own contents, without what overlaps it.
+        CaptureMode::Window => connection.image(
+            window,
+            Rect {
+                left: 0,
+                top: 0,
+                right: area.right - area.left,
+                bottom: area.bottom - area.top,
+            },
+        )?,
+        CaptureMode::Monitor => connection.image(connection.root, area)?,
+    };
+    if settings.cursor == Some(true) {
+        connection.draw_cursor(&mut image, area);
+    }
+    Ok(image)
+}
+
+/// The window's monitor area and the whole screen, for cropping a capture of the screen.
+pub fn screen_area(id: u32, span_monitors: bool) -> Result<(Rect, Rect)> {
+    let connection = connection()?;
+    let bounds = connection.window_rect(xlib::Window::from(id))?;
+    let area = connection.monitor_area(&bounds, span_monitors)?;
+    Ok((area, connection.window_rect(connection.root)?))
+}
+
+/// Milliseconds since the last input the X server saw.
+pub fn idle_time() -> Result<u32> {
+    let connection = connection()?;
+    let xss = connection
+        .xss
+        .as_ref()
+        .context("libXss is needed to detect input")?;
+    let info = unsafe { (xss.XScreenSaverAllocInfo)() };
+    if info.is_null() {
+        bail!("Could not allocate screen saver info");
+    }
+    let status = unsafe { (xss.XScreenSaverQueryInfo)(connection.display, connection.root, info) };
+    let idle = unsafe { (*info).idle } as u32;
+    unsafe { (connection.xlib.XFree)(info.cast()) };
+    if status == 0 {
+        bail!("The X server doesn't support the screen saver extension");
+    }
+    Ok(idle)
+}
+
+/// Calls `changed` whenever the window manager activates another window.
+pub fn watch_foreground(mut changed: impl FnMut()) -> Result<()> {
+    // This blocks waiting for events, so it gets a connection of its own.
+    let connection = Connection::open()?;
+    let active_window = connection.atom("_NET_ACTIVE_WINDOW");
+    unsafe {
+        (connection.xlib.XSelectInput)(
+            connection.display,
+            connection.root,
+            xlib::PropertyChangeMask,
+        )
+    };
+    let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+    loop {
+        unsafe { (connection.xlib.XNextEvent)(connection.display, &mut event) };
+        if event.get_type() == xlib::PropertyNotify
+            && unsafe { event.property.atom } == active_window
+        {
+            changed();
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+fn xdg_dir(variable: &str, default: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(default)))
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn local_data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+pub fn pictures_dir() -> Option<PathBuf> {
+    let output = Command::new("xdg-user-dir").arg("PICTURES").output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let dir = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+            Some(PathBuf::from(dir)).filter(|dir| dir.is_absolute())
+        }
+        _ => None,
+    }
+    .or_else(|| xdg_dir("XDG_PICTURES_DIR", "Pictures"))
+}
+
+pub fn open_file(path: &Path) -> Result<()> {
+    Command::new("xdg-open")
+        .arg(path)
+        .spawn()
+        .context("Starting xdg-open")?;
+    Ok(())
+}
+
+/// xdg-open already picks a text editor for files without a better association.
+pub fn edit_file(path: &Path) -> Result<()> {
+    open_file(path)
+}
+
+pub fn open_folder(path: &Path) -> Result<()> {
+    open_file(&path.canonicalize()?)
+}
+
+/// GTK's default. Tray icons only report menu use here, so this rarely matters.
+pub fn double_click_time() -> Duration {
+    Duration::from_millis(400)
+}
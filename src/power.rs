@@ -0,0 +1,80 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+#[cfg(windows)]
+use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatteryConfig {
+    pub pause: bool,
+    pub screenshot_delay: Option<u64>,
+    pub skip_extras: bool,
+}
+
+impl BatteryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.screenshot_delay == Some(0) {
+            bail!("on_battery.screenshot_delay must be at least 1 second");
+        }
+        Ok(())
+    }
+
+    pub fn skips_extras(&self) -> bool {
+        self.skip_extras && on_battery()
+    }
+}
+
+/// How long a reading of the power source is trusted. The tray and the screenshot loop ask
+/// several times a tick, and reading it means a system call, files in /sys or running `pmset`.
+const POWER_TTL: Duration = Duration::from_secs(5);
+
+pub fn on_battery() -> bool {
+    static CACHED: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+    let mut cached = CACHED.lock().unwrap();
+    if let Some((read_at, on_battery)) = *cached {
+        if read_at.elapsed() < POWER_TTL {
+            return on_battery;
+        }
+    }
+    let on_battery = read_on_battery();
+    *cached = Some((Instant::now(), on_battery));
+    on_battery
+}
+
+#[cfg(windows)]
+fn read_on_battery() -> bool {
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.is_ok() && status.ACLineStatus == 0
+}
+
+/// On battery when the machine has a mains supply and none of them is online, so desktops
+/// without any supply listed never count as on battery.
+#[cfg(target_os = "linux")]
+fn read_on_battery() -> bool {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap_or_default();
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mains: Vec<_> = supplies
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| read(path.join("type")).trim() == "Mains")
+        .collect();
+    !mains.is_empty()
+        && mains
+            .iter()
+            .all(|path| read(path.join("online")).trim() == "0")
+}
+
+/// `pmset` names the current power source on its first line.
+#[cfg(target_os = "macos")]
+fn read_on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
@@ -0,0 +1,52 @@
+use std::{collections::VecDeque, path::Path, sync::Mutex};
+use tracing::warn;
+
+use crate::index::{Index, ScreenshotRow};
+
+const RECENT_SCREENSHOTS: usize = 10;
+
+struct Recent {
+    screenshots: VecDeque<ScreenshotRow>,
+    generation: u64,
+}
+
+static RECENT: Mutex<Recent> = Mutex::new(Recent {
+    screenshots: VecDeque::new(),
+    generation: 0,
+});
+
+/// Fills the list from the index, so the menu isn't empty after a restart.
+pub fn load(target_folder: &Path) {
+    let screenshots = Index::open(target_folder)
+        .and_then(|index| index.recent_screenshots(RECENT_SCREENSHOTS as u32))
+        .unwrap_or_else(|e| {
+            warn!("Could not read recent screenshots from index: {e:?}");
+            Vec::new()
+        });
+    let mut recent = RECENT.lock().unwrap();
+    recent.screenshots = screenshots.into();
+    recent.generation += 1;
+}
+
+pub fn push(screenshot: ScreenshotRow) {
+    let mut recent = RECENT.lock().unwrap();
+    recent.screenshots.push_front(screenshot);
+    recent.screenshots.truncate(RECENT_SCREENSHOTS);
+    recent.generation += 1;
+}
+
+/// Changes whenever the list does.
+pub fn generation() -> u64 {
+    RECENT.lock().unwrap().generation
+}
+
+/// The most recent screenshots that still exist, newest first.
+pub fn list() -> Vec<ScreenshotRow> {
+    let recent = RECENT.lock().unwrap();
+    recent
+        .screenshots
+        .iter()
+        .filter(|screenshot| screenshot.path.exists())
+        .cloned()
+        .collect()
+}
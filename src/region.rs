@@ -0,0 +1,112 @@
+use image::{imageops::FilterType, Rgb, RgbImage};
+use serde::Deserialize;
+
+const BLUR_FACTOR: u32 = 24;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LengthRepr {
+    Pixels(u32),
+    Text(String),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(try_from = "LengthRepr")]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl TryFrom<LengthRepr> for Length {
+    type Error = String;
+
+    fn try_from(value: LengthRepr) -> Result<Self, Self::Error> {
+        let text = match value {
+            LengthRepr::Pixels(pixels) => return Ok(Self::Pixels(pixels)),
+            LengthRepr::Text(text) => text,
+        };
+        let parsed = match text.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(Self::Percent),
+            None => text
+                .trim()
+                .trim_end_matches("px")
+                .parse()
+                .ok()
+                .map(Self::Pixels),
+        };
+        parsed.ok_or_else(|| format!("Invalid length {text:?}, expected pixels or a percentage"))
+    }
+}
+
+impl Length {
+    fn resolve(self, total: u32) -> u32 {
+        match self {
+            Self::Pixels(pixels) => pixels.min(total),
+            Self::Percent(percent) => (total as f32 * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl Region {
+    pub fn resolve(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let x = self.x.resolve(width);
+        let y = self.y.resolve(height);
+        let w = self.width.resolve(width).min(width - x);
+        let h = self.height.resolve(height).min(height - y);
+        (w > 0 && h > 0).then_some((x, y, w, h))
+    }
+}
+
+pub fn crop(image: RgbImage, region: &Region) -> RgbImage {
+    match region.resolve(image.width(), image.height()) {
+        Some((x, y, w, h)) if (w, h) != image.dimensions() => {
+            image::imageops::crop_imm(&image, x, y, w, h).to_image()
+        }
+        _ => image,
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyStyle {
+    #[default]
+    Blur,
+    Blackout,
+}
+
+pub fn redact(image: &mut RgbImage, regions: &[Region], style: PrivacyStyle) {
+    let (width, height) = image.dimensions();
+    for region in regions {
+        let Some((x, y, w, h)) = region.resolve(width, height) else {
+            continue;
+        };
+        let replacement = match style {
+            PrivacyStyle::Blackout => RgbImage::from_pixel(w, h, Rgb([0, 0, 0])),
+            PrivacyStyle::Blur => {
+                let area = image::imageops::crop_imm(image, x, y, w, h).to_image();
+                let small = image::imageops::resize(
+                    &area,
+                    (w / BLUR_FACTOR).max(1),
+                    (h / BLUR_FACTOR).max(1),
+                    FilterType::Triangle,
+                );
+                image::imageops::resize(&small, w, h, FilterType::Triangle)
+            }
+        };
+        image::imageops::replace(image, &replacement, x as i64, y as i64);
+    }
+}
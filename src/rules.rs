@@ -0,0 +1,523 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::{
+    config::GlobPattern,
+    library,
+    platform::{DesktopWindow, WindowInfo},
+    script, Config, MatchMode, RuleEntry,
+};
+
+impl RuleEntry {
+    pub fn has_name_matcher(&self) -> bool {
+        !self.name.is_empty() || self.match_glob.is_some() || self.match_regex.is_some()
+    }
+
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.name.to_lowercase() == name.to_lowercase()
+    }
+
+    pub fn has_qualifier(&self) -> bool {
+        self.path.is_some() || self.window_class.is_some()
+    }
+
+    pub fn matches_qualifiers(&self, target: &RuleTarget) -> bool {
+        self.path
+            .as_ref()
+            .is_none_or(|p| target.path.is_some_and(|path| p.matches(path)))
+            && self
+                .window_class
+                .as_ref()
+                .is_none_or(|p| target.class.is_some_and(|class| p.matches(class)))
+    }
+
+    /// How the rule is shown in menus: the game name it saves as, or what it matches.
+    pub fn label(&self) -> String {
+        let name = self
+            .override_name
+            .as_deref()
+            .or((!self.name.is_empty()).then_some(self.name.as_str()));
+        name.or(self.match_glob.as_ref().map(GlobPattern::as_str))
+            .or(self.match_regex.as_ref().map(|p| p.0.as_str()))
+            .or(self.window_class.as_ref().map(GlobPattern::as_str))
+            .unwrap_or("Unnamed rule")
+            .to_owned()
+    }
+
+    pub fn matches_pattern(&self, name: &str) -> bool {
+        self.match_glob.as_ref().is_some_and(|p| p.matches(name))
+            || self
+                .match_regex
+                .as_ref()
+                .is_some_and(|p| p.0.is_match(name))
+    }
+}
+
+pub struct RuleTarget<'a> {
+    pub name: &'a str,
+    pub path: Option<&'a Path>,
+    pub class: Option<&'a str>,
+}
+
+impl<'a> RuleTarget<'a> {
+    pub fn name(name: &'a str) -> Self {
+        Self {
+            name,
+            path: None,
+            class: None,
+        }
+    }
+}
+
+pub fn find_rule<'a>(rules: &'a [RuleEntry], target: &RuleTarget) -> Option<&'a RuleEntry> {
+    let name = target.name;
+    let qualified = rules.iter().find(|e| {
+        e.has_qualifier()
+            && e.matches_qualifiers(target)
+            && (!e.has_name_matcher() || e.matches_name(name) || e.matches_pattern(name))
+    });
+    let unqualified = || rules.iter().filter(|e| !e.has_qualifier());
+    qualified
+        .or_else(|| unqualified().find(|e| e.matches_name(name)))
+        .or_else(|| unqualified().find(|e| e.matches_pattern(name)))
+}
+
+pub fn normalize_name(name: &str, preserve_unicode: bool) -> String {
+    if preserve_unicode {
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        return name.trim_end_matches(['.', ' ']).to_owned();
+    }
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' | ' ' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct GameWindow {
+    pub id: u32,
+    pub name: String,
+    pub process: String,
+    pub title: String,
+    pub process_path: Option<PathBuf>,
+    pub store_game: Option<library::InstalledGame>,
+    pub elevated: bool,
+    pub rule: RuleEntry,
+}
+
+/// One line saying which rule matches the window and how it would be captured.
+pub fn describe(config: &Config, window: &impl WindowInfo) -> String {
+    let title = window.title().unwrap_or_default();
+    let name = window.process_name().unwrap_or_else(|_| title.clone());
+    let path = window.process_path();
+    let class = window.class();
+    let rule = find_rule(
+        &config.rules,
+        &RuleTarget {
+            name: &name,
+            path: path.as_deref(),
+            class: class.as_deref(),
+        },
+    )
+    .and_then(|rule| config.rules.iter().position(|r| std::ptr::eq(r, rule)))
+    .map_or("no rule".to_owned(), |index| format!("rule {}", index + 1));
+    match evaluate(config, window, false) {
+        Ok(game) => format!("{name} ({title}): {rule}, captured as {:?}", game.name),
+        Err(e) => format!("{name} ({title}): {rule}, not captured: {e}"),
+    }
+}
+
+pub fn get_valid_window(config: &Config, manual: bool) -> Result<GameWindow> {
+    evaluate(config, &DesktopWindow::foreground()?, manual)
+}
+
+pub fn evaluate(config: &Config, window: &impl WindowInfo, manual: bool) -> Result<GameWindow> {
+    let name = if window.pid() == 0 {
+        normalize_name(&window.title()?, config.preserve_unicode)
+    } else {
+        window.process_name()?
+    };
+
+    let process_path = window.process_path();
+    let class = window.class();
+    let rule = find_rule(
+        &config.rules,
+        &RuleTarget {
+            name: &name,
+            path: process_path.as_deref(),
+            class: class.as_deref(),
+        },
+    );
+    let store_game = process_path.as_deref().and_then(library::find_game);
+    let title = window.title().unwrap_or_default();
+    let tolerance = rule
+        .and_then(|r| r.fullscreen_tolerance)
+        .unwrap_or(config.fullscreen_tolerance);
+
+    let decision = match &config.script {
+        Some(script) => {
+            let bounds = window.bounds()?;
+            let decision = script.decide(&script::ScriptWindow {
+                name: &name,
+                title: &title,
+                path: process_path.as_deref(),
+                class: class.as_deref(),
+                store_name: store_game.as_ref().map(|game| game.name.as_str()),
+                rule: rule.map(|r| r.name.as_str()),
+                fullscreen: window.is_fullscreen(tolerance).unwrap_or(false),
+                width: bounds.right - bounds.left,
+                height: bounds.bottom - bounds.top,
+            });
+            decision.unwrap_or_else(|e| {
+                warn!("{e:?}");
+                script::Decision::Default
+            })
+        }
+        None => script::Decision::Default,
+    };
+    if matches!(decision, script::Decision::Ignore) && !manual {
+        bail!("Rules script ignored the window");
+    }
+    let forced = manual || matches!(decision, script::Decision::Capture { .. });
+
+    if rule.is_none() && !forced {
+        match config.mode {
+            MatchMode::All => {}
+            MatchMode::Whitelist => bail!("Executable is not whitelisted"),
+            MatchMode::Known if store_game.is_none() => bail!("Executable is not a known game"),
+            MatchMode::Known => {}
+        }
+    }
+    let associated_config = rule.cloned().unwrap_or_default();
+    if associated_config.ignore && !forced {
+        bail!("Executable is ignored")
+    }
+    if !associated_config.enabled && !forced {
+        bail!("Rule {} is disabled", associated_config.label());
+    }
+
+    if !forced {
+        if let Some(pattern) = &associated_config.title_matches {
+            if !pattern.0.is_match(&title) {
+                bail!("Window title {title:?} does not match title_matches");
+            }
+        }
+        if let Some(pattern) = &associated_config.title_excludes {
+            if pattern.0.is_match(&title) {
+                bail!("Window title {title:?} matches title_excludes");
+            }
+        }
+    }
+
+    if associated_config.needs_fullscreen && !forced && !window.is_fullscreen(tolerance)? {
+        bail!("Window is not fullscreen");
+    }
+
+    let title_name = associated_config.title_name.as_ref().and_then(|pattern| {
+        let captures = pattern.0.captures(&title)?;
+        let game = captures.name("game").or_else(|| captures.get(1))?;
+        Some(normalize_name(
+            game.as_str().trim(),
+            config.preserve_unicode,
+        ))
+    });
+    let script_name = match decision {
+        script::Decision::Capture { name } => {
+            name.map(|n| normalize_name(n.trim(), config.preserve_unicode))
+        }
+        _ => None,
+    };
+    let display_name = if let Some(n) = script_name.filter(|n| !n.is_empty()) {
+        n
+    } else if let Some(n) = title_name.filter(|n| !n.is_empty()) {
+        n
+    } else if let Some(n) = &associated_config.override_name {
+        n.clone()
+    } else if associated_config.use_window_name {
+        normalize_name(&window.title()?, config.preserve_unicode)
+    } else if let Some(game) = store_game.as_ref().filter(|_| config.store_names) {
+        normalize_name(&game.name, config.preserve_unicode)
+    } else {
+        name.clone()
+    };
+
+    Ok(GameWindow {
+        id: window.id(),
+        name: display_name,
+        process: name,
+        title,
+        process_path,
+        store_game,
+        elevated: window.is_elevated(),
+        rule: associated_config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Rect;
+
+    const SCREEN: Rect = Rect {
+        left: 0,
+        top: 0,
+        right: 1920,
+        bottom: 1080,
+    };
+
+    struct MockWindow {
+        pid: u32,
+        process: &'static str,
+        path: Option<&'static str>,
+        class: Option<&'static str>,
+        title: &'static str,
+        bounds: Rect,
+    }
+
+    impl MockWindow {
+        fn new(process: &'static str) -> Self {
+            Self {
+                pid: 1,
+                process,
+                path: None,
+                class: None,
+                title: "",
+                bounds: SCREEN,
+            }
+        }
+    }
+
+    impl WindowInfo for MockWindow {
+        fn id(&self) -> u32 {
+            1
+        }
+
+        fn pid(&self) -> u32 {
+            self.pid
+        }
+
+        fn process_name(&self) -> Result<String> {
+            Ok(self.process.to_owned())
+        }
+
+        fn process_path(&self) -> Option<PathBuf> {
+            self.path.map(PathBuf::from)
+        }
+
+        fn class(&self) -> Option<String> {
+            self.class.map(str::to_owned)
+        }
+
+        fn title(&self) -> Result<String> {
+            Ok(self.title.to_owned())
+        }
+
+        fn bounds(&self) -> Result<Rect> {
+            Ok(self.bounds)
+        }
+
+        fn monitor(&self) -> Result<Rect> {
+            Ok(SCREEN)
+        }
+
+        fn is_elevated(&self) -> bool {
+            false
+        }
+    }
+
+    fn config(text: &str) -> Config {
+        toml::from_str(&format!(
+            "target_folder = \"shots\"\nscreenshot_delay = 60\n{text}"
+        ))
+        .unwrap()
+    }
+
+    fn rule_name(config: &Config, target: &RuleTarget) -> Option<String> {
+        find_rule(&config.rules, target).map(|r| r.override_name.clone().unwrap_or_default())
+    }
+
+    #[test]
+    fn exact_name_beats_patterns() {
+        let config = config(
+            r#"
+            [[rules]]
+            match = "elden*"
+            override_name = "glob"
+            [[rules]]
+            name = "EldenRing"
+            override_name = "name"
+            "#,
+        );
+        let rule = rule_name(&config, &RuleTarget::name("eldenring"));
+        assert_eq!(rule.as_deref(), Some("name"));
+        let rule = rule_name(&config, &RuleTarget::name("eldenring_launcher"));
+        assert_eq!(rule.as_deref(), Some("glob"));
+    }
+
+    #[test]
+    fn qualified_rules_are_checked_first() {
+        let config = config(
+            r#"
+            [[rules]]
+            name = "game"
+            override_name = "unqualified"
+            [[rules]]
+            name = "game"
+            path = "D:/Games/Hollow Knight/*"
+            override_name = "qualified"
+            "#,
+        );
+        let path = Path::new("D:/Games/Hollow Knight/game.exe");
+        let target = RuleTarget {
+            name: "game",
+            path: Some(path),
+            class: None,
+        };
+        assert_eq!(rule_name(&config, &target).as_deref(), Some("qualified"));
+        let path = Path::new("D:/Games/Other/game.exe");
+        let target = RuleTarget {
+            path: Some(path),
+            ..target
+        };
+        assert_eq!(rule_name(&config, &target).as_deref(), Some("unqualified"));
+    }
+
+    #[test]
+    fn window_class_only_rule() {
+        let config = config(
+            r#"
+            [[rules]]
+            window_class = "Unreal*"
+            override_name = "unreal"
+            "#,
+        );
+        let target = RuleTarget {
+            name: "anything",
+            path: None,
+            class: Some("UnrealWindow"),
+        };
+        assert_eq!(rule_name(&config, &target).as_deref(), Some("unreal"));
+        assert_eq!(rule_name(&config, &RuleTarget::name("anything")), None);
+    }
+
+    #[test]
+    fn regex_rule() {
+        let config = config(
+            r#"
+            [[rules]]
+            match_regex = "^(dolphin|cemu)$"
+            override_name = "emulator"
+            "#,
+        );
+        let rule = rule_name(&config, &RuleTarget::name("Cemu"));
+        assert_eq!(rule.as_deref(), Some("emulator"));
+        assert_eq!(rule_name(&config, &RuleTarget::name("cemu2")), None);
+    }
+
+    #[test]
+    fn normalize_ascii() {
+        assert_eq!(normalize_name("Hades: II", false), "Hades_ II");
+        assert_eq!(normalize_name("ファイナル", false), "_____");
+    }
+
+    #[test]
+    fn normalize_unicode() {
+        assert_eq!(normalize_name("ファイナル: XVI", true), "ファイナル_ XVI");
+        assert_eq!(normalize_name("Game. ", true), "Game");
+    }
+
+    #[test]
+    fn ignored_and_whitelist() {
+        let config = config(
+            r#"
+            mode = "whitelist"
+            [[rules]]
+            name = "explorer"
+            ignore = true
+            [[rules]]
+            name = "game"
+            "#,
+        );
+        assert!(evaluate(&config, &MockWindow::new("explorer"), false).is_err());
+        assert!(evaluate(&config, &MockWindow::new("other"), false).is_err());
+        assert!(evaluate(&config, &MockWindow::new("other"), true).is_ok());
+        assert!(evaluate(&config, &MockWindow::new("game"), false).is_ok());
+    }
+
+    #[test]
+    fn disabled_rule() {
+        let config = config(
+            r#"
+            [[rules]]
+            name = "game"
+            enabled = false
+            [[rules]]
+            name = "other"
+            enabled = true
+            "#,
+        );
+        assert!(evaluate(&config, &MockWindow::new("game"), false).is_err());
+        assert!(evaluate(&config, &MockWindow::new("game"), true).is_ok());
+        assert!(evaluate(&config, &MockWindow::new("other"), false).is_ok());
+    }
+
+    #[test]
+    fn fullscreen_requirement() {
+        let config = config("");
+        let mut window = MockWindow::new("game");
+        window.bounds = Rect {
+            right: 1280,
+            bottom: 720,
+            ..SCREEN
+        };
+        assert!(evaluate(&config, &window, false).is_err());
+        assert!(evaluate(&config, &window, true).is_ok());
+    }
+
+    #[test]
+    fn display_name() {
+        let config = config(
+            r#"
+            [[rules]]
+            name = "Dolphin"
+            title_name = '\| ([^|]+?) \([A-Z0-9]{6}\)$'
+            override_name = "Dolphin"
+            [[rules]]
+            name = "eldenring"
+            override_name = "Elden Ring"
+            "#,
+        );
+        let window = evaluate(&config, &MockWindow::new("eldenring"), false).unwrap();
+        assert_eq!(window.name, "Elden Ring");
+        assert_eq!(window.process, "eldenring");
+
+        let mut dolphin = MockWindow::new("Dolphin");
+        dolphin.title = "Dolphin 5.0 | JIT64 | Metroid: Prime (GM8E01)";
+        let window = evaluate(&config, &dolphin, false).unwrap();
+        assert_eq!(window.name, "Metroid_ Prime");
+
+        dolphin.title = "Dolphin 5.0";
+        let window = evaluate(&config, &dolphin, false).unwrap();
+        assert_eq!(window.name, "Dolphin");
+    }
+
+    #[test]
+    fn window_title_without_process() {
+        let config = config("");
+        let mut window = MockWindow::new("");
+        window.pid = 0;
+        window.title = "Some <Game>";
+        let window = evaluate(&config, &window, false).unwrap();
+        assert_eq!(window.name, "Some _Game_");
+    }
+}
@@ -0,0 +1,386 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use toml_edit::{value, Item, Table};
+use winsafe::{co, gui, prelude::*};
+
+use crate::{
+    config::{parse_config, read_document, rule_tables, write_document},
+    platform::{DesktopWindow, WindowInfo},
+    rules::describe,
+    widgets::{self, button, check_box, close, edit, show_error},
+    Config,
+};
+
+const LABEL_WIDTH: u32 = 130;
+const FIELD_X: i32 = 150;
+const FIELD_WIDTH: u32 = 300;
+const PREVIEW_TIMER: usize = 1;
+/// Keys edited by the form, any other key of a rule is kept as is.
+const FORM_KEYS: [&str; 6] = [
+    "name",
+    "match",
+    "window_class",
+    "override_name",
+    "ignore",
+    "needs_fullscreen",
+];
+const MATCHERS: [(&str, &str); 6] = [
+    ("", "name"),
+    ("match ", "match"),
+    ("regex ", "match_regex"),
+    ("path ", "path"),
+    ("class ", "window_class"),
+    ("title ", "title_name"),
+];
+
+/// Process name and window class of a window listed in the picker.
+type RunningWindow = (String, Option<String>);
+
+#[derive(Clone)]
+struct RulesEditor {
+    window: gui::WindowMain,
+    list: gui::ListView,
+    picker: gui::ComboBox,
+    name: gui::Edit,
+    match_glob: gui::Edit,
+    window_class: gui::Edit,
+    override_name: gui::Edit,
+    ignore: gui::CheckBox,
+    needs_fullscreen: gui::CheckBox,
+    add: gui::Button,
+    apply: gui::Button,
+    delete: gui::Button,
+    close: gui::Button,
+    preview: gui::Label,
+    config_path: PathBuf,
+    config: Rc<RefCell<Option<Config>>>,
+    running: Rc<RefCell<Vec<RunningWindow>>>,
+}
+
+fn label(window: &gui::WindowMain, text: &str, y: i32) {
+    let _ = widgets::label(window, text, (10, y + 3), LABEL_WIDTH);
+}
+
+fn text<'a>(rule: &'a Table, key: &str) -> Option<&'a str> {
+    rule.get(key)
+        .and_then(Item::as_str)
+        .filter(|t| !t.is_empty())
+}
+
+fn summary(rule: &Table) -> [String; 3] {
+    let matches: Vec<String> = MATCHERS
+        .iter()
+        .filter_map(|(prefix, key)| Some(format!("{prefix}{}", text(rule, key)?)))
+        .collect();
+    let mut options = Vec::new();
+    if rule.get("ignore").and_then(Item::as_bool) == Some(true) {
+        options.push("ignored".to_owned());
+    }
+    if rule.get("enabled").and_then(Item::as_bool) == Some(false) {
+        options.push("disabled".to_owned());
+    }
+    if rule.get("needs_fullscreen").and_then(Item::as_bool) == Some(false) {
+        options.push("windowed".to_owned());
+    }
+    let others = rule
+        .iter()
+        .filter(|(key, _)| !FORM_KEYS.contains(key))
+        .filter(|(key, _)| !MATCHERS.iter().any(|(_, matcher)| matcher == key))
+        .count();
+    if others > 0 {
+        options.push(format!("+{others} more"));
+    }
+    [
+        matches.join(", "),
+        text(rule, "override_name").unwrap_or_default().to_owned(),
+        options.join(", "),
+    ]
+}
+
+fn set_flag(rule: &mut Table, key: &str, set: bool, default: bool) {
+    if set == default {
+        rule.remove(key);
+    } else {
+        rule[key] = value(set);
+    }
+}
+
+fn load(path: &Path) -> Option<Config> {
+    let text = std::fs::read_to_string(path).ok()?;
+    parse_config(path, &text).ok()
+}
+
+impl RulesEditor {
+    fn new(config_path: &Path) -> Self {
+        let window = gui::WindowMain::new(gui::WindowMainOpts {
+            title: "autogamejournal rules".to_owned(),
+            size: (640, 510),
+            ..Default::default()
+        });
+
+        let list = gui::ListView::new(
+            &window,
+            gui::ListViewOpts {
+                position: (10, 10),
+                size: (620, 200),
+                columns: vec![
+                    ("Matches".to_owned(), 270),
+                    ("Saved as".to_owned(), 170),
+                    ("Options".to_owned(), 160),
+                ],
+                list_view_style: co::LVS::REPORT
+                    | co::LVS::NOSORTHEADER
+                    | co::LVS::SHOWSELALWAYS
+                    | co::LVS::SINGLESEL,
+                list_view_ex_style: co::LVS_EX::FULLROWSELECT,
+                ..Default::default()
+            },
+        );
+        label(&window, "Pick a running window", 220);
+        let picker = gui::ComboBox::new(
+            &window,
+            gui::ComboBoxOpts {
+                position: (FIELD_X, 220),
+                width: 480,
+                ..Default::default()
+            },
+        );
+        label(&window, "Executable name", 255);
+        let name = edit(&window, "", (FIELD_X, 255), FIELD_WIDTH);
+        label(&window, "Or executable glob", 290);
+        let match_glob = edit(&window, "", (FIELD_X, 290), FIELD_WIDTH);
+        label(&window, "Window class", 325);
+        let window_class = edit(&window, "", (FIELD_X, 325), FIELD_WIDTH);
+        label(&window, "Save as", 360);
+        let override_name = edit(&window, "", (FIELD_X, 360), FIELD_WIDTH);
+        let ignore = check_box(&window, "Ignore", (FIELD_X, 395), 140);
+        let needs_fullscreen =
+            check_box(&window, "Only when fullscreen", (FIELD_X + 150, 395), 180);
+        let add = button(&window, "Add", (10, 430));
+        let apply = button(&window, "Apply", (110, 430));
+        let delete = button(&window, "Delete", (210, 430));
+        let close = button(&window, "Close", (540, 430));
+        let preview = widgets::label(&window, "", (10, 475), 620);
+
+        let editor = Self {
+            window,
+            list,
+            picker,
+            name,
+            match_glob,
+            window_class,
+            override_name,
+            ignore,
+            needs_fullscreen,
+            add,
+            apply,
+            delete,
+            close,
+            preview,
+            config_path: config_path.to_owned(),
+            config: Default::default(),
+            running: Default::default(),
+        };
+        editor.events();
+        editor
+    }
+
+    fn events(&self) {
+        let editor = self.clone();
+        self.window.on().wm_create(move |_| {
+            editor
+                .needs_fullscreen
+                .set_check_state(gui::CheckState::Checked);
+            editor.report(Ok(()));
+            editor.window.hwnd().SetTimer(PREVIEW_TIMER, 1000, None)?;
+            Ok(0)
+        });
+
+        let editor = self.clone();
+        self.window.on().wm_timer(PREVIEW_TIMER, move || {
+            editor.update_preview();
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.list.on().lvn_item_changed(move |_| {
+            if let Some(index) = editor.selected() {
+                editor.fill(index)?;
+            }
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.picker.on().cbn_drop_down(move || {
+            editor.list_running();
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.picker.on().cbn_sel_change(move || {
+            let running = editor.running.borrow();
+            let picked = editor.picker.items().selected_index();
+            if let Some((name, class)) = picked.and_then(|i| running.get(i as usize)) {
+                editor.name.set_text(name);
+                editor.match_glob.set_text("");
+                editor
+                    .window_class
+                    .set_text(class.as_deref().unwrap_or_default());
+            }
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.add.on().bn_clicked(move || {
+            editor.report(editor.save(None));
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.apply.on().bn_clicked(move || {
+            match editor.selected() {
+                Some(index) => editor.report(editor.save(Some(index))),
+                None => editor.report(Err(anyhow!("Select a rule to change"))),
+            }
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.delete.on().bn_clicked(move || {
+            if let Some(index) = editor.selected() {
+                editor.report(editor.remove(index));
+            }
+            Ok(())
+        });
+
+        let editor = self.clone();
+        self.close.on().bn_clicked(move || close(&editor.window));
+    }
+
+    fn selected(&self) -> Option<usize> {
+        let items = self.list.items();
+        let item = items.iter_selected().next()?;
+        Some(item.index() as usize)
+    }
+
+    fn report(&self, result: Result<()>) {
+        if let Err(e) = result.and_then(|()| self.refresh()) {
+            show_error(self.window.hwnd(), &e);
+        }
+    }
+
+    fn refresh(&self) -> Result<()> {
+        let mut document = read_document(&self.config_path)?;
+        self.list.items().delete_all();
+        for rule in rule_tables(&mut document)?.iter() {
+            self.list.items().add(&summary(rule), None);
+        }
+        *self.config.borrow_mut() = load(&self.config_path);
+        Ok(())
+    }
+
+    fn fill(&self, index: usize) -> Result<()> {
+        let mut document = read_document(&self.config_path)?;
+        let Some(rule) = rule_tables(&mut document)?.get(index) else {
+            return Ok(());
+        };
+        self.name.set_text(text(rule, "name").unwrap_or_default());
+        self.match_glob
+            .set_text(text(rule, "match").unwrap_or_default());
+        self.window_class
+            .set_text(text(rule, "window_class").unwrap_or_default());
+        self.override_name
+            .set_text(text(rule, "override_name").unwrap_or_default());
+        let flag = |key: &str, default: bool| match rule
+            .get(key)
+            .and_then(Item::as_bool)
+            .unwrap_or(default)
+        {
+            true => gui::CheckState::Checked,
+            false => gui::CheckState::Unchecked,
+        };
+        self.ignore.set_check_state(flag("ignore", false));
+        self.needs_fullscreen
+            .set_check_state(flag("needs_fullscreen", true));
+        Ok(())
+    }
+
+    fn save(&self, index: Option<usize>) -> Result<()> {
+        let mut document = read_document(&self.config_path)?;
+        let rules = rule_tables(&mut document)?;
+        let rule = match index {
+            Some(index) => rules.get_mut(index).context("The rule no longer exists")?,
+            None => {
+                rules.push(Table::new());
+                rules.get_mut(rules.len() - 1).unwrap()
+            }
+        };
+        for (key, field) in [
+            ("name", &self.name),
+            ("match", &self.match_glob),
+            ("window_class", &self.window_class),
+            ("override_name", &self.override_name),
+        ] {
+            match field.text().trim() {
+                "" => rule.remove(key),
+                text => rule.insert(key, value(text)),
+            };
+        }
+        set_flag(rule, "ignore", self.ignore.is_checked(), false);
+        set_flag(
+            rule,
+            "needs_fullscreen",
+            self.needs_fullscreen.is_checked(),
+            true,
+        );
+        if summary(rule)[0].is_empty() {
+            bail!("Fill in an executable name, glob or window class");
+        }
+        write_document(&self.config_path, &document)
+    }
+
+    fn remove(&self, index: usize) -> Result<()> {
+        let mut document = read_document(&self.config_path)?;
+        rule_tables(&mut document)?.remove(index);
+        write_document(&self.config_path, &document)
+    }
+
+    fn list_running(&self) {
+        let own_pid = std::process::id();
+        let windows = DesktopWindow::visible().unwrap_or_default();
+        let mut running = self.running.borrow_mut();
+        running.clear();
+        self.picker.items().delete_all();
+        for window in windows.iter().filter(|window| window.pid() != own_pid) {
+            let Ok(name) = window.process_name() else {
+                continue;
+            };
+            let title = window.title().unwrap_or_default();
+            self.picker.items().add(&[format!("{name} - {title}")]);
+            running.push((name, window.class()));
+        }
+    }
+
+    fn update_preview(&self) {
+        // Keep showing the last window while the editor itself is focused.
+        let Ok(window) = DesktopWindow::foreground() else {
+            return;
+        };
+        if window.pid() == std::process::id() {
+            return;
+        }
+        let text = match &*self.config.borrow() {
+            Some(config) => describe(config, &window),
+            None => "The config file has errors".to_owned(),
+        };
+        self.preview.set_text(&format!("Foreground: {text}"));
+    }
+}
+
+pub fn run(config_path: &Path) -> Result<()> {
+    widgets::run(&RulesEditor::new(config_path).window)
+}
@@ -0,0 +1,88 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, Weekday};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    pub no_capture: Vec<QuietPeriod>,
+}
+
+impl Schedule {
+    pub fn is_quiet(&self, time: DateTime<Local>) -> bool {
+        self.no_capture.iter().any(|period| period.contains(time))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct QuietPeriod {
+    days: Option<Vec<Weekday>>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+fn parse_day(text: &str) -> Result<Weekday> {
+    text.parse()
+        .map_err(|_| anyhow::anyhow!("Unknown weekday {text:?}"))
+}
+
+fn parse_days(text: &str) -> Result<Vec<Weekday>> {
+    let mut days = Vec::new();
+    for part in text.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (mut day, last) = (parse_day(first)?, parse_day(last)?);
+                days.push(day);
+                while day != last {
+                    day = day.succ();
+                    days.push(day);
+                }
+            }
+            None => days.push(parse_day(part)?),
+        }
+    }
+    Ok(days)
+}
+
+fn parse_time(text: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M")
+        .with_context(|| format!("Invalid time {text:?}, expected HH:MM"))
+}
+
+impl TryFrom<String> for QuietPeriod {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let (days, times) = match value.trim().rsplit_once(' ') {
+            Some((days, times)) => (Some(parse_days(days.trim())?), times),
+            None => (None, value.trim()),
+        };
+        let Some((start, end)) = times.split_once('-') else {
+            bail!("Invalid period {value:?}, expected e.g. \"23:00-07:00\" or \"sat,sun 01:00-09:00\"");
+        };
+        Ok(Self {
+            days,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+}
+
+impl QuietPeriod {
+    fn applies_to(&self, day: Weekday) -> bool {
+        self.days.as_ref().is_none_or(|days| days.contains(&day))
+    }
+
+    fn contains(&self, time: DateTime<Local>) -> bool {
+        let now = time.time();
+        let today = time.weekday();
+        if self.start <= self.end {
+            self.applies_to(today) && self.start <= now && now < self.end
+        } else {
+            let yesterday = (time - Duration::days(1)).weekday();
+            (self.applies_to(today) && now >= self.start)
+                || (self.applies_to(yesterday) && now < self.end)
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use image::RgbImage;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{create_dir_all, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    caption,
+    capture::{Capturer, Frame},
+    discord, encoder, hooks, imaging, index, learn, maintenance, ocr, recent, region,
+    rules::GameWindow,
+    session::SessionTracker,
+    spool, template, upload, webhook, CaptureBackend, CaptureSettings, Config, ImageFormat,
+};
+
+const RECENT_PATHS: usize = 64;
+
+/// Where screenshots are recorded. The screenshot thread goes through this so it can be tested
+/// without a journal folder.
+pub trait Journal {
+    /// Names the screenshot and records it, the file is written in the background.
+    fn store(
+        &mut self,
+        config: &Config,
+        window: &GameWindow,
+        frame: Frame,
+        hash: u64,
+    ) -> Result<PathBuf>;
+}
+
+impl Journal for SessionTracker {
+    fn store(
+        &mut self,
+        config: &Config,
+        window: &GameWindow,
+        frame: Frame,
+        hash: u64,
+    ) -> Result<PathBuf> {
+        store_screenshot(config, self, window, frame, hash)
+    }
+}
+
+/// What became of a capture that didn't fail.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Saved(PathBuf),
+    Blank,
+    Duplicate,
+}
+
+/// Captures the window and stores it, unless the frame is blank or a near-duplicate of the
+/// last screenshot of the game. Manual captures keep blank frames, forced ones keep duplicates.
+pub fn take_screenshot(
+    config: &Config,
+    capturer: &impl Capturer,
+    journal: &mut impl Journal,
+    window: &GameWindow,
+    last_hashes: &mut HashMap<String, u64>,
+    manual: bool,
+    forced: bool,
+) -> Result<Outcome> {
+    let mut settings = config.capture_settings(&window.rule);
+    if config.on_battery.skips_extras() {
+        settings.burst_frames = 1;
+        settings.thumbnails = false;
+    }
+    let frame = capturer
+        .capture_burst(window.id, &settings)
+        .context("Capturing the window")?;
+    if settings.skip_blank && !manual && imaging::is_blank(&frame.image) {
+        return Ok(Outcome::Blank);
+    }
+    let hash = imaging::dhash(&frame.image);
+    if let Some(&last_hash) = last_hashes.get(&window.name).filter(|_| !forced) {
+        if imaging::hash_distance(hash, last_hash) < settings.duplicate_threshold {
+            return Ok(Outcome::Duplicate);
+        }
+    }
+    let path = journal.store(config, window, frame, hash)?;
+    last_hashes.insert(window.name.clone(), hash);
+    Ok(Outcome::Saved(path))
+}
+
+pub fn save_image(image: &RgbImage, path: &Path, format: ImageFormat, quality: u8) -> Result<()> {
+    use image::codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder};
+    let mut file = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Jpeg => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut file, quality))?
+        }
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new(&mut file))?,
+        // The image crate only encodes lossless webp, so quality doesn't apply.
+        ImageFormat::Webp => image.write_with_encoder(WebPEncoder::new_lossless(&mut file))?,
+    }
+    file.flush()?;
+    Ok(())
+}
+
+fn screenshot_path(
+    target_path: &Path,
+    template: &str,
+    context: &template::FileNameContext,
+    settings: CaptureSettings,
+) -> Result<PathBuf> {
+    Ok(target_path.join(template::render(
+        template,
+        context,
+        settings.format.extension(),
+    )?))
+}
+
+fn unique_path(path: PathBuf) -> PathBuf {
+    static RECENT: Mutex<VecDeque<PathBuf>> = Mutex::new(VecDeque::new());
+    let mut recent = RECENT.lock().unwrap();
+    let taken = |path: &Path| path.exists() || recent.iter().any(|p| p == path);
+    let mut unique = path.clone();
+    let mut suffix = 2;
+    while taken(&unique) {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        unique = path.with_file_name(format!("{stem}_{suffix}.{extension}"));
+        suffix += 1;
+    }
+    recent.push_back(unique.clone());
+    if recent.len() > RECENT_PATHS {
+        recent.pop_front();
+    }
+    unique
+}
+
+fn save_screenshot(image: &RgbImage, filename: &Path, settings: CaptureSettings) -> Result<()> {
+    if let Some(dir) = filename.parent() {
+        create_dir_all(dir)?;
+    }
+    let resized = settings
+        .max_dimension
+        .and_then(|max| imaging::downscale(image, max));
+    save_image(
+        resized.as_ref().unwrap_or(image),
+        filename,
+        settings.format,
+        settings.quality,
+    )?;
+    if settings.thumbnails {
+        if let Err(e) = imaging::save_thumbnail(image, filename) {
+            warn!("Could not save thumbnail for {filename:?}: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+pub struct ScreenshotInfo {
+    pub config: Config,
+    pub window: GameWindow,
+    pub hash: u64,
+    pub time: DateTime<Local>,
+    pub path: PathBuf,
+    pub method: CaptureBackend,
+    pub session_id: Option<String>,
+    pub session_file: Option<PathBuf>,
+    pub session_count: u32,
+}
+
+struct PendingScreenshot {
+    frame: Frame,
+    caption: Option<String>,
+    info: ScreenshotInfo,
+}
+
+fn finish_screenshot(shot: PendingScreenshot) {
+    let PendingScreenshot {
+        mut frame,
+        caption,
+        info,
+    } = shot;
+    let config = &info.config;
+    region::redact(
+        &mut frame.image,
+        &info.window.rule.privacy_regions,
+        info.window.rule.privacy_style,
+    );
+    if let Some(text) = &caption {
+        caption::stamp(&mut frame.image, &config.caption, text);
+    }
+    let settings = config.capture_settings(&info.window.rule);
+    if !spool::is_reachable(&config.target_folder) {
+        let spooled = spool::spool_path(&config.target_folder, &info.path);
+        match save_screenshot(&frame.image, &spooled, settings) {
+            Ok(()) => spool::hold(spooled, info),
+            Err(e) => error!(game = %info.window.name, "Could not spool screenshot: {e:?}"),
+        }
+        return;
+    }
+    if let Err(e) = save_screenshot(&frame.image, &info.path, settings) {
+        error!(game = %info.window.name, "Could not save screenshot: {e:?}");
+        return;
+    }
+    screenshot_saved(info);
+}
+
+pub fn screenshot_saved(info: ScreenshotInfo) {
+    let ScreenshotInfo {
+        config,
+        window,
+        hash,
+        time,
+        path,
+        method,
+        session_id,
+        session_file,
+        session_count,
+    } = info;
+    index::update(&config.target_folder, |index| {
+        index.record_screenshot(&index::ScreenshotRecord {
+            path: &path,
+            game: &window.name,
+            timestamp: time,
+            hash,
+            window_title: &window.title,
+            session_id: session_id.as_deref(),
+            capture_method: method.name(),
+        })
+    });
+    info!(game = %window.name, path = ?path, "Saved screenshot");
+    if !window.rule.has_name_matcher() && !window.rule.has_qualifier() {
+        learn::record(&window.process, &window.name);
+    }
+    recent::push(index::ScreenshotRow {
+        path: path.clone(),
+        game: window.name.clone(),
+        timestamp: time.fixed_offset(),
+    });
+    if config.ocr && !config.on_battery.skips_extras() {
+        ocr::enqueue(&path);
+    }
+    if config.upload.is_some() {
+        upload::enqueue(&path);
+        if let Some(session_file) = &session_file {
+            upload::enqueue(session_file);
+        }
+    }
+    if let Some(command) = &config.on_screenshot {
+        hooks::run(
+            "on_screenshot",
+            command,
+            &[
+                ("path", &path.to_string_lossy()),
+                ("game", &window.name),
+                ("session", session_id.as_deref().unwrap_or_default()),
+                ("timestamp", &time.to_rfc3339()),
+            ],
+        );
+    }
+    if let Some(url) = &window.rule.discord_webhook {
+        if session_count.is_multiple_of(window.rule.discord_every.unwrap_or(1)) {
+            discord::post_screenshot(url.clone(), window.name.clone(), time, path.clone());
+        }
+    }
+    if !config.webhooks.is_empty() {
+        webhook::enqueue(webhook::CaptureEvent {
+            game: window.name.clone(),
+            timestamp: time,
+            path,
+            session_id,
+        });
+    }
+
+    if config.max_total_size.is_some() {
+        maintenance::request_quota_check();
+    }
+}
+
+pub fn store_screenshot(
+    config: &Config,
+    sessions: &mut SessionTracker,
+    window: &GameWindow,
+    frame: Frame,
+    hash: u64,
+) -> Result<PathBuf> {
+    let settings = config.capture_settings(&window.rule);
+    let time = Local::now();
+    let sequence = if template::uses_placeholder(config.filename_template(), "sequence") {
+        index::Index::open(&config.target_folder)
+            .and_then(|index| index.screenshot_count(&window.name))
+            .unwrap_or_else(|e| {
+                warn!("Could not read screenshot count from index: {e:?}");
+                0
+            })
+            + 1
+    } else {
+        0
+    };
+    let context = template::FileNameContext {
+        game: &window.name,
+        time,
+        session: sessions.current_id(),
+        session_index: sessions.current().map_or(0, |s| s.screenshot_count) + 1,
+        sequence,
+        utc: config.utc_timestamps,
+        datetime_format: &config.timestamp_format,
+    };
+    let path = unique_path(screenshot_path(
+        &config.target_folder,
+        config.filename_template(),
+        &context,
+        settings,
+    )?);
+    let caption = config
+        .caption
+        .enabled
+        .then(|| caption::text(&window.name, time, sessions.current()));
+    sessions.record_screenshot(&path);
+    let shot = PendingScreenshot {
+        info: ScreenshotInfo {
+            config: config.clone(),
+            window: window.clone(),
+            hash,
+            time,
+            path: path.clone(),
+            method: frame.method,
+            session_id: sessions.current_id().map(str::to_owned),
+            session_file: sessions.current().map(|s| s.path()),
+            session_count: sessions.current().map_or(1, |s| s.screenshot_count),
+        },
+        frame,
+        caption,
+    };
+    encoder::submit(move || finish_screenshot(shot));
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleEntry;
+    use anyhow::bail;
+
+    struct MockCapturer(Option<RgbImage>);
+
+    impl Capturer for MockCapturer {
+        fn capture(&self, _id: u32, _settings: &CaptureSettings) -> Result<Frame> {
+            match &self.0 {
+                Some(image) => Ok(Frame {
+                    image: image.clone(),
+                    method: CaptureBackend::Gdi,
+                }),
+                None => bail!("Window is gone"),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct MockJournal {
+        hashes: Vec<u64>,
+    }
+
+    impl Journal for MockJournal {
+        fn store(
+            &mut self,
+            _config: &Config,
+            window: &GameWindow,
+            _frame: Frame,
+            hash: u64,
+        ) -> Result<PathBuf> {
+            self.hashes.push(hash);
+            Ok(PathBuf::from(format!(
+                "{}_{}.jpg",
+                window.name,
+                self.hashes.len()
+            )))
+        }
+    }
+
+    fn config() -> Config {
+        toml::from_str("target_folder = \"shots\"\nscreenshot_delay = 60\nduplicate_threshold = 5")
+            .unwrap()
+    }
+
+    fn window() -> GameWindow {
+        GameWindow {
+            id: 1,
+            name: "game".to_owned(),
+            process: "game".to_owned(),
+            title: "Game".to_owned(),
+            process_path: None,
+            store_game: None,
+            elevated: false,
+            rule: RuleEntry::default(),
+        }
+    }
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(64, 36, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 7) as u8, 128])
+        })
+    }
+
+    fn take(
+        capturer: &MockCapturer,
+        journal: &mut MockJournal,
+        last_hashes: &mut HashMap<String, u64>,
+        forced: bool,
+    ) -> Result<Outcome> {
+        take_screenshot(
+            &config(),
+            capturer,
+            journal,
+            &window(),
+            last_hashes,
+            false,
+            forced,
+        )
+    }
+
+    #[test]
+    fn duplicates_are_skipped_unless_forced() {
+        let capturer = MockCapturer(Some(gradient()));
+        let mut journal = MockJournal::default();
+        let mut last_hashes = HashMap::new();
+        let first = take(&capturer, &mut journal, &mut last_hashes, false).unwrap();
+        assert_eq!(first, Outcome::Saved(PathBuf::from("game_1.jpg")));
+        let second = take(&capturer, &mut journal, &mut last_hashes, false).unwrap();
+        assert_eq!(second, Outcome::Duplicate);
+        let forced = take(&capturer, &mut journal, &mut last_hashes, true).unwrap();
+        assert_eq!(forced, Outcome::Saved(PathBuf::from("game_2.jpg")));
+        assert_eq!(journal.hashes.len(), 2);
+    }
+
+    #[test]
+    fn blank_frames_are_skipped() {
+        let capturer = MockCapturer(Some(RgbImage::new(64, 36)));
+        let mut journal = MockJournal::default();
+        let mut last_hashes = HashMap::new();
+        let outcome = take(&capturer, &mut journal, &mut last_hashes, false).unwrap();
+        assert_eq!(outcome, Outcome::Blank);
+        assert!(journal.hashes.is_empty());
+    }
+
+    #[test]
+    fn capture_errors_leave_the_journal_alone() {
+        let capturer = MockCapturer(None);
+        let mut journal = MockJournal::default();
+        let mut last_hashes = HashMap::new();
+        assert!(take(&capturer, &mut journal, &mut last_hashes, false).is_err());
+        assert!(journal.hashes.is_empty());
+        assert!(last_hashes.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, bail, Result};
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+
+const MAX_OPERATIONS: u64 = 100_000;
+
+pub struct ScriptWindow<'a> {
+    pub name: &'a str,
+    pub title: &'a str,
+    pub path: Option<&'a Path>,
+    pub class: Option<&'a str>,
+    pub store_name: Option<&'a str>,
+    pub rule: Option<&'a str>,
+    pub fullscreen: bool,
+    pub width: i32,
+    pub height: i32,
+}
+
+pub enum Decision {
+    Default,
+    Ignore,
+    Capture { name: Option<String> },
+}
+
+pub struct RulesScript {
+    engine: Engine,
+    ast: AST,
+}
+
+fn optional(value: Option<&str>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, |v| v.into())
+}
+
+impl RulesScript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine
+            .compile_file(path.to_owned())
+            .map_err(|e| anyhow!("Could not compile {path:?}: {e}"))?;
+        Ok(Self { engine, ast })
+    }
+
+    pub fn decide(&self, window: &ScriptWindow) -> Result<Decision> {
+        let mut info = Map::new();
+        info.insert("name".into(), window.name.into());
+        info.insert("title".into(), window.title.into());
+        info.insert(
+            "path".into(),
+            optional(window.path.map(|p| p.to_string_lossy()).as_deref()),
+        );
+        info.insert("class".into(), optional(window.class));
+        info.insert("store_name".into(), optional(window.store_name));
+        info.insert("rule".into(), optional(window.rule));
+        info.insert("fullscreen".into(), window.fullscreen.into());
+        info.insert("width".into(), (window.width as i64).into());
+        info.insert("height".into(), (window.height as i64).into());
+
+        let mut scope = Scope::new();
+        scope.push_constant("window", info);
+        let result: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| anyhow!("Rules script failed: {e}"))?;
+
+        if result.is_unit() {
+            Ok(Decision::Default)
+        } else if let Some(capture) = result.clone().try_cast::<bool>() {
+            Ok(if capture {
+                Decision::Capture { name: None }
+            } else {
+                Decision::Ignore
+            })
+        } else if result.is_string() {
+            Ok(Decision::Capture {
+                name: Some(result.into_string().unwrap_or_default()),
+            })
+        } else if let Some(map) = result.clone().try_cast::<Map>() {
+            let capture = map
+                .get("capture")
+                .and_then(|v| v.as_bool().ok())
+                .unwrap_or(true);
+            let name = map.get("name").and_then(|v| v.clone().into_string().ok());
+            Ok(if capture {
+                Decision::Capture { name }
+            } else {
+                Decision::Ignore
+            })
+        } else {
+            bail!(
+                "Rules script returned a {}, expected (), a bool, a string or a map",
+                result.type_name()
+            )
+        }
+    }
+}
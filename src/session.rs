@@ -0,0 +1,306 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Serializer};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    animation, contact_sheet, encoder, format_interval, hooks, index::Index, journal,
+    library::Store, notification, spool, timelapse, upload, Config, GameWindow,
+};
+
+#[derive(Serialize)]
+pub struct Session {
+    pub id: String,
+    pub game: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub window_title: String,
+    pub process_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<Store>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_id: Option<String>,
+    pub screenshot_count: u32,
+    pub screenshots: Vec<PathBuf>,
+    #[serde(rename = "played_seconds", serialize_with = "serialize_seconds")]
+    pub played: Duration,
+    #[serde(skip)]
+    unsaved_playtime: Duration,
+    #[serde(skip)]
+    previous_playtime: Duration,
+    #[serde(skip)]
+    target_folder: PathBuf,
+    #[serde(skip)]
+    last_seen: Instant,
+    #[serde(skip)]
+    focused: bool,
+    /// Changed while the target folder was unreachable, and not written yet.
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+impl Session {
+    fn new(window: &GameWindow, target_folder: &Path) -> Self {
+        let start = Local::now();
+        let previous_playtime = Index::open(target_folder)
+            .and_then(|index| index.total_playtime(&window.name))
+            .unwrap_or_else(|e| {
+                warn!("Could not read playtime from index: {e:?}");
+                Duration::ZERO
+            });
+        Self {
+            id: start.format("%Y-%m-%d_%H-%M-%S").to_string(),
+            game: window.name.clone(),
+            start,
+            end: start,
+            window_title: window.title.clone(),
+            process_path: window.process_path.clone(),
+            store: window.store_game.as_ref().map(|game| game.store),
+            store_id: window.store_game.as_ref().map(|game| game.id.clone()),
+            screenshot_count: 0,
+            screenshots: Vec::new(),
+            played: Duration::ZERO,
+            unsaved_playtime: Duration::ZERO,
+            previous_playtime,
+            target_folder: target_folder.to_owned(),
+            last_seen: Instant::now(),
+            focused: false,
+            unsaved: false,
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.target_folder.join(&self.game)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir().join(format!("{}.session.json", self.id))
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::create_dir_all(self.dir())?;
+        fs::write(self.path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn hook_values(&self) -> [(&'static str, String); 5] {
+        [
+            ("game", self.game.clone()),
+            ("session", self.id.clone()),
+            ("session_file", self.path().to_string_lossy().into_owned()),
+            ("screenshots", self.screenshot_count.to_string()),
+            ("played", self.played.as_secs().to_string()),
+        ]
+    }
+
+    pub fn total_playtime(&self) -> Duration {
+        self.previous_playtime + self.played
+    }
+
+    fn update_playtime(&mut self) {
+        let now = Instant::now();
+        if self.focused {
+            let elapsed = now - self.last_seen;
+            self.played += elapsed;
+            self.unsaved_playtime += elapsed;
+        }
+        self.end = Local::now();
+        self.last_seen = now;
+    }
+
+    fn save_or_log(&mut self) {
+        // Screenshots are spooled locally meanwhile, the session is written once it's back.
+        if !spool::is_reachable(&self.target_folder) {
+            debug!(session = %self.id, "Target folder is unreachable, not saving session yet");
+            self.unsaved = true;
+            return;
+        }
+        self.unsaved = false;
+        if let Err(e) = self.save() {
+            error!("Could not save session {:?}: {e:?}", self.path());
+        }
+        let result = Index::open(&self.target_folder).and_then(|index| {
+            index.record_session(self)?;
+            index.add_playtime(&self.game, Local::now().date_naive(), self.unsaved_playtime)
+        });
+        match result {
+            Ok(()) => self.unsaved_playtime = Duration::ZERO,
+            Err(e) => warn!("Could not update index: {e:?}"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SessionTracker {
+    current: Option<Session>,
+    /// Sessions that ended while the target folder was unreachable.
+    ended: Vec<Session>,
+}
+
+fn on_session_start(config: &Config, session: &Session) {
+    if config.notifications {
+        let text = format!(
+            "Now journaling: {} \u{2014} screenshots every {}",
+            session.game,
+            format_interval(config.screenshot_delay)
+        );
+        if let Err(e) = notification::show_toast("autogamejournal", &text) {
+            warn!("Could not show notification: {e:?}");
+        }
+    }
+    if let Some(command) = &config.on_session_start {
+        let values = session.hook_values();
+        hooks::run(
+            "on_session_start",
+            command,
+            &values
+                .each_ref()
+                .map(|(name, value)| (*name, value.as_str())),
+        );
+    }
+}
+
+fn on_session_end(config: &Config, session: &Session) {
+    encoder::flush();
+    if config.upload.is_some() {
+        upload::enqueue(&session.path());
+    }
+    if config.daily_journal {
+        if let Err(e) =
+            journal::write_daily_journal(&session.target_folder, session.start.date_naive())
+        {
+            warn!("Could not write daily journal: {e:?}");
+        }
+    }
+    let extras = session.screenshot_count > 0 && !config.on_battery.skips_extras();
+    if config.animation.on_session_end && extras {
+        animation::make_animation_in_background(
+            config.clone(),
+            session.game.clone(),
+            session.id.clone(),
+        );
+    }
+    if config.contact_sheet.on_session_end && extras {
+        contact_sheet::make_contact_sheet_in_background(
+            config.clone(),
+            session.game.clone(),
+            session.id.clone(),
+        );
+    }
+    if config.timelapse.on_session_end && extras {
+        timelapse::make_timelapse_in_background(
+            config.clone(),
+            session.game.clone(),
+            session.id.clone(),
+        );
+    }
+    if let Some(command) = &config.on_session_end {
+        let values = session.hook_values();
+        hooks::run(
+            "on_session_end",
+            command,
+            &values
+                .each_ref()
+                .map(|(name, value)| (*name, value.as_str())),
+        );
+    }
+}
+
+impl SessionTracker {
+    pub fn focused(&mut self, window: &GameWindow, config: &Config) -> bool {
+        if self.current.as_ref().is_some_and(|s| s.game != window.name) {
+            self.end(config);
+        }
+        let started = self.current.is_none();
+        let session = self.current.get_or_insert_with(|| {
+            let mut session = Session::new(window, &config.target_folder);
+            info!("Started session {} for {}", session.id, session.game);
+            session.save_or_log();
+            on_session_start(config, &session);
+            session
+        });
+        session.update_playtime();
+        session.focused = true;
+        started
+    }
+
+    pub fn unfocused(&mut self) {
+        if let Some(session) = self.current.as_mut().filter(|s| s.focused) {
+            session.update_playtime();
+            session.focused = false;
+        }
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.current.as_ref().is_some_and(|s| s.focused)
+    }
+
+    pub fn timed_out(&self, config: &Config) -> bool {
+        let timeout = Duration::from_secs(config.session_timeout);
+        self.current
+            .as_ref()
+            .is_some_and(|s| s.last_seen.elapsed() > timeout)
+    }
+
+    pub fn check_timeout(&mut self, config: &Config) {
+        if self.timed_out(config) {
+            self.end(config);
+        }
+    }
+
+    pub fn current(&self) -> Option<&Session> {
+        self.current.as_ref()
+    }
+
+    pub fn current_id(&self) -> Option<&str> {
+        self.current.as_ref().map(|s| s.id.as_str())
+    }
+
+    pub fn end(&mut self, config: &Config) {
+        if let Some(mut session) = self.current.take() {
+            session.save_or_log();
+            info!("Ended session {} for {}", session.id, session.game);
+            if session.unsaved {
+                self.ended.push(session);
+            } else {
+                on_session_end(config, &session);
+            }
+        }
+    }
+
+    /// Writes sessions that changed while the target folder was unreachable, once it's back.
+    pub fn save_unsaved(&mut self, config: &Config) {
+        if let Some(session) = self.current.as_mut().filter(|s| s.unsaved) {
+            session.save_or_log();
+        }
+        if self.ended.is_empty() || !spool::is_reachable(&config.target_folder) {
+            return;
+        }
+        for mut session in std::mem::take(&mut self.ended) {
+            session.save_or_log();
+            if session.unsaved {
+                self.ended.push(session);
+            } else {
+                on_session_end(config, &session);
+            }
+        }
+    }
+
+    pub fn record_screenshot(&mut self, path: &Path) {
+        if let Some(session) = &mut self.current {
+            session.screenshot_count += 1;
+            let relative = path.strip_prefix(session.dir()).unwrap_or(path);
+            session.screenshots.push(relative.to_owned());
+            session.save_or_log();
+        }
+    }
+}
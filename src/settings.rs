@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, DocumentMut, Item};
+use winsafe::{co, gui, prelude::*};
+
+use crate::{
+    config::{read_document, write_document},
+    widgets::{self, button, close, edit, pick_folder, show_error},
+};
+
+const FORMATS: [&str; 3] = ["jpeg", "png", "webp"];
+const LABEL_WIDTH: u32 = 130;
+const FIELD_X: i32 = 150;
+const FIELD_WIDTH: u32 = 300;
+
+#[derive(Clone)]
+struct SettingsWindow {
+    window: gui::WindowMain,
+    target_folder: gui::Edit,
+    browse: gui::Button,
+    delay: gui::Edit,
+    format: gui::ComboBox,
+    quality: gui::Edit,
+    no_capture: gui::Edit,
+    save: gui::Button,
+    cancel: gui::Button,
+    config_path: PathBuf,
+}
+
+fn label(window: &gui::WindowMain, text: &str, y: i32) {
+    let _ = widgets::label(window, text, (10, y + 3), LABEL_WIDTH);
+}
+
+impl SettingsWindow {
+    fn new(config_path: &Path, document: &DocumentMut) -> Self {
+        let window = gui::WindowMain::new(gui::WindowMainOpts {
+            title: "autogamejournal settings".to_owned(),
+            size: (570, 330),
+            ..Default::default()
+        });
+
+        let string = |key: &str, default: &str| {
+            document
+                .get(key)
+                .and_then(Item::as_str)
+                .unwrap_or(default)
+                .to_owned()
+        };
+        let integer = |key: &str, default: i64| {
+            document
+                .get(key)
+                .and_then(Item::as_integer)
+                .unwrap_or(default)
+        };
+        let periods: Vec<&str> = document
+            .get("schedule")
+            .and_then(|schedule| schedule.get("no_capture"))
+            .and_then(Item::as_array)
+            .map(|periods| periods.iter().filter_map(|p| p.as_str()).collect())
+            .unwrap_or_default();
+        let format = string("format", "jpeg");
+
+        label(&window, "Journal folder", 10);
+        let target_folder = edit(
+            &window,
+            &string("target_folder", ""),
+            (FIELD_X, 10),
+            FIELD_WIDTH,
+        );
+        let browse = button(&window, "Browse...", (FIELD_X + FIELD_WIDTH as i32 + 10, 9));
+        label(&window, "Seconds between shots", 45);
+        let delay = edit(
+            &window,
+            &integer("screenshot_delay", 300).to_string(),
+            (FIELD_X, 45),
+            80,
+        );
+        label(&window, "Image format", 80);
+        let format = gui::ComboBox::new(
+            &window,
+            gui::ComboBoxOpts {
+                position: (FIELD_X, 80),
+                width: 80,
+                items: FORMATS.iter().map(|f| f.to_string()).collect(),
+                selected_item: FORMATS.iter().position(|&f| f == format).map(|i| i as u32),
+                ..Default::default()
+            },
+        );
+        label(&window, "JPEG quality", 115);
+        let quality = edit(
+            &window,
+            &integer("quality", 90).to_string(),
+            (FIELD_X, 115),
+            80,
+        );
+        label(&window, "No captures during", 150);
+        let no_capture = gui::Edit::new(
+            &window,
+            gui::EditOpts {
+                text: periods.join("\r\n"),
+                position: (FIELD_X, 150),
+                width: FIELD_WIDTH,
+                height: 100,
+                edit_style: co::ES::MULTILINE | co::ES::WANTRETURN | co::ES::AUTOVSCROLL,
+                window_style: co::WS::CHILD | co::WS::VISIBLE | co::WS::TABSTOP | co::WS::VSCROLL,
+                ..Default::default()
+            },
+        );
+        let _ = widgets::label(
+            &window,
+            "One period per line, like 23:00-07:00 or sat,sun 01:00-09:00",
+            (FIELD_X, 255),
+            FIELD_WIDTH + 100,
+        );
+        let save = button(&window, "Save", (370, 290));
+        let cancel = button(&window, "Cancel", (470, 290));
+
+        let settings = Self {
+            window,
+            target_folder,
+            browse,
+            delay,
+            format,
+            quality,
+            no_capture,
+            save,
+            cancel,
+            config_path: config_path.to_owned(),
+        };
+        settings.events();
+        settings
+    }
+
+    fn events(&self) {
+        let settings = self.clone();
+        self.window.on().wm_create(move |_| {
+            settings.update_quality();
+            Ok(0)
+        });
+
+        let settings = self.clone();
+        self.format.on().cbn_sel_change(move || {
+            settings.update_quality();
+            Ok(())
+        });
+
+        let settings = self.clone();
+        self.browse.on().bn_clicked(move || {
+            let hwnd = settings.window.hwnd();
+            match pick_folder(hwnd, "Choose the journal folder") {
+                Ok(Some(folder)) => settings.target_folder.set_text(&folder),
+                Ok(None) => {}
+                Err(e) => show_error(hwnd, &e),
+            }
+            Ok(())
+        });
+
+        let settings = self.clone();
+        self.save.on().bn_clicked(move || {
+            match settings.save() {
+                Ok(()) => close(&settings.window)?,
+                Err(e) => show_error(settings.window.hwnd(), &e),
+            }
+            Ok(())
+        });
+
+        let settings = self.clone();
+        self.cancel.on().bn_clicked(move || close(&settings.window));
+    }
+
+    fn update_quality(&self) {
+        let jpeg = self.format.items().selected_text().as_deref() == Some("jpeg");
+        self.quality.hwnd().EnableWindow(jpeg);
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut document = read_document(&self.config_path)?;
+        let target_folder = self.target_folder.text();
+        if target_folder.trim().is_empty() {
+            return Err(anyhow!("Choose a journal folder"));
+        }
+        document["target_folder"] = value(target_folder.trim());
+        let delay: i64 = self
+            .delay
+            .text()
+            .trim()
+            .parse()
+            .context("Seconds between shots must be a number")?;
+        document["screenshot_delay"] = value(delay);
+        let format = self.format.items().selected_text();
+        if let Some(format) = &format {
+            document["format"] = value(format);
+        }
+        // Quality is rejected with lossless formats, so it's left out for them.
+        if format.as_deref().is_none_or(|format| format == "jpeg") {
+            let quality: i64 = self
+                .quality
+                .text()
+                .trim()
+                .parse()
+                .context("JPEG quality must be a number")?;
+            document["quality"] = value(quality);
+        } else {
+            document.remove("quality");
+        }
+
+        let periods: Array = self
+            .no_capture
+            .iter_lines()
+            .map(|line| line.trim().to_owned())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if !periods.is_empty() {
+            document["schedule"]["no_capture"] = value(periods);
+        } else if let Some(schedule) = document
+            .get_mut("schedule")
+            .and_then(Item::as_table_like_mut)
+        {
+            schedule.remove("no_capture");
+        }
+        write_document(&self.config_path, &document)
+    }
+}
+
+pub fn run(config_path: &Path) -> Result<()> {
+    let document = read_document(config_path)?;
+    widgets::run(&SettingsWindow::new(config_path, &document).window)
+}
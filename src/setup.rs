@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use winsafe::{self as w, co, gui, prelude::*};
+
+use crate::{
+    config::{default_target_folder, write_initial_config},
+    widgets::{self, button, close, edit, pick_folder, show_error},
+    MatchMode,
+};
+
+const INTERVALS: [(&str, u64); 5] = [
+    ("Every minute", 60),
+    ("Every 2 minutes", 120),
+    ("Every 5 minutes", 300),
+    ("Every 10 minutes", 600),
+    ("Every 30 minutes", 1800),
+];
+const DEFAULT_INTERVAL: u32 = 2;
+const PAGES: [(&str, &str); 3] = [
+    (
+        "Where should screenshots be saved?",
+        "Each game gets its own folder in there, next to a daily journal.",
+    ),
+    (
+        "How often should a screenshot be taken while you play?",
+        "This can be changed later from Settings... in the tray menu.",
+    ),
+    (
+        "Which windows should be captured?",
+        "Games can be added or ignored later from Edit rules... in the tray menu.",
+    ),
+];
+
+#[derive(Clone)]
+struct SetupWindow {
+    window: gui::WindowMain,
+    heading: gui::Label,
+    hint: gui::Label,
+    target_folder: gui::Edit,
+    browse: gui::Button,
+    interval: gui::ComboBox,
+    mode: gui::RadioGroup,
+    back: gui::Button,
+    next: gui::Button,
+    cancel: gui::Button,
+    page: Rc<Cell<usize>>,
+    config_path: PathBuf,
+}
+
+impl SetupWindow {
+    fn new(config_path: &Path) -> Self {
+        let window = gui::WindowMain::new(gui::WindowMainOpts {
+            title: "Set up autogamejournal".to_owned(),
+            size: (520, 220),
+            ..Default::default()
+        });
+
+        let heading = widgets::label(&window, "", (10, 15), 500);
+        let target_folder = edit(
+            &window,
+            &default_target_folder().to_string_lossy(),
+            (10, 50),
+            380,
+        );
+        let browse = button(&window, "Browse...", (400, 49));
+        let interval = gui::ComboBox::new(
+            &window,
+            gui::ComboBoxOpts {
+                position: (10, 50),
+                width: 200,
+                items: INTERVALS.iter().map(|(text, _)| text.to_string()).collect(),
+                selected_item: Some(DEFAULT_INTERVAL),
+                ..Default::default()
+            },
+        );
+        let mode = gui::RadioGroup::new(
+            &window,
+            &[
+                gui::RadioButtonOpts {
+                    text: "Every fullscreen game, apart from ignored apps".to_owned(),
+                    position: (10, 50),
+                    selected: true,
+                    ..Default::default()
+                },
+                gui::RadioButtonOpts {
+                    text: "Only the games I add to the rules".to_owned(),
+                    position: (10, 80),
+                    ..Default::default()
+                },
+            ],
+        );
+        let hint = widgets::label(&window, "", (10, 120), 500);
+        let back = button(&window, "< Back", (220, 180));
+        let next = button(&window, "Next >", (320, 180));
+        let cancel = button(&window, "Cancel", (420, 180));
+
+        let setup = Self {
+            window,
+            heading,
+            hint,
+            target_folder,
+            browse,
+            interval,
+            mode,
+            back,
+            next,
+            cancel,
+            page: Default::default(),
+            config_path: config_path.to_owned(),
+        };
+        setup.events();
+        setup
+    }
+
+    fn events(&self) {
+        let setup = self.clone();
+        self.window.on().wm_create(move |_| {
+            setup.show_page(0);
+            Ok(0)
+        });
+
+        let setup = self.clone();
+        self.browse.on().bn_clicked(move || {
+            let hwnd = setup.window.hwnd();
+            match pick_folder(hwnd, "Choose the journal folder") {
+                Ok(Some(folder)) => setup.target_folder.set_text(&folder),
+                Ok(None) => {}
+                Err(e) => show_error(hwnd, &e),
+            }
+            Ok(())
+        });
+
+        let setup = self.clone();
+        self.back.on().bn_clicked(move || {
+            setup.show_page(setup.page.get().saturating_sub(1));
+            Ok(())
+        });
+
+        let setup = self.clone();
+        self.next.on().bn_clicked(move || {
+            let page = setup.page.get();
+            if page == 0 && setup.target_folder.text().trim().is_empty() {
+                show_error(setup.window.hwnd(), &anyhow!("Choose a journal folder"));
+            } else if page + 1 < PAGES.len() {
+                setup.show_page(page + 1);
+            } else if setup.confirm_overwrite() {
+                match setup.finish() {
+                    Ok(()) => close(&setup.window)?,
+                    Err(e) => show_error(setup.window.hwnd(), &e),
+                }
+            }
+            Ok(())
+        });
+
+        let setup = self.clone();
+        self.cancel.on().bn_clicked(move || close(&setup.window));
+    }
+
+    fn show_page(&self, page: usize) {
+        self.page.set(page);
+        let (heading, hint) = PAGES[page];
+        self.heading.set_text(heading);
+        self.hint.set_text(hint);
+        let show = |hwnd: &w::HWND, on: usize| {
+            hwnd.ShowWindow(if page == on {
+                co::SW::SHOW
+            } else {
+                co::SW::HIDE
+            });
+        };
+        show(self.target_folder.hwnd(), 0);
+        show(self.browse.hwnd(), 0);
+        show(self.interval.hwnd(), 1);
+        for radio in self.mode.iter() {
+            show(radio.hwnd(), 2);
+        }
+        self.back.hwnd().EnableWindow(page > 0);
+        self.next.set_text(if page + 1 == PAGES.len() {
+            "Finish"
+        } else {
+            "Next >"
+        });
+    }
+
+    fn confirm_overwrite(&self) -> bool {
+        if !self.config_path.exists() {
+            return true;
+        }
+        let text = format!(
+            "{} already exists. Replace it with a new config?",
+            self.config_path.display()
+        );
+        let answer = self.window.hwnd().MessageBox(
+            &text,
+            "autogamejournal",
+            co::MB::YESNO | co::MB::ICONWARNING,
+        );
+        matches!(answer, Ok(co::DLGID::YES))
+    }
+
+    fn finish(&self) -> Result<()> {
+        let delay = self
+            .interval
+            .items()
+            .selected_index()
+            .map_or(INTERVALS[DEFAULT_INTERVAL as usize].1, |i| {
+                INTERVALS[i as usize].1
+            });
+        let mode = match self.mode.checked_index() {
+            Some(1) => MatchMode::Whitelist,
+            _ => MatchMode::All,
+        };
+        let target_folder = self.target_folder.text();
+        write_initial_config(
+            &self.config_path,
+            Path::new(target_folder.trim()),
+            delay,
+            mode,
+        )
+    }
+}
+
+pub fn run(config_path: &Path) -> Result<()> {
+    widgets::run(&SetupWindow::new(config_path).window)
+}
@@ -0,0 +1,222 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    imaging, maintenance, platform,
+    rules::{self, GameWindow, RuleTarget},
+    screenshot::{screenshot_saved, ScreenshotInfo},
+    CaptureBackend, Config, State,
+};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+static SPOOLED: Mutex<Vec<(PathBuf, ScreenshotInfo)>> = Mutex::new(Vec::new());
+
+/// The local folder screenshots wait in while `target_folder` is unreachable. Each journal gets
+/// its own, so instances running side by side never pick up each other's screenshots.
+pub fn spool_dir(target_folder: &Path) -> PathBuf {
+    let key = target_folder.to_string_lossy().to_lowercase();
+    let hash: String = Sha256::digest(key.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    platform::local_data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("autogamejournal")
+        .join("spool")
+        .join(hash)
+}
+
+/// What `screenshot_saved` needs to know about a spooled screenshot, kept next to it so a later
+/// run can still index, upload and announce it.
+#[derive(Serialize, Deserialize)]
+struct SpooledRecord {
+    game: String,
+    process: String,
+    title: String,
+    process_path: Option<PathBuf>,
+    hash: u64,
+    time: DateTime<Local>,
+    method: String,
+    session_id: Option<String>,
+    session_file: Option<PathBuf>,
+    session_count: u32,
+}
+
+impl SpooledRecord {
+    fn new(info: &ScreenshotInfo) -> Self {
+        Self {
+            game: info.window.name.clone(),
+            process: info.window.process.clone(),
+            title: info.window.title.clone(),
+            process_path: info.window.process_path.clone(),
+            hash: info.hash,
+            time: info.time,
+            method: info.method.name().to_owned(),
+            session_id: info.session_id.clone(),
+            session_file: info.session_file.clone(),
+            session_count: info.session_count,
+        }
+    }
+
+    /// The rule is looked up again in the current config, the one it was taken with is gone.
+    fn into_info(self, config: &Config, path: PathBuf) -> ScreenshotInfo {
+        let rule = rules::find_rule(
+            &config.rules,
+            &RuleTarget {
+                name: &self.game,
+                path: self.process_path.as_deref(),
+                class: None,
+            },
+        )
+        .cloned()
+        .unwrap_or_default();
+        ScreenshotInfo {
+            config: config.clone(),
+            window: GameWindow {
+                id: 0,
+                name: self.game,
+                process: self.process,
+                title: self.title,
+                process_path: self.process_path,
+                store_game: None,
+                elevated: false,
+                rule,
+            },
+            hash: self.hash,
+            time: self.time,
+            path,
+            method: CaptureBackend::from_name(&self.method).unwrap_or_default(),
+            session_id: self.session_id,
+            session_file: self.session_file,
+            session_count: self.session_count,
+        }
+    }
+}
+
+fn record_path(spooled: &Path) -> PathBuf {
+    let mut path = OsString::from(spooled);
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Whether screenshots can be written to the target folder, without creating anything: an
+/// unmounted share or removed drive would otherwise be recreated on the local disk. A missing
+/// target counts as reachable when the closest folder above it that exists has files in it, an
+/// empty one being what a mount point looks like while nothing is mounted on it.
+pub fn is_reachable(target_folder: &Path) -> bool {
+    if target_folder.is_dir() {
+        return true;
+    }
+    target_folder
+        .ancestors()
+        .skip(1)
+        .filter(|dir| dir.parent().is_some())
+        .find(|dir| dir.is_dir())
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .is_some_and(|mut entries| entries.next().is_some())
+}
+
+pub fn spool_path(target_folder: &Path, path: &Path) -> PathBuf {
+    spool_dir(target_folder).join(path.strip_prefix(target_folder).unwrap_or(path))
+}
+
+pub fn hold(spooled: PathBuf, info: ScreenshotInfo) {
+    warn!(path = ?info.path, "Target folder is unreachable, spooled screenshot locally");
+    let record = serde_json::to_string(&SpooledRecord::new(&info))
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(fs::write(record_path(&spooled), json)?));
+    if let Err(e) = record {
+        warn!(path = ?spooled, "Could not record spooled screenshot: {e:?}");
+    }
+    SPOOLED.lock().unwrap().push((spooled, info));
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to).with_context(|| format!("Copying {from:?} to {to:?}"))?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn move_screenshot(from: &Path, to: &Path) -> Result<()> {
+    move_file(from, to)?;
+    let thumbnail = imaging::thumbnail_path(from);
+    if thumbnail.exists() {
+        move_file(&thumbnail, &imaging::thumbnail_path(to))?;
+    }
+    Ok(())
+}
+
+fn move_spooled(config: &Config) {
+    let target_folder = &config.target_folder;
+    let spooled = std::mem::take(&mut *SPOOLED.lock().unwrap());
+    for (spooled, info) in spooled {
+        match move_screenshot(&spooled, &info.path) {
+            Ok(()) => {
+                let _ = fs::remove_file(record_path(&spooled));
+                screenshot_saved(info);
+            }
+            Err(e) => {
+                warn!(path = ?spooled, "Could not move spooled screenshot: {e:?}");
+                SPOOLED.lock().unwrap().push((spooled, info));
+            }
+        }
+    }
+
+    let dir = spool_dir(target_folder);
+    let mut files = Vec::new();
+    if maintenance::walk_files(&dir, &mut files).is_err() {
+        return;
+    }
+    let held: Vec<_> = SPOOLED
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(p, _)| p.clone())
+        .collect();
+    let screenshots = files
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !held.contains(path) && path.extension().is_none_or(|ext| ext != "json"));
+    for path in screenshots {
+        let target = target_folder.join(path.strip_prefix(&dir).unwrap_or(&path));
+        let record = fs::read_to_string(record_path(&path))
+            .ok()
+            .and_then(|json| serde_json::from_str::<SpooledRecord>(&json).ok());
+        if let Err(e) = move_screenshot(&path, &target) {
+            warn!(?path, "Could not move spooled screenshot: {e:?}");
+            continue;
+        }
+        let _ = fs::remove_file(record_path(&path));
+        match record {
+            Some(record) => screenshot_saved(record.into_info(config, target)),
+            None => info!(path = ?target, "Moved screenshot spooled by a previous run"),
+        }
+    }
+}
+
+pub fn spool_thread(state: Arc<State>) -> ! {
+    loop {
+        let config = state.config.read().unwrap().clone();
+        if spool_dir(&config.target_folder).exists() && is_reachable(&config.target_folder) {
+            move_spooled(&config);
+        }
+        std::thread::sleep(RETRY_INTERVAL);
+    }
+}
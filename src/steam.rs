@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+#[cfg(windows)]
+use winsafe::{prelude::*, RegistryValue, HKEY};
+
+use crate::library::{self, InstalledGame, Store};
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn quoted_strings(line: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('"') {
+        let body = &rest[start + 1..];
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in body.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = Some(i);
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        let Some(end) = end else { break };
+        strings.push(unescape(&body[..end]));
+        rest = &body[end + 1..];
+    }
+    strings
+}
+
+fn vdf_pairs(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut strings = quoted_strings(line).into_iter();
+            Some((strings.next()?, strings.next()?))
+        })
+        .collect()
+}
+
+fn read_manifest(steamapps: &Path, path: &Path) -> Option<InstalledGame> {
+    let text = fs::read_to_string(path).ok()?;
+    let pairs = vdf_pairs(&text);
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    };
+    Some(InstalledGame {
+        store: Store::Steam,
+        id: value("appid")?,
+        name: value("name")?,
+        install_dir: steamapps.join("common").join(value("installdir")?),
+    })
+}
+
+fn manifests(steamapps: &Path) -> Vec<InstalledGame> {
+    let Ok(entries) = fs::read_dir(steamapps) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("appmanifest_") && name.ends_with(".acf"))
+        })
+        .filter_map(|path| read_manifest(steamapps, &path))
+        .collect()
+}
+
+#[cfg(windows)]
+fn steam_path() -> Option<PathBuf> {
+    match HKEY::CURRENT_USER.RegGetValue(Some("Software\\Valve\\Steam"), Some("SteamPath")) {
+        Ok(RegistryValue::Sz(path)) => Some(PathBuf::from(path)),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn steam_path() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    [".steam/steam", ".local/share/Steam"]
+        .into_iter()
+        .map(|dir| home.join(dir))
+        .find(|dir| dir.join("steamapps").is_dir())
+}
+
+#[cfg(target_os = "macos")]
+fn steam_path() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(home.join("Library/Application Support/Steam")).filter(|dir| dir.is_dir())
+}
+
+fn library_folders() -> Vec<PathBuf> {
+    let Some(steam) = steam_path() else {
+        return Vec::new();
+    };
+    let mut folders = vec![steam.join("steamapps")];
+    if let Ok(text) = fs::read_to_string(steam.join("steamapps").join("libraryfolders.vdf")) {
+        for (key, value) in vdf_pairs(&text) {
+            let folder = PathBuf::from(value).join("steamapps");
+            if key == "path" && !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+    }
+    folders
+}
+
+pub fn installed_games() -> Vec<InstalledGame> {
+    library_folders()
+        .iter()
+        .flat_map(|folder| manifests(folder))
+        .collect()
+}
+
+pub fn find_near(exe: &Path) -> Option<InstalledGame> {
+    let steamapps = exe.ancestors().find(|dir| {
+        dir.file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case("steamapps"))
+    })?;
+    library::find_installed(&manifests(steamapps), exe)
+}
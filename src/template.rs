@@ -0,0 +1,168 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+use std::{
+    fmt::Write,
+    path::{Component, PathBuf},
+};
+
+pub const DEFAULT_DATETIME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFolders {
+    #[default]
+    None,
+    Month,
+    Day,
+}
+
+impl DateFolders {
+    pub fn template(self) -> &'static str {
+        match self {
+            DateFolders::None => "{game}/{datetime}",
+            DateFolders::Month => "{game}/{date:%Y-%m}/{datetime}",
+            DateFolders::Day => "{game}/{date}/{datetime}",
+        }
+    }
+}
+
+pub struct FileNameContext<'a> {
+    pub game: &'a str,
+    pub time: DateTime<Local>,
+    pub session: Option<&'a str>,
+    pub session_index: u32,
+    pub sequence: u32,
+    pub utc: bool,
+    pub datetime_format: &'a str,
+}
+
+impl FileNameContext<'_> {
+    pub fn example() -> Self {
+        Self {
+            game: "game",
+            time: Local::now(),
+            session: Some("session"),
+            session_index: 1,
+            sequence: 1,
+            utc: false,
+            datetime_format: DEFAULT_DATETIME_FORMAT,
+        }
+    }
+}
+
+pub fn format_time(time: DateTime<Local>, format: &str, utc: bool) -> Result<String> {
+    let mut text = String::new();
+    let written = if utc {
+        write!(text, "{}", time.with_timezone(&Utc).format(format))
+    } else {
+        write!(text, "{}", time.format(format))
+    };
+    written.with_context(|| format!("Invalid time format {format:?}"))?;
+    Ok(text)
+}
+
+fn render_placeholder(placeholder: &str, context: &FileNameContext) -> Result<String> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (placeholder, None),
+    };
+    let time = |default: &str| format_time(context.time, format.unwrap_or(default), context.utc);
+    let number = |value: u32| match format {
+        Some(width) => width
+            .parse::<usize>()
+            .map(|width| format!("{value:0width$}"))
+            .with_context(|| format!("Invalid width {width:?} for {{{name}}}")),
+        None => Ok(value.to_string()),
+    };
+    match name {
+        "game" => Ok(context.game.to_owned()),
+        "date" => time("%Y-%m-%d"),
+        "time" => time("%H-%M-%S"),
+        "datetime" => time(context.datetime_format),
+        "session" => Ok(context.session.unwrap_or("no-session").to_owned()),
+        "session_index" => number(context.session_index),
+        "sequence" => number(context.sequence),
+        _ => bail!("Unknown placeholder {{{name}}}"),
+    }
+}
+
+pub fn uses_placeholder(template: &str, name: &str) -> bool {
+    template.contains(&format!("{{{name}}}")) || template.contains(&format!("{{{name}:"))
+}
+
+pub fn render(template: &str, context: &FileNameContext, extension: &str) -> Result<PathBuf> {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text += &rest[..start];
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed placeholder in {template:?}"))?;
+        text += &render_placeholder(&rest[start + 1..start + end], context)?;
+        rest = &rest[start + end + 1..];
+    }
+    text += rest;
+    if text.trim_matches(['/', '\\']).is_empty() {
+        bail!("Template {template:?} produces an empty file name");
+    }
+    let mut path = PathBuf::from(text);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("Template {template:?} must produce a relative path inside the target folder");
+    }
+    let has_image_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "jpg" | "jpeg" | "png" | "webp"));
+    if has_image_extension {
+        path.set_extension(extension);
+    } else {
+        path.as_mut_os_string().push(format!(".{extension}"));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context() -> FileNameContext<'static> {
+        FileNameContext {
+            time: Local.with_ymd_and_hms(2024, 3, 9, 18, 5, 7).unwrap(),
+            sequence: 7,
+            ..FileNameContext::example()
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let path = render(
+            "{game}/{date:%Y/%m}/{datetime}_{sequence:3}",
+            &context(),
+            "jpg",
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("game/2024/03/2024-03-09_18-05-07_007.jpg")
+        );
+    }
+
+    #[test]
+    fn replaces_image_extension() {
+        let path = render("{game}/{session}.png", &context(), "webp").unwrap();
+        assert_eq!(path, PathBuf::from("game/session.webp"));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(render("{game", &context(), "jpg").is_err());
+        assert!(render("{unknown}", &context(), "jpg").is_err());
+        assert!(render("../{game}", &context(), "jpg").is_err());
+        assert!(render("/", &context(), "jpg").is_err());
+    }
+}
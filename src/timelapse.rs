@@ -0,0 +1,122 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use crate::{hooks, index::Index, notification, Config};
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimelapseConfig {
+    pub ffmpeg: PathBuf,
+    pub fps: u32,
+    pub max_dimension: u32,
+    pub on_session_end: bool,
+}
+
+impl Default for TimelapseConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            fps: 10,
+            max_dimension: 1920,
+            on_session_end: false,
+        }
+    }
+}
+
+impl TimelapseConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.fps == 0 {
+            bail!("timelapse.fps must be at least 1");
+        }
+        if self.max_dimension < 2 {
+            bail!("timelapse.max_dimension must be at least 2 pixels");
+        }
+        Ok(())
+    }
+}
+
+pub fn output_path(target_folder: &Path, game: &str, session_id: &str, extension: &str) -> PathBuf {
+    target_folder
+        .join(game)
+        .join(format!("{session_id}.{extension}"))
+}
+
+fn concat_list(frames: &[PathBuf], fps: u32) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    let duration = 1.0 / fps as f64;
+    for frame in frames.iter().chain(frames.last()) {
+        let path = frame
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace('\'', "'\\''");
+        let _ = writeln!(list, "file '{path}'\nduration {duration}");
+    }
+    list
+}
+
+pub fn make_timelapse(config: &Config, game: &str, session_id: &str) -> Result<PathBuf> {
+    let settings = &config.timelapse;
+    let frames = Index::open(&config.target_folder)?.session_screenshot_files(game, session_id)?;
+    if frames.is_empty() {
+        bail!("Session {session_id} of {game} has no screenshots");
+    }
+    let output = output_path(&config.target_folder, game, session_id, "timelapse.mp4");
+    let list_path = output.with_extension("ffconcat");
+    fs::write(&list_path, concat_list(&frames, settings.fps))?;
+
+    let size = settings.max_dimension;
+    let filter = format!(
+        "scale={size}:{size}:force_original_aspect_ratio=decrease,pad=ceil(iw/2)*2:ceil(ih/2)*2,format=yuv420p"
+    );
+    let result = hooks::hidden_command(&settings.ffmpeg)
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "concat",
+            "-safe",
+            "0",
+            "-i",
+        ])
+        .arg(&list_path)
+        .args([
+            "-vf",
+            &filter,
+            "-r",
+            &settings.fps.to_string(),
+            "-c:v",
+            "libx264",
+        ])
+        .args(["-movflags", "+faststart"])
+        .arg(&output)
+        .output();
+    let _ = fs::remove_file(&list_path);
+    let output_status = result.with_context(|| format!("Running {:?}", settings.ffmpeg))?;
+    if !output_status.status.success() {
+        bail!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output_status.stderr).trim()
+        );
+    }
+    info!(%game, session = session_id, path = ?output, "Created timelapse");
+    Ok(output)
+}
+
+pub fn make_timelapse_in_background(config: Config, game: String, session_id: String) {
+    std::thread::spawn(move || match make_timelapse(&config, &game, &session_id) {
+        Ok(path) => {
+            if config.notifications {
+                let text = format!("Timelapse of {game} saved to {}", path.display());
+                let _ = notification::show_toast("autogamejournal", &text);
+            }
+        }
+        Err(e) => warn!(%game, session = %session_id, "Could not create timelapse: {e:?}"),
+    });
+}
@@ -0,0 +1,690 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tao::{
+    event::{Event, StartCause},
+    event_loop::{ControlFlow, EventLoopBuilder},
+};
+use tracing::{debug, error, info, warn};
+use tray_icon::{
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, Submenu},
+    ClickType, Icon, TrayIconBuilder, TrayIconEvent,
+};
+
+use crate::{
+    animation, autostart, contact_sheet, edit_file, elevation, exe_dir, find_rule, format_duration,
+    index, learn, library, open_file, open_folder, pause_reason, platform, read_document, recent,
+    reload_config, rule_tables, snooze, snoozed_until, timelapse, tomorrow, write_document,
+    Command, RuleEntry, RuleTarget, State, TrayAction,
+};
+
+const DEFAULT_ICON: &[u8] = include_bytes!("../Icon.png");
+
+fn latest_log(log_dir: &Path) -> Result<PathBuf> {
+    std::fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("autogamejournal")
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+        .context("No log file written yet")
+}
+
+fn register_hotkey(manager: &GlobalHotKeyManager, text: &str) -> Result<HotKey> {
+    let hotkey: HotKey = text.parse()?;
+    manager.register(hotkey)?;
+    Ok(hotkey)
+}
+
+fn load_icon() -> image::RgbaImage {
+    let overrides = [
+        Some(PathBuf::from("Icon.png")),
+        exe_dir().map(|d| d.join("Icon.png")),
+    ];
+    for path in overrides.into_iter().flatten() {
+        if !path.exists() {
+            continue;
+        }
+        match image::open(&path) {
+            Ok(image) => return image.into_rgba8(),
+            Err(e) => warn!(?path, "Could not load icon override: {e:?}"),
+        }
+    }
+    image::load_from_memory(DEFAULT_ICON)
+        .expect("Embedded icon is a valid image")
+        .into_rgba8()
+}
+
+/// The rule a new one for `app` would clash with: one naming it, or one ignoring it. A rule
+/// only matching it by pattern doesn't count, a rule naming it takes precedence over that.
+fn existing_rule(state: &State, app: &str) -> Option<RuleEntry> {
+    let config = state.config.read().unwrap();
+    find_rule(&config.rules, &RuleTarget::name(app))
+        .filter(|rule| rule.ignore || rule.matches_name(app))
+        .cloned()
+}
+
+/// Appends a `[[rules]]` table naming `app` with `fields` to the config, checking the result
+/// is valid before writing it, then reloads it.
+fn add_rule(
+    path: &Path,
+    state: &State,
+    app: &str,
+    fields: impl IntoIterator<Item = (&'static str, toml_edit::Item)>,
+) -> Result<()> {
+    let mut document = read_document(path)?;
+    let mut rule = toml_edit::Table::new();
+    rule.insert("name", toml_edit::value(app));
+    for (key, value) in fields {
+        rule.insert(key, value);
+    }
+    rule_tables(&mut document)?.push(rule);
+    write_document(path, &document)?;
+    learn::forget(app);
+    reload_config(path, state);
+    Ok(())
+}
+
+fn add_ignore_rule(path: &Path, state: &State, app: &str) -> Result<()> {
+    if let Some(rule) = existing_rule(state, app) {
+        if rule.ignore {
+            bail!("{app} is already ignored");
+        }
+        bail!("{app} already has a rule in the config, set `ignore = true` on it instead");
+    }
+    add_rule(path, state, app, [("ignore", toml_edit::value(true))])?;
+    info!("Added ignore rule for {app}");
+    Ok(())
+}
+
+/// Adds a rule always capturing `app`, saving it as `name`.
+fn add_journal_rule(path: &Path, state: &State, app: &str, name: &str) -> Result<()> {
+    // A rule naming the app takes precedence over one ignoring it by pattern, so only that
+    // kind of rule is in the way.
+    if existing_rule(state, app).is_some_and(|rule| rule.matches_name(app)) {
+        bail!("{app} already has a rule in the config");
+    }
+    let override_name = (name != app).then(|| ("override_name", toml_edit::value(name)));
+    add_rule(path, state, app, override_name)?;
+    info!("Added rule for {app}");
+    Ok(())
+}
+
+/// Turns the rule at `index` on or off in the config file, then reloads it.
+fn set_rule_enabled(path: &Path, state: &State, index: usize, enabled: bool) -> Result<()> {
+    let mut document = read_document(path)?;
+    let rule = rule_tables(&mut document)?
+        .get_mut(index)
+        .context("The rule is no longer in the config")?;
+    if enabled {
+        rule.remove("enabled");
+    } else {
+        rule.insert("enabled", toml_edit::value(false));
+    }
+    write_document(path, &document)?;
+    info!(index, enabled, "Changed rule");
+    reload_config(path, state);
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TrayState {
+    Idle,
+    Journaling,
+    Paused,
+    Error,
+}
+
+impl TrayState {
+    fn current(state: &State) -> Self {
+        if pause_reason(state).is_some() {
+            return TrayState::Paused;
+        }
+        let status = state.status.lock().unwrap();
+        if let Some((_, false)) = status.last_capture {
+            TrayState::Error
+        } else if status.game.is_some() {
+            TrayState::Journaling
+        } else {
+            TrayState::Idle
+        }
+    }
+
+    fn badge_color(self) -> Option<image::Rgba<u8>> {
+        match self {
+            TrayState::Idle => None,
+            TrayState::Journaling => Some(image::Rgba([46, 160, 67, 255])),
+            TrayState::Paused => Some(image::Rgba([212, 160, 23, 255])),
+            TrayState::Error => Some(image::Rgba([207, 34, 46, 255])),
+        }
+    }
+}
+
+/// Draws a coloured dot in the bottom right corner of the icon for the given state.
+fn tray_icon_image(base: &image::RgbaImage, tray_state: TrayState) -> Icon {
+    let mut image = base.clone();
+    if let Some(color) = tray_state.badge_color() {
+        let (w, h) = image.dimensions();
+        let radius = w.min(h) as f32 / 4.;
+        let center = (w as f32 - radius, h as f32 - radius);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let dx = x as f32 + 0.5 - center.0;
+            let dy = y as f32 + 0.5 - center.1;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance <= radius - 1. {
+                *pixel = color;
+            } else if distance <= radius {
+                *pixel = image::Rgba([255, 255, 255, 255]);
+            }
+        }
+    }
+    let (w, h) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), w, h).unwrap()
+}
+
+fn tooltip_text(state: &State) -> String {
+    let mut text = "autogamejournal".to_string();
+    if let Some(reason) = pause_reason(state) {
+        text += &format!(" ({reason})");
+    }
+    if state.dry_run.load(Ordering::Relaxed) {
+        text += " (dry run)";
+    }
+    let mut status = state.status.lock().unwrap();
+    status.roll_over();
+    if let Some(game) = &status.game {
+        text += &format!("\n{game}: {} played", format_duration(status.playtime));
+    }
+    match status.last_capture {
+        Some((time, true)) => text += &format!("\nLast capture at {}", time.format("%H:%M")),
+        Some((time, false)) => {
+            text += &format!("\nLast capture failed at {}", time.format("%H:%M"))
+        }
+        None => {}
+    }
+    text += &format!("\n{} screenshots today", status.screenshots_today);
+    text
+}
+
+fn open_window(config_path: &Path, command: &str) -> Result<()> {
+    let exe = std::env::current_exe().context("Getting executable path")?;
+    std::process::Command::new(exe)
+        .arg("--config")
+        .arg(config_path)
+        .arg(command)
+        .spawn()
+        .with_context(|| format!("Starting the {command} window"))?;
+    Ok(())
+}
+
+/// Runs the action configured for a click on the tray icon.
+fn run_tray_action(action: TrayAction, state: &State, config_path: &Path) {
+    let result = match action {
+        TrayAction::None | TrayAction::Menu => Ok(()),
+        TrayAction::OpenFolder => open_folder(&state.config.read().unwrap().target_folder),
+        TrayAction::Journal => open_window(config_path, "journal"),
+        TrayAction::Settings => open_window(config_path, "settings"),
+        TrayAction::Capture => {
+            let _ = state.commands.send(Command::CaptureLastGame);
+            Ok(())
+        }
+        TrayAction::Pause => {
+            state.paused.fetch_xor(true, Ordering::Relaxed);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        error!("Tray action failed: {e:?}");
+    }
+}
+
+/// Shows the tray icon and runs its event loop on this thread until the app quits.
+pub fn run_tray(
+    state: Arc<State>,
+    config_path: PathBuf,
+    log_dir: PathBuf,
+    screenshot_thread: JoinHandle<()>,
+) -> ! {
+    let mut screenshot_thread = Some(screenshot_thread);
+    let mut tray_icon = None;
+    let mut current_tooltip = String::new();
+    let base_icon = load_icon();
+    let mut current_tray_state = TrayState::Idle;
+    let mut menu_on_left_click = None;
+    let mut pending_click: Option<Instant> = None;
+    let mut double_clicked: Option<Instant> = None;
+    let hotkey_manager = GlobalHotKeyManager::new().unwrap();
+    let mut hotkey = None;
+    let mut hotkey_text = None;
+
+    let quit_menu_item = MenuItem::new("Quit", true, None);
+    let open_menu_item = MenuItem::new("Open", true, None);
+    let capture_menu_item = MenuItem::new("Take screenshot now", true, None);
+    let ignore_menu_item = MenuItem::new("Ignore current app", false, None);
+    let mut ignore_menu_app: Option<String> = None;
+    let settings_menu_item = MenuItem::new("Settings...", true, None);
+    let rules_menu_item = MenuItem::new("Edit rules...", true, None);
+    let edit_config_menu_item = MenuItem::new("Edit config file", true, None);
+    let journal_menu_item = MenuItem::new("Browse journal...", true, None);
+    let last_screenshot_menu_item = MenuItem::new("Open last screenshot", false, None);
+    let recent_menu = Submenu::new("Recent screenshots", false);
+    let mut recent_items: Vec<(MenuItem, PathBuf)> = Vec::new();
+    let mut recent_generation = None;
+    let learn_menu = Submenu::new("New apps", false);
+    let mut learn_items: Vec<(Submenu, [MenuItem; 3], learn::SeenApp)> = Vec::new();
+    let mut learn_generation = None;
+    let rule_toggles_menu = Submenu::new("Rules", false);
+    let mut rule_toggle_items: Vec<CheckMenuItem> = Vec::new();
+    let mut rule_toggles = Vec::new();
+    let reload_menu_item = MenuItem::new("Reload config", true, None);
+    let log_menu_item = MenuItem::new("Show log", true, None);
+    let rescan_menu_item = MenuItem::new("Rescan game libraries", true, None);
+    let timelapse_menu_item = MenuItem::new("Make timelapse of last session", true, None);
+    let animation_menu_item = MenuItem::new("Make GIF of last session", true, None);
+    let contact_sheet_menu_item = MenuItem::new("Make contact sheet of last session", true, None);
+    let pause_menu_item = CheckMenuItem::new("Pause", true, state.args.paused, None);
+    let dry_run_menu_item = CheckMenuItem::new("Dry run", true, state.args.dry_run, None);
+    let snooze_menu = Submenu::new("Snooze", true);
+    let snooze_30_menu_item = MenuItem::new("For 30 minutes", true, None);
+    let snooze_60_menu_item = MenuItem::new("For 1 hour", true, None);
+    let snooze_tomorrow_menu_item = MenuItem::new("Until tomorrow", true, None);
+    let resume_menu_item = MenuItem::new("Resume now", false, None);
+    let mut snoozed = None;
+    let autostart_menu_item = CheckMenuItem::new(
+        if cfg!(windows) {
+            "Start with Windows"
+        } else {
+            "Start on login"
+        },
+        true,
+        autostart::is_enabled(),
+        None,
+    );
+    let elevate_menu_item = MenuItem::new("Restart as administrator", true, None);
+
+    let event_loop = EventLoopBuilder::new().build();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(50));
+
+        if let Event::NewEvents(StartCause::Init) = event {
+            // The journal viewer, settings and rules editor are Windows dialogs.
+            let dialogs = cfg!(windows);
+            let menu = Menu::new();
+            menu.append(&quit_menu_item).unwrap();
+            menu.append(&open_menu_item).unwrap();
+            if dialogs {
+                menu.append(&journal_menu_item).unwrap();
+            }
+            menu.append(&last_screenshot_menu_item).unwrap();
+            menu.append(&recent_menu).unwrap();
+            menu.append(&capture_menu_item).unwrap();
+            menu.append(&ignore_menu_item).unwrap();
+            if dialogs {
+                menu.append(&settings_menu_item).unwrap();
+                menu.append(&rules_menu_item).unwrap();
+            }
+            menu.append(&rule_toggles_menu).unwrap();
+            menu.append(&learn_menu).unwrap();
+            menu.append(&edit_config_menu_item).unwrap();
+            menu.append(&reload_menu_item).unwrap();
+            menu.append(&log_menu_item).unwrap();
+            menu.append(&rescan_menu_item).unwrap();
+            menu.append(&timelapse_menu_item).unwrap();
+            menu.append(&animation_menu_item).unwrap();
+            menu.append(&contact_sheet_menu_item).unwrap();
+            menu.append(&pause_menu_item).unwrap();
+            snooze_menu
+                .append_items(&[
+                    &snooze_30_menu_item,
+                    &snooze_60_menu_item,
+                    &snooze_tomorrow_menu_item,
+                    &resume_menu_item,
+                ])
+                .unwrap();
+            menu.append(&snooze_menu).unwrap();
+            menu.append(&dry_run_menu_item).unwrap();
+            menu.append(&autostart_menu_item).unwrap();
+            if cfg!(windows) && !elevation::is_elevated() {
+                menu.append(&elevate_menu_item).unwrap();
+            }
+
+            tray_icon = Some(
+                TrayIconBuilder::new()
+                    .with_menu(Box::new(menu))
+                    .with_icon(tray_icon_image(&base_icon, current_tray_state))
+                    .with_tooltip("autogamejournal")
+                    .build()
+                    .unwrap(),
+            );
+        }
+
+        let configured_hotkey = state.config.read().unwrap().hotkey.clone();
+        if configured_hotkey != hotkey_text {
+            if let Some(old) = hotkey.take() {
+                let _ = hotkey_manager.unregister(old);
+            }
+            if let Some(text) = &configured_hotkey {
+                match register_hotkey(&hotkey_manager, text) {
+                    Ok(h) => hotkey = Some(h),
+                    Err(e) => error!("Could not register hotkey {text:?}: {e:?}"),
+                }
+            }
+            hotkey_text = configured_hotkey;
+        }
+        if let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
+            if hotkey.is_some_and(|h: HotKey| h.id() == event.id)
+                && event.state == HotKeyState::Pressed
+            {
+                let _ = state.commands.send(Command::CaptureNow);
+            }
+        }
+
+        let tray_config = state.config.read().unwrap().tray.clone();
+        if let Some(tray_icon) = &tray_icon {
+            let menu_on_click = tray_config.click == TrayAction::Menu;
+            if menu_on_left_click != Some(menu_on_click) {
+                tray_icon.set_show_menu_on_left_click(menu_on_click);
+                menu_on_left_click = Some(menu_on_click);
+            }
+        }
+        // A double click also sends the clicks it is made of, so the click action waits
+        // for the double click time and is dropped if the clicks turn out to be a double click.
+        let double_click_time = platform::double_click_time();
+        while let Ok(event) = TrayIconEvent::receiver().try_recv() {
+            let now = Instant::now();
+            match event.click_type {
+                ClickType::Left if tray_config.double_click == TrayAction::None => {
+                    run_tray_action(tray_config.click, &state, &config_path)
+                }
+                ClickType::Left => {
+                    if pending_click.is_none() && double_clicked.is_none_or(|until| until <= now) {
+                        pending_click = Some(now + double_click_time);
+                    }
+                }
+                ClickType::Double => {
+                    pending_click = None;
+                    double_clicked = Some(now + double_click_time);
+                    run_tray_action(tray_config.double_click, &state, &config_path)
+                }
+                ClickType::Right => {}
+            }
+        }
+        if pending_click.is_some_and(|deadline| deadline <= Instant::now()) {
+            pending_click = None;
+            run_tray_action(tray_config.click, &state, &config_path);
+        }
+        if let Ok(event) = MenuEvent::receiver().try_recv() {
+            let restarting = event.id == elevate_menu_item.id()
+                && elevation::restart_elevated()
+                    .map_err(|e| error!("Could not restart as administrator: {e:?}"))
+                    .is_ok();
+            if event.id == quit_menu_item.id() || restarting {
+                let _ = state.commands.send(Command::Shutdown);
+                if let Some(thread) = screenshot_thread.take() {
+                    let _ = thread.join();
+                }
+                *control_flow = ControlFlow::Exit;
+            }
+            if event.id == open_menu_item.id() {
+                let target_path = state.config.read().unwrap().target_folder.clone();
+                if let Err(e) = open_folder(&target_path) {
+                    error!("Error opening folder {target_path:?} {e:?}");
+                }
+            }
+            if event.id == capture_menu_item.id() {
+                let _ = state.commands.send(Command::CaptureLastGame);
+            }
+            if event.id == ignore_menu_item.id() {
+                if let Some(app) = &ignore_menu_app {
+                    if let Err(e) = add_ignore_rule(&config_path, &state, app) {
+                        error!("Could not ignore {app}: {e:?}");
+                    }
+                }
+            }
+            if event.id == settings_menu_item.id() {
+                if let Err(e) = open_window(&config_path, "settings") {
+                    error!("Could not open settings: {e:?}");
+                }
+            }
+            if event.id == journal_menu_item.id() {
+                if let Err(e) = open_window(&config_path, "journal") {
+                    error!("Could not open the journal viewer: {e:?}");
+                }
+            }
+            if event.id == rules_menu_item.id() {
+                if let Err(e) = open_window(&config_path, "rules") {
+                    error!("Could not open the rules editor: {e:?}");
+                }
+            }
+            let toggled_rule = rule_toggle_items
+                .iter()
+                .position(|item| event.id == item.id());
+            if let Some(position) = toggled_rule {
+                let (index, _, enabled) = &rule_toggles[position];
+                if let Err(e) = set_rule_enabled(&config_path, &state, *index, !enabled) {
+                    error!("Could not change rule: {e:?}");
+                    rule_toggle_items[position].set_checked(*enabled);
+                }
+            }
+            for (_, [journal, ignore, later], seen) in &learn_items {
+                let added = if event.id == journal.id() {
+                    add_journal_rule(&config_path, &state, &seen.app, &seen.name)
+                } else if event.id == ignore.id() {
+                    add_ignore_rule(&config_path, &state, &seen.app)
+                } else if event.id == later.id() {
+                    learn::later(&seen.app);
+                    Ok(())
+                } else {
+                    Ok(())
+                };
+                if let Err(e) = added {
+                    error!("Could not add a rule for {}: {e:?}", seen.app);
+                }
+            }
+            if event.id == edit_config_menu_item.id() {
+                if let Err(e) = edit_file(&config_path) {
+                    error!("Could not open {config_path:?}: {e:?}");
+                }
+            }
+            if event.id == reload_menu_item.id() {
+                reload_config(&config_path, &state);
+            }
+            if event.id == log_menu_item.id() {
+                if let Err(e) = latest_log(&log_dir).and_then(|path| open_file(&path)) {
+                    error!("Could not open log: {e:?}");
+                }
+            }
+            let last_screenshot = recent_items
+                .first()
+                .filter(|_| event.id == last_screenshot_menu_item.id());
+            let clicked_screenshot = recent_items.iter().find(|(item, _)| event.id == item.id());
+            if let Some((_, path)) = last_screenshot.or(clicked_screenshot) {
+                if let Err(e) = open_file(path) {
+                    error!("Could not open {path:?}: {e:?}");
+                }
+            }
+            if event.id == rescan_menu_item.id() {
+                std::thread::spawn(library::scan);
+            }
+            let summary_items = [
+                &timelapse_menu_item,
+                &animation_menu_item,
+                &contact_sheet_menu_item,
+            ];
+            if summary_items.iter().any(|item| event.id == item.id()) {
+                let config = state.config.read().unwrap().clone();
+                match index::Index::open(&config.target_folder)
+                    .and_then(|index| index.latest_session(None))
+                {
+                    Ok(Some(session)) if event.id == timelapse_menu_item.id() => {
+                        timelapse::make_timelapse_in_background(config, session.game, session.id)
+                    }
+                    Ok(Some(session)) if event.id == animation_menu_item.id() => {
+                        animation::make_animation_in_background(config, session.game, session.id)
+                    }
+                    Ok(Some(session)) => contact_sheet::make_contact_sheet_in_background(
+                        config,
+                        session.game,
+                        session.id,
+                    ),
+                    Ok(None) => warn!("No session recorded yet"),
+                    Err(e) => error!("Could not find the last session: {e:?}"),
+                }
+            }
+            if event.id == pause_menu_item.id() {
+                state
+                    .paused
+                    .store(pause_menu_item.is_checked(), Ordering::Relaxed);
+            }
+            if event.id == dry_run_menu_item.id() {
+                let dry_run = dry_run_menu_item.is_checked();
+                state.dry_run.store(dry_run, Ordering::Relaxed);
+                info!(dry_run, "Changed dry run");
+            }
+            if event.id == snooze_30_menu_item.id() {
+                snooze(&state, Some(Local::now() + Duration::from_secs(30 * 60)));
+            }
+            if event.id == snooze_60_menu_item.id() {
+                snooze(&state, Some(Local::now() + Duration::from_secs(60 * 60)));
+            }
+            if event.id == snooze_tomorrow_menu_item.id() {
+                snooze(&state, tomorrow());
+            }
+            if event.id == resume_menu_item.id() {
+                snooze(&state, None);
+            }
+            if event.id == autostart_menu_item.id() {
+                if let Err(e) = autostart::set_enabled(autostart_menu_item.is_checked()) {
+                    error!("Could not update autostart: {e:?}");
+                }
+                autostart_menu_item.set_checked(autostart::is_enabled());
+            }
+        }
+
+        let paused = state.paused.load(Ordering::Relaxed);
+        if pause_menu_item.is_checked() != paused {
+            pause_menu_item.set_checked(paused);
+        }
+
+        let until = snoozed_until(&state);
+        if until != snoozed {
+            match until {
+                Some(until) => {
+                    snooze_menu.set_text(format!("Snoozed until {}", until.format("%H:%M")))
+                }
+                None => snooze_menu.set_text("Snooze"),
+            }
+            resume_menu_item.set_enabled(until.is_some());
+            snoozed = until;
+        }
+
+        let generation = recent::generation();
+        if recent_generation != Some(generation) {
+            for (item, _) in recent_items.drain(..) {
+                let _ = recent_menu.remove(&item);
+            }
+            for screenshot in recent::list() {
+                let text = format!(
+                    "{} \u{2013} {}",
+                    screenshot.game.replace('&', "&&"),
+                    screenshot.timestamp.format("%b %-d, %H:%M")
+                );
+                let item = MenuItem::new(text, true, None);
+                let _ = recent_menu.append(&item);
+                recent_items.push((item, screenshot.path));
+            }
+            recent_menu.set_enabled(!recent_items.is_empty());
+            last_screenshot_menu_item.set_enabled(!recent_items.is_empty());
+            recent_generation = Some(generation);
+        }
+
+        // Rules added elsewhere, like the rules editor, also answer a suggestion.
+        let config = state.config.read().unwrap();
+        let generation = (learn::generation(), config.rules.len());
+        if learn_generation != Some(generation) {
+            let suggestions: Vec<_> = learn::suggestions()
+                .into_iter()
+                .filter(|seen| find_rule(&config.rules, &RuleTarget::name(&seen.app)).is_none())
+                .collect();
+            for (submenu, _, _) in learn_items.drain(..) {
+                let _ = learn_menu.remove(&submenu);
+            }
+            for seen in suggestions {
+                let submenu = Submenu::new(seen.app.replace('&', "&&"), true);
+                let items = [
+                    MenuItem::new(
+                        format!("Always journal as {}", seen.name.replace('&', "&&")),
+                        true,
+                        None,
+                    ),
+                    MenuItem::new("Ignore", true, None),
+                    MenuItem::new("Ask me later", true, None),
+                ];
+                let _ = submenu.append_items(&[&items[0], &items[1], &items[2]]);
+                let _ = learn_menu.append(&submenu);
+                learn_items.push((submenu, items, seen));
+            }
+            learn_menu.set_enabled(!learn_items.is_empty());
+            learn_generation = Some(generation);
+        }
+        drop(config);
+
+        // Ignore rules only stop captures, so turning them off from here would be confusing.
+        let toggles: Vec<(usize, String, bool)> = state
+            .config
+            .read()
+            .unwrap()
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| !rule.ignore)
+            .map(|(index, rule)| (index, rule.label(), rule.enabled))
+            .collect();
+        if toggles != rule_toggles {
+            for item in rule_toggle_items.drain(..) {
+                let _ = rule_toggles_menu.remove(&item);
+            }
+            for (_, label, enabled) in &toggles {
+                let item = CheckMenuItem::new(label.replace('&', "&&"), true, *enabled, None);
+                let _ = rule_toggles_menu.append(&item);
+                rule_toggle_items.push(item);
+            }
+            rule_toggles_menu.set_enabled(!toggles.is_empty());
+            rule_toggles = toggles;
+        }
+
+        let app = state.status.lock().unwrap().app.clone();
+        if app != ignore_menu_app {
+            match &app {
+                Some(app) => ignore_menu_item.set_text(format!("Ignore {app}")),
+                None => ignore_menu_item.set_text("Ignore current app"),
+            }
+            ignore_menu_item.set_enabled(app.is_some());
+            ignore_menu_app = app;
+        }
+
+        if let Some(tray_icon) = &tray_icon {
+            let tooltip = tooltip_text(&state);
+            if tooltip != current_tooltip {
+                let _ = tray_icon.set_tooltip(Some(&tooltip));
+                current_tooltip = tooltip;
+            }
+            let tray_state = TrayState::current(&state);
+            if tray_state != current_tray_state {
+                debug!(?tray_state, "Tray state changed");
+                let _ = tray_icon.set_icon(Some(tray_icon_image(&base_icon, tray_state)));
+                current_tray_state = tray_state;
+            }
+        }
+    });
+}
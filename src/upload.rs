@@ -0,0 +1,323 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{webhook, Config, State};
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// Files waiting to be uploaded, also saved to `queue_file` so they survive a restart.
+struct Queue {
+    paths: VecDeque<PathBuf>,
+    queue_file: Option<PathBuf>,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue {
+    paths: VecDeque::new(),
+    queue_file: None,
+});
+static QUEUED: Condvar = Condvar::new();
+
+/// Why an upload failed: a retry might work, or the file will never upload as is.
+enum UploadError {
+    Retry(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl Queue {
+    fn save(&self) {
+        let Some(queue_file) = &self.queue_file else {
+            return;
+        };
+        let saved = if self.paths.is_empty() {
+            fs::remove_file(queue_file).or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e.into()),
+            })
+        } else {
+            serde_json::to_string(&self.paths)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| Ok(fs::write(queue_file, text)?))
+        };
+        if let Err(e) = saved {
+            warn!(?queue_file, "Could not save upload queue: {e:?}");
+        }
+    }
+
+    /// Removes `path` once it is done with, if it is still first in the queue.
+    fn finish(&mut self, path: &Path) {
+        if self.paths.front().is_some_and(|front| front == path) {
+            self.paths.pop_front();
+            self.save();
+        }
+    }
+}
+
+fn default_region() -> String {
+    "us-east-1".to_owned()
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UploadConfig {
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    #[serde(default)]
+    pub prefix: String,
+}
+
+impl UploadConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.endpoint.starts_with("https://") && !self.endpoint.starts_with("http://") {
+            bail!("upload.endpoint must start with https:// or http://");
+        }
+        if self.bucket.is_empty() {
+            bail!("upload.bucket must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Restores the uploads still pending when the app last quit.
+pub fn load(target_folder: &Path) {
+    let queue_file = target_folder.join("upload_queue.json");
+    let pending: VecDeque<PathBuf> = match fs::read_to_string(&queue_file) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            warn!(?queue_file, "Could not read upload queue: {e:?}");
+            VecDeque::new()
+        }),
+        Err(_) => VecDeque::new(),
+    };
+    let mut queue = QUEUE.lock().unwrap();
+    for path in pending {
+        if !queue.paths.contains(&path) {
+            queue.paths.push_back(path);
+        }
+    }
+    queue.queue_file = Some(queue_file);
+    if !queue.paths.is_empty() {
+        info!(count = queue.paths.len(), "Resuming pending uploads");
+        QUEUED.notify_one();
+    }
+}
+
+pub fn enqueue(path: &Path) {
+    let mut queue = QUEUE.lock().unwrap();
+    if !queue.paths.iter().any(|queued| queued == path) {
+        queue.paths.push_back(path.to_owned());
+        queue.save();
+        QUEUED.notify_one();
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut text, byte| {
+        let _ = write!(text, "{byte:02x}");
+        text
+    })
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode_key(key: &str) -> String {
+    key.bytes().fold(String::new(), |mut text, byte| {
+        if byte.is_ascii_alphanumeric() || b"-_.~/".contains(&byte) {
+            text.push(byte as char);
+        } else {
+            let _ = write!(text, "%{byte:02X}");
+        }
+        text
+    })
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("jpg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+fn put_object(
+    config: &UploadConfig,
+    key: &str,
+    body: &[u8],
+    content_type: &str,
+) -> Result<(), UploadError> {
+    let endpoint = config.endpoint.trim_end_matches('/');
+    let host = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let uri = format!("/{}/{}", config.bucket, encode_key(key));
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(&Sha256::digest(body));
+    let canonical_request = format!(
+        "PUT\n{uri}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{SIGNED_HEADERS}\n{payload_hash}"
+    );
+    let scope = format!("{date}/{}/s3/aws4_request", config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut signing_key = hmac_sha256(
+        format!("AWS4{}", config.secret_key).as_bytes(),
+        date.as_bytes(),
+    );
+    for part in [config.region.as_str(), "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part.as_bytes());
+    }
+    let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={signature}",
+        config.access_key
+    );
+
+    ureq::put(&format!("{endpoint}{uri}"))
+        .set("Authorization", &authorization)
+        .set("Content-Type", content_type)
+        .set("x-amz-content-sha256", &payload_hash)
+        .set("x-amz-date", &amz_date)
+        .send_bytes(body)
+        .map_err(|e| {
+            // Client errors, like bad credentials or a missing bucket, fail the same way
+            // every time, apart from timeouts and rate limiting.
+            let permanent = matches!(&e, ureq::Error::Status(code, _)
+                if (400..500).contains(code) && !matches!(code, 408 | 429));
+            let e = webhook::http_error(e).context(format!("Uploading {key}"));
+            if permanent {
+                UploadError::Permanent(e)
+            } else {
+                UploadError::Retry(e)
+            }
+        })?;
+    Ok(())
+}
+
+fn upload_file(
+    config: &UploadConfig,
+    target_folder: &Path,
+    path: &Path,
+) -> Result<(), UploadError> {
+    let relative = path
+        .strip_prefix(target_folder)
+        .with_context(|| format!("{path:?} is outside the target folder"))
+        .map_err(UploadError::Permanent)?;
+    let key = format!(
+        "{}{}",
+        config.prefix,
+        relative.to_string_lossy().replace('\\', "/")
+    );
+    let body = fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => UploadError::Permanent(e.into()),
+        _ => UploadError::Retry(e.into()),
+    })?;
+    put_object(config, &key, &body, content_type(path))?;
+    info!(%key, "Uploaded file");
+    Ok(())
+}
+
+pub fn drain(config: &Config) {
+    let Some(upload) = &config.upload else {
+        return;
+    };
+    let pending = QUEUE.lock().unwrap().paths.len();
+    for _ in 0..pending {
+        let Some(path) = QUEUE.lock().unwrap().paths.front().cloned() else {
+            break;
+        };
+        let retry = match upload_file(upload, &config.target_folder, &path) {
+            Ok(()) => false,
+            Err(UploadError::Permanent(e)) => {
+                warn!(?path, "Upload failed and won't be retried: {e:?}");
+                false
+            }
+            Err(UploadError::Retry(e)) => {
+                warn!(?path, "Upload failed: {e:?}");
+                true
+            }
+        };
+        let mut queue = QUEUE.lock().unwrap();
+        queue.finish(&path);
+        if retry {
+            // At the back, so the rest of the queue gets a try first.
+            queue.paths.push_back(path);
+            queue.save();
+        }
+    }
+}
+
+/// The next file to upload, left in the queue until it is done with.
+fn next_path() -> PathBuf {
+    let mut queue = QUEUE.lock().unwrap();
+    loop {
+        if let Some(path) = queue.paths.front() {
+            return path.clone();
+        }
+        queue = QUEUED.wait(queue).unwrap();
+    }
+}
+
+pub fn upload_thread(state: Arc<State>) -> ! {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let path = next_path();
+        let config = state.config.read().unwrap().clone();
+        let Some(upload) = &config.upload else {
+            let mut queue = QUEUE.lock().unwrap();
+            queue.paths.clear();
+            queue.save();
+            continue;
+        };
+        if !path.exists() {
+            debug!(?path, "Skipping upload of removed file");
+            QUEUE.lock().unwrap().finish(&path);
+            continue;
+        }
+        match upload_file(upload, &config.target_folder, &path) {
+            Ok(()) => backoff = MIN_BACKOFF,
+            Err(UploadError::Permanent(e)) => {
+                error!(?path, "Upload failed and won't be retried: {e:?}");
+            }
+            Err(UploadError::Retry(e)) => {
+                warn!(
+                    ?path,
+                    "Upload failed, retrying in {}s: {e:?}",
+                    backoff.as_secs()
+                );
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        }
+        QUEUE.lock().unwrap().finish(&path);
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::Result;
+use image::{imageops, Rgb, RgbImage};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use tracing::warn;
+use winsafe::{self as w, co, guard::ImageListDestroyGuard, gui, prelude::*};
+
+use crate::{
+    calendar, format_duration, imaging,
+    index::{Index, SessionFilter},
+    open_file,
+    widgets::{self, button, show_error},
+    Config,
+};
+
+const TILE: (u32, u32) = (192, 108);
+
+#[derive(Clone, Default)]
+enum Location {
+    #[default]
+    Games,
+    Sessions(String),
+    Screenshots {
+        game: String,
+        session: String,
+        title: String,
+    },
+}
+
+impl Location {
+    fn breadcrumb(&self) -> String {
+        match self {
+            Location::Games => "Games".to_owned(),
+            Location::Sessions(game) => format!("Games > {game}"),
+            Location::Screenshots { game, title, .. } => format!("Games > {game} > {title}"),
+        }
+    }
+}
+
+/// A tile in the list: its label, the screenshot shown on it and what opening it does.
+struct Entry {
+    label: String,
+    cover: Option<PathBuf>,
+    target: Target,
+}
+
+enum Target {
+    Open(Location),
+    File(PathBuf),
+}
+
+#[derive(Default)]
+struct State {
+    location: Location,
+    /// Locations above the current one, with the tile that was opened in each.
+    parents: Vec<(Location, usize)>,
+    entries: Vec<Entry>,
+    images: Option<ImageListDestroyGuard>,
+}
+
+#[derive(Clone)]
+struct JournalViewer {
+    window: gui::WindowMain,
+    path: gui::Label,
+    back: gui::Button,
+    calendar: gui::Button,
+    list: gui::ListView,
+    index: Rc<Index>,
+    target_folder: PathBuf,
+    state: Rc<RefCell<State>>,
+}
+
+fn entries(index: &Index, location: &Location) -> Result<Vec<Entry>> {
+    Ok(match location {
+        Location::Games => index
+            .game_stats()?
+            .into_iter()
+            .map(|stats| {
+                let cover = index
+                    .latest_session(Some(&stats.game))?
+                    .map(|session| index.session_screenshot_files(&stats.game, &session.id))
+                    .transpose()?
+                    .and_then(|files| files.into_iter().last());
+                Ok(Entry {
+                    label: format!("{} ({})", stats.game, format_duration(stats.played)),
+                    cover,
+                    target: Target::Open(Location::Sessions(stats.game)),
+                })
+            })
+            .collect::<Result<_>>()?,
+        Location::Sessions(game) => index
+            .sessions(&SessionFilter {
+                game: Some(game),
+                ..Default::default()
+            })?
+            .into_iter()
+            .rev()
+            .map(|session| {
+                let title = session.start.format("%Y-%m-%d %H:%M").to_string();
+                Ok(Entry {
+                    label: format!(
+                        "{title} ({}, {} screenshots)",
+                        format_duration(session.played),
+                        session.screenshot_count
+                    ),
+                    cover: index
+                        .session_screenshot_files(game, &session.id)?
+                        .into_iter()
+                        .next(),
+                    target: Target::Open(Location::Screenshots {
+                        game: game.clone(),
+                        session: session.id,
+                        title,
+                    }),
+                })
+            })
+            .collect::<Result<_>>()?,
+        Location::Screenshots { game, session, .. } => index
+            .session_screenshot_files(game, session)?
+            .into_iter()
+            .map(|path| Entry {
+                label: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                cover: Some(path.clone()),
+                target: Target::File(path),
+            })
+            .collect(),
+    })
+}
+
+/// Letterboxes the screenshot, or its thumbnail when there is one, into a tile.
+fn tile(path: &Path) -> Result<RgbImage> {
+    let thumbnail = imaging::thumbnail_path(path);
+    let source = if thumbnail.exists() { &thumbnail } else { path };
+    let image = image::open(source)?.thumbnail(TILE.0, TILE.1).to_rgb8();
+    let mut tile = RgbImage::from_pixel(TILE.0, TILE.1, Rgb([32, 32, 32]));
+    let x = (TILE.0 - image.width()) / 2;
+    let y = (TILE.1 - image.height()) / 2;
+    imageops::overlay(&mut tile, &image, x.into(), y.into());
+    Ok(tile)
+}
+
+fn add_tile(images: &w::HIMAGELIST, cover: Option<&Path>) -> Result<u32> {
+    let tile = cover
+        .map(|path| {
+            tile(path).unwrap_or_else(|e| {
+                warn!("Could not load {path:?}: {e:?}");
+                RgbImage::from_pixel(TILE.0, TILE.1, Rgb([32, 32, 32]))
+            })
+        })
+        .unwrap_or_else(|| RgbImage::from_pixel(TILE.0, TILE.1, Rgb([32, 32, 32])));
+    let mut pixels: Vec<u8> = tile
+        .pixels()
+        .flat_map(|p| [p[2], p[1], p[0], 255])
+        .collect();
+    let size = w::SIZE::new(TILE.0 as i32, TILE.1 as i32);
+    let bitmap = w::HBITMAP::CreateBitmap(size, 1, 32, pixels.as_mut_ptr())?;
+    Ok(images.Add(&bitmap, None)?)
+}
+
+impl JournalViewer {
+    fn new(index: Index, target_folder: &Path) -> Self {
+        let window = gui::WindowMain::new(gui::WindowMainOpts {
+            title: "autogamejournal".to_owned(),
+            size: (900, 620),
+            ..Default::default()
+        });
+        let path = widgets::label(&window, "", (10, 13), 670);
+        let calendar = button(&window, "Calendar", (700, 8));
+        let back = button(&window, "< Back", (800, 8));
+        let list = gui::ListView::new(
+            &window,
+            gui::ListViewOpts {
+                position: (10, 40),
+                size: (880, 570),
+                columns: vec![("Name".to_owned(), 200)],
+                list_view_style: co::LVS::ICON
+                    | co::LVS::AUTOARRANGE
+                    | co::LVS::SINGLESEL
+                    | co::LVS::SHOWSELALWAYS
+                    | co::LVS::SHAREIMAGELISTS,
+                ..Default::default()
+            },
+        );
+
+        let viewer = Self {
+            window,
+            path,
+            back,
+            calendar,
+            list,
+            index: Rc::new(index),
+            target_folder: target_folder.to_owned(),
+            state: Default::default(),
+        };
+        viewer.events();
+        viewer
+    }
+
+    fn events(&self) {
+        let viewer = self.clone();
+        self.window.on().wm_create(move |_| {
+            viewer.show(Location::Games, 0);
+            Ok(0)
+        });
+
+        let viewer = self.clone();
+        self.list.on().lvn_item_activate(move |_| {
+            viewer.activate();
+            Ok(())
+        });
+
+        // Enter and Escape reach the window as dialog commands rather than list key presses.
+        let viewer = self.clone();
+        self.window
+            .on()
+            .wm_command_accel_menu(co::DLGID::OK.raw(), move || {
+                viewer.activate();
+                Ok(())
+            });
+
+        let viewer = self.clone();
+        self.window
+            .on()
+            .wm_command_accel_menu(co::DLGID::CANCEL.raw(), move || {
+                if !viewer.up() {
+                    widgets::close(&viewer.window)?;
+                }
+                Ok(())
+            });
+
+        let viewer = self.clone();
+        self.list.on().lvn_key_down(move |key| {
+            if key.wVKey == co::VK::BACK {
+                viewer.up();
+            }
+            Ok(())
+        });
+
+        let viewer = self.clone();
+        self.calendar.on().bn_clicked(move || {
+            let written = calendar::write_calendar(&viewer.target_folder);
+            if let Err(e) = written.and_then(|path| open_file(&path)) {
+                show_error(viewer.window.hwnd(), &e);
+            }
+            Ok(())
+        });
+
+        let viewer = self.clone();
+        self.back.on().bn_clicked(move || {
+            viewer.up();
+            Ok(())
+        });
+    }
+
+    fn show(&self, location: Location, selected: usize) {
+        if let Err(e) = self.load(location, selected) {
+            show_error(self.window.hwnd(), &e);
+        }
+    }
+
+    fn load(&self, location: Location, selected: usize) -> Result<()> {
+        let entries = entries(&self.index, &location)?;
+        let size = w::SIZE::new(TILE.0 as i32, TILE.1 as i32);
+        let images = w::HIMAGELIST::Create(size, co::ILC::COLOR32, entries.len() as i32, 16)?;
+        let items = self.list.items();
+        self.list.set_redraw(false);
+        items.delete_all();
+        self.list.set_image_list(co::LVSIL::NORMAL, &images);
+        for entry in &entries {
+            let icon = add_tile(&images, entry.cover.as_deref())?;
+            items.add(&[&entry.label], Some(icon));
+        }
+        self.list.set_redraw(true);
+
+        let mut state = self.state.borrow_mut();
+        self.path.set_text(&location.breadcrumb());
+        self.back
+            .hwnd()
+            .EnableWindow(!matches!(location, Location::Games));
+        state.location = location;
+        state.entries = entries;
+        state.images = Some(images);
+        if let Some(item) = items.iter().nth(selected) {
+            item.select(true);
+            item.focus();
+            item.ensure_visible();
+        }
+        self.list.hwnd().SetFocus();
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<usize> {
+        let items = self.list.items();
+        let item = items.iter_selected().next()?;
+        Some(item.index() as usize)
+    }
+
+    fn activate(&self) {
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        let mut state = self.state.borrow_mut();
+        let location = match state.entries.get(selected).map(|entry| &entry.target) {
+            Some(Target::Open(location)) => location.clone(),
+            Some(Target::File(path)) => {
+                if let Err(e) = open_file(path) {
+                    show_error(self.window.hwnd(), &e);
+                }
+                return;
+            }
+            None => return,
+        };
+        let parent = std::mem::replace(&mut state.location, location.clone());
+        state.parents.push((parent, selected));
+        drop(state);
+        self.show(location, 0);
+    }
+
+    /// Goes back to the parent location, returns false at the top.
+    fn up(&self) -> bool {
+        let parent = self.state.borrow_mut().parents.pop();
+        let Some((location, selected)) = parent else {
+            return false;
+        };
+        self.show(location, selected);
+        true
+    }
+}
+
+pub fn run(config: &Config) -> Result<()> {
+    let index = Index::open(&config.target_folder)?;
+    widgets::run(&JournalViewer::new(index, &config.target_folder).window)
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+use crate::{upload, State};
+
+const BOUNDARY: &str = "autogamejournal-boundary-7d1f3a";
+
+static QUEUE: Mutex<VecDeque<CaptureEvent>> = Mutex::new(VecDeque::new());
+static QUEUED: Condvar = Condvar::new();
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub include_image: bool,
+}
+
+impl Webhook {
+    pub fn validate(&self) -> Result<()> {
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            bail!(
+                "webhook url {:?} must start with https:// or http://",
+                self.url
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct CaptureEvent {
+    pub game: String,
+    pub timestamp: DateTime<Local>,
+    pub path: PathBuf,
+    pub session_id: Option<String>,
+}
+
+pub fn enqueue(event: CaptureEvent) {
+    QUEUE.lock().unwrap().push_back(event);
+    QUEUED.notify_one();
+}
+
+pub fn multipart_body(
+    json_field: &str,
+    json: &str,
+    file_field: &str,
+    path: &Path,
+) -> Result<Vec<u8>> {
+    let image = fs::read(path)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{json_field}\"\r\nContent-Type: application/json\r\n\r\n{json}\r\n\
+         --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{file_field}\"; filename=\"{file_name}\"\r\nContent-Type: {}\r\n\r\n",
+        upload::content_type(path)
+    )
+    .into_bytes();
+    body.extend_from_slice(&image);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    Ok(body)
+}
+
+pub fn multipart_content_type() -> String {
+    format!("multipart/form-data; boundary={BOUNDARY}")
+}
+
+pub fn http_error(error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(code, response) => {
+            anyhow!("{code}: {}", response.into_string().unwrap_or_default())
+        }
+        e => e.into(),
+    }
+}
+
+fn send(agent: &ureq::Agent, webhook: &Webhook, event: &CaptureEvent) -> Result<()> {
+    let json = serde_json::to_string(event)?;
+    let request = agent.post(&webhook.url);
+    let result = if webhook.include_image {
+        request
+            .set("Content-Type", &multipart_content_type())
+            .send_bytes(&multipart_body("payload", &json, "image", &event.path)?)
+    } else {
+        request
+            .set("Content-Type", "application/json")
+            .send_string(&json)
+    };
+    result.map_err(http_error)?;
+    Ok(())
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build()
+}
+
+fn send_all(agent: &ureq::Agent, webhooks: &[Webhook], event: &CaptureEvent) {
+    for webhook in webhooks {
+        match send(agent, webhook, event) {
+            Ok(()) => debug!(url = %webhook.url, "Sent webhook"),
+            Err(e) => warn!(url = %webhook.url, "Webhook failed: {e:?}"),
+        }
+    }
+}
+
+pub fn drain(webhooks: &[Webhook]) {
+    let agent = agent();
+    while let Some(event) = QUEUE.lock().unwrap().pop_front() {
+        send_all(&agent, webhooks, &event);
+    }
+}
+
+fn next_event() -> CaptureEvent {
+    let mut queue = QUEUE.lock().unwrap();
+    loop {
+        if let Some(event) = queue.pop_front() {
+            return event;
+        }
+        queue = QUEUED.wait(queue).unwrap();
+    }
+}
+
+pub fn webhook_thread(state: Arc<State>) -> ! {
+    let agent = agent();
+    loop {
+        let event = next_event();
+        let webhooks = state.config.read().unwrap().webhooks.clone();
+        send_all(&agent, &webhooks, &event);
+    }
+}
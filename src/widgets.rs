@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use winsafe::{self as w, co, gui, prelude::*};
+
+pub fn label(parent: &impl GuiParent, text: &str, position: (i32, i32), width: u32) -> gui::Label {
+    gui::Label::new(
+        parent,
+        gui::LabelOpts {
+            text: text.to_owned(),
+            position,
+            size: (width, 20),
+            ..Default::default()
+        },
+    )
+}
+
+pub fn edit(parent: &impl GuiParent, text: &str, position: (i32, i32), width: u32) -> gui::Edit {
+    gui::Edit::new(
+        parent,
+        gui::EditOpts {
+            text: text.to_owned(),
+            position,
+            width,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn button(parent: &impl GuiParent, text: &str, position: (i32, i32)) -> gui::Button {
+    gui::Button::new(
+        parent,
+        gui::ButtonOpts {
+            text: text.to_owned(),
+            position,
+            width: 90,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn check_box(
+    parent: &impl GuiParent,
+    text: &str,
+    position: (i32, i32),
+    width: u32,
+) -> gui::CheckBox {
+    gui::CheckBox::new(
+        parent,
+        gui::CheckBoxOpts {
+            text: text.to_owned(),
+            position,
+            size: (width, 20),
+            ..Default::default()
+        },
+    )
+}
+
+pub fn pick_folder(owner: &w::HWND, title: &str) -> Result<Option<String>> {
+    let dialog = w::CoCreateInstance::<w::IFileOpenDialog>(
+        &co::CLSID::FileOpenDialog,
+        None,
+        co::CLSCTX::INPROC_SERVER,
+    )?;
+    dialog.SetOptions(dialog.GetOptions()? | co::FOS::PICKFOLDERS | co::FOS::FORCEFILESYSTEM)?;
+    dialog.SetTitle(title)?;
+    if !dialog.Show(owner)? {
+        return Ok(None);
+    }
+    Ok(Some(
+        dialog.GetResult()?.GetDisplayName(co::SIGDN::FILESYSPATH)?,
+    ))
+}
+
+pub fn show_error(owner: &w::HWND, error: &anyhow::Error) {
+    let _ = owner.MessageBox(&format!("{error:#}"), "autogamejournal", co::MB::ICONERROR);
+}
+
+pub fn close(window: &gui::WindowMain) -> w::AnyResult<()> {
+    window.hwnd().PostMessage(w::msg::wm::Close {})?;
+    Ok(())
+}
+
+pub fn run(window: &gui::WindowMain) -> Result<()> {
+    let _com = w::CoInitializeEx(co::COINIT::APARTMENTTHREADED)?;
+    window.run_main(None).map_err(|e| anyhow!("{e}"))?;
+    Ok(())
+}
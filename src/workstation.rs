@@ -0,0 +1,342 @@
+use anyhow::{bail, Result};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, OnceLock,
+};
+use tracing::info;
+#[cfg(windows)]
+use windows::{
+    core::w,
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        System::{
+            LibraryLoader::GetModuleHandleW,
+            RemoteDesktop::{
+                WTSRegisterSessionNotification, WTSUnRegisterSessionNotification,
+                NOTIFY_FOR_THIS_SESSION,
+            },
+        },
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, GetSystemMetrics,
+            RegisterClassW, TranslateMessage, MSG, PBT_APMRESUMEAUTOMATIC, PBT_APMSUSPEND,
+            SM_REMOTESESSION, WINDOW_EX_STYLE, WINDOW_STYLE, WM_POWERBROADCAST,
+            WM_WTSSESSION_CHANGE, WNDCLASSW, WTS_CONSOLE_CONNECT, WTS_CONSOLE_DISCONNECT,
+            WTS_REMOTE_CONNECT, WTS_REMOTE_DISCONNECT, WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+        },
+    },
+};
+
+use crate::Command;
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static DISCONNECTED: AtomicBool = AtomicBool::new(false);
+static REMOTE: AtomicBool = AtomicBool::new(false);
+static SENDER: OnceLock<mpsc::Sender<Command>> = OnceLock::new();
+
+pub fn suspend_reason() -> Option<&'static str> {
+    if LOCKED.load(Ordering::Relaxed) {
+        Some("workstation locked")
+    } else if DISCONNECTED.load(Ordering::Relaxed) {
+        Some("session disconnected")
+    } else if REMOTE.load(Ordering::Relaxed) {
+        Some("remote session")
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn is_remote_session() -> bool {
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+fn send(command: Command) {
+    if let Some(sender) = SENDER.get() {
+        let _ = sender.send(command);
+    }
+}
+
+#[cfg(windows)]
+fn session_changed(event: u32) {
+    match event {
+        WTS_SESSION_LOCK => LOCKED.store(true, Ordering::Relaxed),
+        WTS_SESSION_UNLOCK => LOCKED.store(false, Ordering::Relaxed),
+        WTS_CONSOLE_CONNECT | WTS_REMOTE_CONNECT => {
+            DISCONNECTED.store(false, Ordering::Relaxed);
+            REMOTE.store(is_remote_session(), Ordering::Relaxed);
+        }
+        WTS_CONSOLE_DISCONNECT | WTS_REMOTE_DISCONNECT => {
+            DISCONNECTED.store(true, Ordering::Relaxed)
+        }
+        _ => return,
+    }
+    match suspend_reason() {
+        Some(reason) => info!("Capturing suspended: {reason}"),
+        None => info!("Capturing resumed"),
+    }
+    send(Command::ForegroundChanged);
+}
+
+#[cfg(windows)]
+fn power_changed(event: u32) {
+    match event {
+        PBT_APMSUSPEND => {
+            info!("System is going to sleep");
+            send(Command::Sleep);
+        }
+        PBT_APMRESUMEAUTOMATIC => {
+            info!("System resumed from sleep");
+            send(Command::Wake);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_WTSSESSION_CHANGE => session_changed(wparam.0 as u32),
+        WM_POWERBROADCAST => power_changed(wparam.0 as u32),
+        _ => {}
+    }
+    DefWindowProcW(window, message, wparam, lparam)
+}
+
+#[cfg(windows)]
+pub fn workstation_thread(sender: mpsc::Sender<Command>) -> Result<()> {
+    if SENDER.set(sender).is_err() {
+        bail!("Session notifications already registered");
+    }
+    REMOTE.store(is_remote_session(), Ordering::Relaxed);
+
+    unsafe {
+        let instance = GetModuleHandleW(None)?;
+        let class_name = w!("autogamejournal-workstation");
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance.into(),
+            lpszClassName: class_name,
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            bail!("RegisterClassW failed");
+        }
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class_name,
+            w!("autogamejournal"),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            HWND::default(),
+            None,
+            instance,
+            None,
+        );
+        if window.0 == 0 {
+            bail!("CreateWindowExW failed");
+        }
+        WTSRegisterSessionNotification(window, NOTIFY_FOR_THIS_SESSION)?;
+
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, HWND::default(), 0, 0).as_bool() {
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+        let _ = WTSUnRegisterSessionNotification(window);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+const LOGIND: &str = "org.freedesktop.login1";
+
+#[cfg(target_os = "linux")]
+fn session_property(
+    connection: &gio::DBusConnection,
+    session: &str,
+    name: &str,
+) -> Result<gio::glib::Variant> {
+    use gio::{glib::ToVariant, Cancellable, DBusCallFlags};
+    let reply = connection.call_sync(
+        Some(LOGIND),
+        session,
+        "org.freedesktop.DBus.Properties",
+        "Get",
+        Some(&("org.freedesktop.login1.Session", name).to_variant()),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        None::<&Cancellable>,
+    )?;
+    reply
+        .child_value(0)
+        .as_variant()
+        .ok_or_else(|| anyhow::anyhow!("Unexpected {name} reply"))
+}
+
+#[cfg(target_os = "linux")]
+fn properties_changed(changed: &gio::glib::Variant) {
+    let changed = gio::glib::VariantDict::new(Some(changed));
+    if let Ok(Some(locked)) = changed.lookup::<bool>("LockedHint") {
+        LOCKED.store(locked, Ordering::Relaxed);
+    } else if let Ok(Some(active)) = changed.lookup::<bool>("Active") {
+        // Switching to another user or console leaves the session inactive.
+        DISCONNECTED.store(!active, Ordering::Relaxed);
+    } else {
+        return;
+    }
+    match suspend_reason() {
+        Some(reason) => info!("Capturing suspended: {reason}"),
+        None => info!("Capturing resumed"),
+    }
+    send(Command::ForegroundChanged);
+}
+
+/// Follows the logind session's lock and active state and the system's sleep over D-Bus.
+#[cfg(target_os = "linux")]
+pub fn workstation_thread(sender: mpsc::Sender<Command>) -> Result<()> {
+    use gio::{
+        glib::{MainContext, MainLoop, ToVariant, VariantTy},
+        BusType, Cancellable, DBusCallFlags, DBusSignalFlags,
+    };
+
+    if SENDER.set(sender).is_err() {
+        bail!("Session notifications already registered");
+    }
+    let connection = gio::bus_get_sync(BusType::System, None::<&Cancellable>)?;
+    let session = connection.call_sync(
+        Some(LOGIND),
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+        "GetSessionByPID",
+        Some(&(std::process::id(),).to_variant()),
+        Some(VariantTy::new("(o)")?),
+        DBusCallFlags::NONE,
+        -1,
+        None::<&Cancellable>,
+    )?;
+    let session = session.child_value(0).str().unwrap_or_default().to_owned();
+    if let Ok(remote) = session_property(&connection, &session, "Remote") {
+        REMOTE.store(remote.get().unwrap_or(false), Ordering::Relaxed);
+    }
+    if let Ok(locked) = session_property(&connection, &session, "LockedHint") {
+        LOCKED.store(locked.get().unwrap_or(false), Ordering::Relaxed);
+    }
+
+    // Signals are delivered on this thread's own context, away from the tray's.
+    let context = MainContext::new();
+    context.with_thread_default(|| {
+        let _sleep = connection.signal_subscribe(
+            Some(LOGIND),
+            Some("org.freedesktop.login1.Manager"),
+            Some("PrepareForSleep"),
+            Some("/org/freedesktop/login1"),
+            None,
+            DBusSignalFlags::NONE,
+            |_, _, _, _, _, parameters| match parameters.get::<(bool,)>() {
+                Some((true,)) => {
+                    info!("System is going to sleep");
+                    send(Command::Sleep);
+                }
+                Some((false,)) => {
+                    info!("System resumed from sleep");
+                    send(Command::Wake);
+                }
+                None => {}
+            },
+        );
+        let _session = connection.signal_subscribe(
+            Some(LOGIND),
+            Some("org.freedesktop.DBus.Properties"),
+            Some("PropertiesChanged"),
+            Some(&session),
+            Some("org.freedesktop.login1.Session"),
+            DBusSignalFlags::NONE,
+            |_, _, _, _, _, parameters| properties_changed(&parameters.child_value(1)),
+        );
+        MainLoop::new(Some(&context), false).run();
+    })?;
+    Ok(())
+}
+
+/// launchd has no lock or sleep notifications without an app run loop, so they are polled.
+#[cfg(target_os = "macos")]
+const SESSION_POLL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How much longer the wall clock may run than the monotonic clock, which stops during sleep,
+/// before a poll counts as a wake from sleep.
+#[cfg(target_os = "macos")]
+const SLEEP_GAP: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGSessionCopyCurrentDictionary() -> core_foundation::dictionary::CFDictionaryRef;
+}
+
+/// Whether the screen is locked and whether the session is on the console, from the window
+/// server's session dictionary.
+#[cfg(target_os = "macos")]
+fn session_state() -> Option<(bool, bool)> {
+    use core_foundation::{
+        base::{CFType, TCFType},
+        boolean::CFBoolean,
+        dictionary::CFDictionary,
+        string::CFString,
+    };
+    let session = unsafe { CGSessionCopyCurrentDictionary() };
+    if session.is_null() {
+        return None;
+    }
+    let session: CFDictionary<CFString, CFType> =
+        unsafe { CFDictionary::wrap_under_create_rule(session) };
+    let flag = |key: &str| {
+        session
+            .find(CFString::new(key))
+            .and_then(|value| value.downcast::<CFBoolean>())
+            .map(bool::from)
+    };
+    Some((
+        flag("CGSSessionScreenIsLocked").unwrap_or(false),
+        flag("kCGSSessionOnConsoleKey").unwrap_or(true),
+    ))
+}
+
+/// Follows the screen lock, fast user switching and the system's sleep by polling.
+#[cfg(target_os = "macos")]
+pub fn workstation_thread(sender: mpsc::Sender<Command>) -> Result<()> {
+    use std::time::{Instant, SystemTime};
+
+    if SENDER.set(sender).is_err() {
+        bail!("Session notifications already registered");
+    }
+    loop {
+        if let Some((locked, on_console)) = session_state() {
+            let was_suspended = suspend_reason();
+            LOCKED.store(locked, Ordering::Relaxed);
+            DISCONNECTED.store(!on_console, Ordering::Relaxed);
+            if suspend_reason() != was_suspended {
+                match suspend_reason() {
+                    Some(reason) => info!("Capturing suspended: {reason}"),
+                    None => info!("Capturing resumed"),
+                }
+                send(Command::ForegroundChanged);
+            }
+        }
+
+        let (instant, system) = (Instant::now(), SystemTime::now());
+        std::thread::sleep(SESSION_POLL);
+        if system.elapsed().unwrap_or_default() > instant.elapsed() + SLEEP_GAP {
+            info!("System resumed from sleep");
+            send(Command::Wake);
+        }
+    }
+}